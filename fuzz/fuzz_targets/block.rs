@@ -0,0 +1,12 @@
+//! Arbitrary bytes given as a whole block, which exercises the footer parser, must error rather
+//! than panic.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let mut decompressed = vec![];
+    if bgzf::Decompressor::new().decompress_block(data, &mut decompressed).is_ok() {
+        assert!(decompressed.len() <= 64 * 1024);
+    }
+});
@@ -0,0 +1,10 @@
+//! Arbitrary bytes given to the header parser must error rather than panic.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(size) = bgzf::core::block_size(data) {
+        assert!(size <= 64 * 1024);
+    }
+});
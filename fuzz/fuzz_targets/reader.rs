@@ -0,0 +1,11 @@
+//! Arbitrary bytes read through a `Reader` must error rather than panic.
+#![no_main]
+
+use std::io::Read;
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let mut decompressed = vec![];
+    let _ = bgzf::Reader::new(data).read_to_end(&mut decompressed);
+});
@@ -0,0 +1,22 @@
+//! Compressed data with arbitrary bytes changed must either error or decompress to the original
+//! data, and never panic.
+#![no_main]
+
+use std::io::Write;
+
+use bgzf::{decompress_all, CompressionLevel, Writer};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: (Vec<u8>, Vec<(u16, u8)>)| {
+    let (data, mutations) = input;
+    let mut writer = Writer::new(vec![], CompressionLevel::new(1).unwrap());
+    writer.write_all(&data).unwrap();
+    let (mut compressed, _) = writer.finish().unwrap();
+    for (position, value) in mutations {
+        let position = position as usize % compressed.len();
+        compressed[position] = value;
+    }
+    if let Ok(decompressed) = decompress_all(&compressed) {
+        assert_eq!(decompressed, data);
+    }
+});
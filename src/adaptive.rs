@@ -0,0 +1,155 @@
+//! Choosing the compression level block by block to meet a throughput target.
+use std::time::Duration;
+
+use crate::CompressionLevel;
+
+/// The number of blocks compressed at a level before it is reconsidered.
+const BLOCKS_PER_DECISION: u32 = 4;
+
+/// The fraction of the target throughput that must be exceeded before trying a higher level.
+const UPGRADE_HEADROOM: f64 = 1.5;
+
+/// The compressed to uncompressed ratio above which data is treated as incompressible, so spending
+/// more time on it is wasted.
+const INCOMPRESSIBLE_RATIO: f64 = 0.95;
+
+/// Settings for a [`crate::Writer`] that picks its compression level per block.
+///
+/// The writer measures how quickly recent blocks compressed and how well, stepping the level down
+/// when it falls behind `target_throughput` or the data isn't compressing, and back up when there
+/// is time to spare.
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{AdaptiveLevel, Writer};
+/// use std::io::Write;
+///
+/// let mut writer = Writer::new(vec![], 6.try_into().unwrap());
+/// // Aim for 200 MB/s of uncompressed input, using levels 1 through 9.
+/// writer.set_adaptive_level(AdaptiveLevel::new(
+///     1.try_into().unwrap(),
+///     9.try_into().unwrap(),
+///     200_000_000,
+/// ));
+/// writer.write_all(b"hello world").unwrap();
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct AdaptiveLevel {
+    min: u8,
+    max: u8,
+    target_throughput: u64,
+}
+
+impl AdaptiveLevel {
+    /// Allow levels from `min` to `max`, aiming for `target_throughput` uncompressed bytes per
+    /// second of compression.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `min` is a higher level than `max`.
+    pub fn new(min: CompressionLevel, max: CompressionLevel, target_throughput: u64) -> Self {
+        let (min, max) = (u8::from(min), u8::from(max));
+        assert!(min <= max, "The minimum compression level must not exceed the maximum");
+        Self { min, max, target_throughput }
+    }
+}
+
+/// Tracks recent blocks for an [`AdaptiveLevel`] and decides when to change level.
+pub(crate) struct AdaptiveController {
+    settings: AdaptiveLevel,
+    level: u8,
+    blocks: u32,
+    uncompressed: u64,
+    compressed: u64,
+    elapsed: Duration,
+}
+
+impl AdaptiveController {
+    /// Start adapting from `level`, clamped to the allowed range.
+    pub(crate) fn new(settings: AdaptiveLevel, level: CompressionLevel) -> Self {
+        let level = u8::from(level).clamp(settings.min, settings.max);
+        Self { settings, level, blocks: 0, uncompressed: 0, compressed: 0, elapsed: Duration::ZERO }
+    }
+
+    /// The level blocks should currently be compressed at.
+    pub(crate) fn level(&self) -> CompressionLevel {
+        CompressionLevel::new(self.level).expect("adaptive levels are within a valid range")
+    }
+
+    /// Record a block that took `elapsed` to compress, returning the new level if it should change.
+    pub(crate) fn observe(
+        &mut self,
+        uncompressed: usize,
+        compressed: usize,
+        elapsed: Duration,
+    ) -> Option<CompressionLevel> {
+        self.blocks += 1;
+        self.uncompressed += uncompressed as u64;
+        self.compressed += compressed as u64;
+        self.elapsed += elapsed;
+        if self.blocks < BLOCKS_PER_DECISION {
+            return None;
+        }
+
+        let seconds = self.elapsed.as_secs_f64();
+        let throughput =
+            if seconds > 0.0 { self.uncompressed as f64 / seconds } else { f64::INFINITY };
+        let ratio = self.compressed as f64 / self.uncompressed.max(1) as f64;
+        let target = self.settings.target_throughput as f64;
+
+        let level = if throughput < target || ratio > INCOMPRESSIBLE_RATIO {
+            self.level.saturating_sub(1).max(self.settings.min)
+        } else if throughput > target * UPGRADE_HEADROOM {
+            (self.level + 1).min(self.settings.max)
+        } else {
+            self.level
+        };
+
+        self.blocks = 0;
+        self.uncompressed = 0;
+        self.compressed = 0;
+        self.elapsed = Duration::ZERO;
+        if level == self.level {
+            None
+        } else {
+            self.level = level;
+            Some(self.level())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use super::*;
+    use crate::{decompress_all, test::test_input, Writer};
+
+    #[test]
+    fn test_adaptive_level() {
+        let input = test_input(1_000_000);
+        let min = CompressionLevel::new(2).unwrap();
+        let max = CompressionLevel::new(8).unwrap();
+
+        // An unreachable target walks the level down to the minimum.
+        let mut compressed = vec![];
+        let mut writer = Writer::new(&mut compressed, CompressionLevel::new(6).unwrap());
+        writer.set_adaptive_level(AdaptiveLevel::new(min, max, u64::MAX));
+        for _ in 0..3 {
+            writer.write_all(&input).unwrap();
+        }
+        assert_eq!(writer.compression_level(), min);
+        drop(writer);
+        assert_eq!(decompress_all(&compressed).unwrap(), input.repeat(3));
+
+        // A trivial target walks it up to the maximum.
+        let mut writer = Writer::new(vec![], CompressionLevel::new(1).unwrap());
+        writer.set_adaptive_level(AdaptiveLevel::new(min, max, 1));
+        assert_eq!(writer.compression_level(), min);
+        for _ in 0..3 {
+            writer.write_all(&input).unwrap();
+        }
+        assert_eq!(writer.compression_level(), max);
+    }
+}
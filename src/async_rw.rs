@@ -0,0 +1,279 @@
+//! Asynchronous BGZF reading and writing for `futures` based runtimes such as async-std and smol.
+//!
+//! Blocks are framed and (de)compressed by the [`crate::core`] state machines, inline in the task
+//! that polls them, so no thread pool or runtime specific API is needed.
+use std::{
+    io,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use futures_io::{AsyncRead, AsyncWrite};
+
+use crate::{
+    core::{into_io_error, Decoder, Encoder},
+    CompressionLevel, BUFSIZE,
+};
+
+/// An asynchronous BGZF reader over a [`futures_io::AsyncRead`].
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{AsyncReader, Writer};
+/// use futures_lite::{future::block_on, AsyncReadExt};
+/// use std::io::Write;
+///
+/// let mut compressed = vec![];
+/// let mut writer = Writer::new(&mut compressed, 2.try_into().unwrap());
+/// writer.write_all(b"hello world").unwrap();
+/// drop(writer);
+///
+/// let mut reader = AsyncReader::new(compressed.as_slice());
+/// let mut decompressed = String::new();
+/// block_on(reader.read_to_string(&mut decompressed)).unwrap();
+/// assert_eq!(decompressed, "hello world");
+/// ```
+pub struct AsyncReader<R> {
+    reader: R,
+    decoder: Decoder,
+    /// Compressed bytes read but not yet pushed to the decoder
+    input: Vec<u8>,
+    /// The range of `input` still to be pushed
+    input_start: usize,
+    input_end: usize,
+    /// The data of the current block
+    output: Vec<u8>,
+    /// How much of `output` has been read
+    output_pos: usize,
+    /// Whether the inner reader is exhausted
+    eof: bool,
+}
+
+impl<R> AsyncReader<R> {
+    /// Create an [`AsyncReader`] over `reader`.
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            decoder: Decoder::new(),
+            input: vec![0; BUFSIZE],
+            input_start: 0,
+            input_end: 0,
+            output: Vec::new(),
+            output_pos: 0,
+            eof: false,
+        }
+    }
+
+    /// Get a reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Unwrap this [`AsyncReader`], returning the inner reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R> AsyncRead for AsyncReader<R>
+where
+    R: AsyncRead + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        loop {
+            if this.output_pos < this.output.len() || buf.is_empty() {
+                let available = &this.output[this.output_pos..];
+                let amount = std::cmp::min(available.len(), buf.len());
+                buf[..amount].copy_from_slice(&available[..amount]);
+                this.output_pos += amount;
+                return Poll::Ready(Ok(amount));
+            }
+
+            let pushed = this.decoder.push(&this.input[this.input_start..this.input_end]);
+            this.input_start += pushed;
+            // Empty blocks decode to nothing, and go round again for the next block.
+            if let Some(data) = this.decoder.decode().map_err(into_io_error)? {
+                this.output.clear();
+                this.output.extend_from_slice(data);
+                this.output_pos = 0;
+                continue;
+            }
+            if this.input_start < this.input_end {
+                continue;
+            }
+            if this.eof {
+                this.decoder.finish().map_err(into_io_error)?;
+                return Poll::Ready(Ok(0));
+            }
+
+            let read = ready!(Pin::new(&mut this.reader).poll_read(cx, &mut this.input))?;
+            this.input_start = 0;
+            this.input_end = read;
+            this.eof = read == 0;
+        }
+    }
+}
+
+/// An asynchronous BGZF writer over a [`futures_io::AsyncWrite`].
+///
+/// Closing the writer writes the last partial block and the EOF marker. Flushing writes the
+/// partial block without an EOF marker.
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{decompress_all, AsyncWriter};
+/// use futures_lite::{future::block_on, AsyncWriteExt};
+///
+/// let mut writer = AsyncWriter::new(vec![], 2.try_into().unwrap());
+/// block_on(async {
+///     writer.write_all(b"hello world").await?;
+///     writer.close().await
+/// })
+/// .unwrap();
+/// assert_eq!(decompress_all(writer.get_ref()).unwrap(), b"hello world");
+/// ```
+pub struct AsyncWriter<W> {
+    writer: W,
+    encoder: Encoder,
+    /// Compressed bytes not yet written to the inner writer
+    pending: Vec<u8>,
+    /// How much of `pending` has been written
+    pending_pos: usize,
+    /// Whether the EOF marker has been queued
+    finished: bool,
+}
+
+impl<W> AsyncWriter<W> {
+    /// Create an [`AsyncWriter`] that compresses to `writer` at `compression_level`.
+    pub fn new(writer: W, compression_level: CompressionLevel) -> Self {
+        Self {
+            writer,
+            encoder: Encoder::new(compression_level),
+            pending: Vec::new(),
+            pending_pos: 0,
+            finished: false,
+        }
+    }
+
+    /// Get a reference to the inner writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Unwrap this [`AsyncWriter`], returning the inner writer.
+    ///
+    /// Anything not yet written by a flush or close is lost.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W> AsyncWriter<W>
+where
+    W: AsyncWrite + Unpin,
+{
+    /// Write out the pending compressed bytes.
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.pending_pos < self.pending.len() {
+            let written = ready!(
+                Pin::new(&mut self.writer).poll_write(cx, &self.pending[self.pending_pos..])
+            )?;
+            if written == 0 {
+                return Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::WriteZero,
+                    "failed to write whole block",
+                )));
+            }
+            self.pending_pos += written;
+        }
+        self.pending.clear();
+        self.pending_pos = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W> AsyncWrite for AsyncWriter<W>
+where
+    W: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        let amount = this.encoder.push(buf);
+        if let Some(block) = this.encoder.encode().map_err(into_io_error)? {
+            this.pending.extend_from_slice(block);
+        }
+        Poll::Ready(Ok(amount))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if let Some(block) = this.encoder.flush().map_err(into_io_error)? {
+            this.pending.extend_from_slice(block);
+        }
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.writer).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if !this.finished {
+            let rest = this.encoder.finish().map_err(into_io_error)?;
+            this.pending.extend_from_slice(rest);
+            this.finished = true;
+        }
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.writer).poll_close(cx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures_lite::{future::block_on, AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+    use crate::{compress_parallel, decompress_all, test::test_input};
+
+    #[test]
+    fn test_round_trip() {
+        let input = test_input(300_000);
+        let level = CompressionLevel::new(3).unwrap();
+
+        let mut writer = AsyncWriter::new(vec![], level);
+        block_on(async {
+            for chunk in input.chunks(10_000) {
+                writer.write_all(chunk).await?;
+            }
+            writer.close().await
+        })
+        .unwrap();
+        let compressed = writer.into_inner();
+        assert_eq!(compressed, compress_parallel(&input, level).unwrap());
+        assert_eq!(decompress_all(&compressed).unwrap(), input);
+
+        let mut reader = AsyncReader::new(compressed.as_slice());
+        let mut decompressed = vec![];
+        block_on(reader.read_to_end(&mut decompressed)).unwrap();
+        assert_eq!(decompressed, input);
+    }
+
+    #[test]
+    fn test_truncated_input() {
+        let compressed =
+            compress_parallel(b"hello world", CompressionLevel::new(3).unwrap()).unwrap();
+        let mut reader = AsyncReader::new(&compressed[..20]);
+        let error = block_on(reader.read_to_end(&mut vec![])).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
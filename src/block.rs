@@ -0,0 +1,242 @@
+//! Typed BGZF block headers and footers.
+use std::io::{self, Read, Write};
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::{
+    check_uncompressed_size, BgzfError, BgzfResult, BGZF_BLOCK_SIZE_OFFSET,
+    BGZF_COMPRESSION_METHOD, BGZF_DEFAULT_MTIME, BGZF_DEFAULT_OS, BGZF_EXTRA_FLAG_LEN,
+    BGZF_FOOTER_SIZE, BGZF_HEADER_SIZE, BGZF_MAGIC_BYTE_A, BGZF_MAGIC_BYTE_B,
+    BGZF_NAME_COMMENT_EXTRA_FLAG, BGZF_SUBFIELD_ID1, BGZF_SUBFIELD_ID2, BGZF_SUBFIELD_LEN,
+    MAX_BGZF_BLOCK_SIZE,
+};
+
+/// The gzip header that starts every BGZF block, with the `BC` extra subfield holding the size of
+/// the block.
+///
+/// The gzip fields a BGZF block must have, the magic bytes, the deflate method, the `FEXTRA` flag
+/// and a lone `BC` subfield, are checked by [`BlockHeader::parse`] and written by
+/// [`BlockHeader::to_bytes`], leaving the fields that vary between blocks.
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{BlockFooter, BlockHeader, Compressor, BGZF_HEADER_SIZE};
+///
+/// let mut block = vec![];
+/// Compressor::new(2.try_into().unwrap()).compress(b"hello world", &mut block).unwrap();
+///
+/// let mut header = BlockHeader::parse(&block).unwrap();
+/// assert_eq!(header.block_size(), block.len());
+/// assert_eq!(BlockFooter::from_block(&block).unwrap().uncompressed_size, 11);
+///
+/// header.mtime = 1_700_000_000;
+/// block[..BGZF_HEADER_SIZE].copy_from_slice(&header.to_bytes());
+/// assert_eq!(BlockHeader::parse(&block).unwrap(), header);
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct BlockHeader {
+    /// The gzip modification time, in seconds since the Unix epoch, or zero
+    pub mtime: u32,
+    /// The gzip extra flags, a hint of the compression level used
+    pub xfl: u8,
+    /// The gzip operating system
+    pub os: u8,
+    /// The size of the whole block, one more than the `BC` subfield's BSIZE
+    block_size: usize,
+}
+
+impl BlockHeader {
+    /// The header of a block of `block_size` bytes in all, with the MTIME, XFL and OS fields this
+    /// crate's writers use for a compression hint of zero.
+    ///
+    /// Fails if `block_size` can't hold a header and footer or is over [`MAX_BGZF_BLOCK_SIZE`].
+    pub fn new(block_size: usize) -> BgzfResult<Self> {
+        if block_size < BGZF_HEADER_SIZE + BGZF_FOOTER_SIZE {
+            return Err(BgzfError::InvalidHeader("Block size smaller than header and footer"));
+        }
+        if block_size > MAX_BGZF_BLOCK_SIZE {
+            return Err(BgzfError::BlockSizeExceeded(block_size, MAX_BGZF_BLOCK_SIZE));
+        }
+        Ok(Self { mtime: BGZF_DEFAULT_MTIME, xfl: 0, os: BGZF_DEFAULT_OS, block_size })
+    }
+
+    /// The size of the whole block, including this header and the footer.
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// The size of the block's deflate compressed data.
+    pub fn compressed_data_size(&self) -> usize {
+        self.block_size - BGZF_HEADER_SIZE - BGZF_FOOTER_SIZE
+    }
+
+    /// Parse the header at the start of `bytes`, which must hold at least [`BGZF_HEADER_SIZE`]
+    /// bytes.
+    pub fn parse(bytes: &[u8]) -> BgzfResult<Self> {
+        let bytes = bytes.get(..BGZF_HEADER_SIZE).ok_or(BgzfError::Truncated)?;
+        if bytes[0] != BGZF_MAGIC_BYTE_A || bytes[1] != BGZF_MAGIC_BYTE_B {
+            return Err(BgzfError::InvalidHeader("Bad gzip magic bytes"));
+        }
+        if bytes[2] != BGZF_COMPRESSION_METHOD {
+            return Err(BgzfError::InvalidHeader("Compression method is not deflate"));
+        }
+        if bytes[3] & BGZF_NAME_COMMENT_EXTRA_FLAG == 0 {
+            return Err(BgzfError::InvalidHeader("Extra field flag not set"));
+        }
+        if LittleEndian::read_u16(&bytes[10..]) != BGZF_EXTRA_FLAG_LEN {
+            return Err(BgzfError::InvalidHeader("Extra field is not a lone BC subfield"));
+        }
+        if bytes[12] != BGZF_SUBFIELD_ID1 || bytes[13] != BGZF_SUBFIELD_ID2 {
+            return Err(BgzfError::InvalidHeader("Bad SID"));
+        }
+        if LittleEndian::read_u16(&bytes[14..]) != BGZF_SUBFIELD_LEN {
+            return Err(BgzfError::InvalidHeader("Bad BC subfield length"));
+        }
+        let bsize = LittleEndian::read_u16(&bytes[BGZF_BLOCK_SIZE_OFFSET..]);
+        let mut header = Self::new(usize::from(bsize) + 1)?;
+        header.mtime = LittleEndian::read_u32(&bytes[4..]);
+        header.xfl = bytes[8];
+        header.os = bytes[9];
+        Ok(header)
+    }
+
+    /// The header as it is written at the start of a block.
+    pub fn to_bytes(&self) -> [u8; BGZF_HEADER_SIZE] {
+        let mut bytes = [0; BGZF_HEADER_SIZE];
+        bytes[0] = BGZF_MAGIC_BYTE_A;
+        bytes[1] = BGZF_MAGIC_BYTE_B;
+        bytes[2] = BGZF_COMPRESSION_METHOD;
+        bytes[3] = BGZF_NAME_COMMENT_EXTRA_FLAG;
+        LittleEndian::write_u32(&mut bytes[4..], self.mtime);
+        bytes[8] = self.xfl;
+        bytes[9] = self.os;
+        LittleEndian::write_u16(&mut bytes[10..], BGZF_EXTRA_FLAG_LEN);
+        bytes[12] = BGZF_SUBFIELD_ID1;
+        bytes[13] = BGZF_SUBFIELD_ID2;
+        LittleEndian::write_u16(&mut bytes[14..], BGZF_SUBFIELD_LEN);
+        // The size is at most MAX_BGZF_BLOCK_SIZE, so this fits.
+        LittleEndian::write_u16(&mut bytes[BGZF_BLOCK_SIZE_OFFSET..], (self.block_size - 1) as u16);
+        bytes
+    }
+
+    /// Read and parse a header from `reader`.
+    pub fn read_from<R>(mut reader: R) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        let mut bytes = [0; BGZF_HEADER_SIZE];
+        reader.read_exact(&mut bytes)?;
+        Self::parse(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Write the header to `writer`.
+    pub fn write_to<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        writer.write_all(&self.to_bytes())
+    }
+}
+
+/// The gzip footer that ends every BGZF block: the CRC32 and size of the uncompressed data.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct BlockFooter {
+    /// The CRC32 of the block's uncompressed data
+    pub crc32: u32,
+    /// The size of the block's uncompressed data, the gzip ISIZE field
+    pub uncompressed_size: u32,
+}
+
+impl BlockFooter {
+    /// Parse the footer at the start of `bytes`, which must hold at least [`BGZF_FOOTER_SIZE`]
+    /// bytes.
+    ///
+    /// Fails if the uncompressed size is more than a block can hold.
+    pub fn parse(bytes: &[u8]) -> BgzfResult<Self> {
+        let bytes = bytes.get(..BGZF_FOOTER_SIZE).ok_or(BgzfError::Truncated)?;
+        let uncompressed_size = LittleEndian::read_u32(&bytes[4..]);
+        check_uncompressed_size(uncompressed_size as usize)?;
+        Ok(Self { crc32: LittleEndian::read_u32(bytes), uncompressed_size })
+    }
+
+    /// Parse the footer at the end of `block`.
+    pub fn from_block(block: &[u8]) -> BgzfResult<Self> {
+        let start = block.len().checked_sub(BGZF_FOOTER_SIZE).ok_or(BgzfError::Truncated)?;
+        Self::parse(&block[start..])
+    }
+
+    /// The footer as it is written at the end of a block.
+    pub fn to_bytes(&self) -> [u8; BGZF_FOOTER_SIZE] {
+        let mut bytes = [0; BGZF_FOOTER_SIZE];
+        LittleEndian::write_u32(&mut bytes, self.crc32);
+        LittleEndian::write_u32(&mut bytes[4..], self.uncompressed_size);
+        bytes
+    }
+
+    /// Read and parse a footer from `reader`.
+    pub fn read_from<R>(mut reader: R) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        let mut bytes = [0; BGZF_FOOTER_SIZE];
+        reader.read_exact(&mut bytes)?;
+        Self::parse(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Write the footer to `writer`.
+    pub fn write_to<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        writer.write_all(&self.to_bytes())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{block_overhead, BGZF_EOF};
+
+    #[test]
+    fn test_block_header_and_footer() {
+        let header = BlockHeader::parse(BGZF_EOF).unwrap();
+        assert_eq!((header.mtime, header.xfl, header.os), (0, 0, 255));
+        assert_eq!(header.block_size(), BGZF_EOF.len());
+        assert_eq!(header.compressed_data_size(), 2);
+        let footer = BlockFooter::from_block(BGZF_EOF).unwrap();
+        assert_eq!(footer, BlockFooter { crc32: 0, uncompressed_size: 0 });
+
+        // A block assembled from its parts is the EOF marker.
+        let mut block = vec![];
+        BlockHeader::new(BGZF_EOF.len()).unwrap().write_to(&mut block).unwrap();
+        block.extend_from_slice(&[0x03, 0x00]);
+        footer.write_to(&mut block).unwrap();
+        assert_eq!(block, BGZF_EOF);
+
+        let mut reader = BGZF_EOF;
+        assert_eq!(BlockHeader::read_from(&mut reader).unwrap(), header);
+        reader = &reader[2..];
+        assert_eq!(BlockFooter::read_from(&mut reader).unwrap(), footer);
+
+        for (offset, message) in [
+            (0, "Bad gzip magic bytes"),
+            (2, "Compression method is not deflate"),
+            (3, "Extra field flag not set"),
+            (10, "Extra field is not a lone BC subfield"),
+            (12, "Bad SID"),
+            (14, "Bad BC subfield length"),
+        ] {
+            let mut bad = BGZF_EOF.to_vec();
+            bad[offset] ^= 0x04;
+            let error = BlockHeader::parse(&bad).unwrap_err();
+            assert_eq!(error.to_string(), format!("Invalid block header: {}", message));
+        }
+        assert!(matches!(BlockHeader::parse(&BGZF_EOF[..17]), Err(BgzfError::Truncated)));
+        assert!(BlockHeader::new(block_overhead() - 1).is_err());
+        assert!(BlockHeader::new(MAX_BGZF_BLOCK_SIZE + 1).is_err());
+        assert_eq!(BlockHeader::new(MAX_BGZF_BLOCK_SIZE).unwrap().to_bytes()[16..], [0xff, 0xff]);
+        let too_large = BlockFooter { crc32: 0, uncompressed_size: 70_000 }.to_bytes();
+        assert!(BlockFooter::parse(&too_large).is_err());
+    }
+}
@@ -0,0 +1,139 @@
+//! Policies for where the [`crate::Writer`] ends each full block, to keep records whole.
+
+/// Picks where a [`crate::Writer`] ends each full block, so that blocks hold whole records.
+///
+/// When a block's worth of data is pending, the writer passes it to the policy as `window` and
+/// puts the first [`BoundaryPolicy::split_point`] bytes in the block, leaving the rest for the
+/// next one. Returning `None`, zero, or more than `window.len()` puts the whole window in the
+/// block, as for a record longer than a block. Flushes write out everything buffered without
+/// asking the policy.
+///
+/// Any `FnMut(&[u8]) -> Option<usize>` closure implements this trait.
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{ChecksumManifest, Writer};
+/// use std::io::{Cursor, Write};
+///
+/// // Records of a u16 length then that many bytes.
+/// let mut writer = Writer::new(vec![], 2.try_into().unwrap());
+/// writer.set_boundary_policy(|window: &[u8]| {
+///     let mut end = 0;
+///     while let Some(len) = window.get(end..end + 2) {
+///         let next = end + 2 + u16::from_le_bytes([len[0], len[1]]) as usize;
+///         if next > window.len() {
+///             break;
+///         }
+///         end = next;
+///     }
+///     Some(end)
+/// });
+/// for i in 0..10_000_u16 {
+///     writer.write_all(&10_u16.to_le_bytes()).unwrap();
+///     writer.write_all(format!("{:010}", i).as_bytes()).unwrap();
+/// }
+/// let (compressed, _) = writer.finish().unwrap();
+///
+/// let blocks = ChecksumManifest::from_reader(Cursor::new(compressed)).unwrap();
+/// assert!(blocks.blocks().iter().all(|block| block.uncompressed_size % 12 == 0));
+/// ```
+pub trait BoundaryPolicy: Send {
+    /// The number of bytes of `window`, the next block's worth of data, to put in the block.
+    fn split_point(&mut self, window: &[u8]) -> Option<usize>;
+}
+
+impl<F> BoundaryPolicy for F
+where
+    F: FnMut(&[u8]) -> Option<usize> + Send,
+{
+    fn split_point(&mut self, window: &[u8]) -> Option<usize> {
+        self(window)
+    }
+}
+
+/// End each block after its last newline, for line oriented formats such as VCF, BED, and SAM.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct LineBoundary;
+
+impl BoundaryPolicy for LineBoundary {
+    fn split_point(&mut self, window: &[u8]) -> Option<usize> {
+        window.iter().rposition(|&b| b == b'\n').map(|end| end + 1)
+    }
+}
+
+/// End each block after the last whole group of a fixed number of lines, for formats with
+/// multi-line records such as FASTQ.
+///
+/// Lines are counted from the start of each block, so this relies on blocks starting at a
+/// record, as they do unless a record is longer than a block or a flush ends a block part way
+/// through one.
+#[derive(Debug, Copy, Clone)]
+pub struct LineGroupBoundary {
+    lines: usize,
+}
+
+impl LineGroupBoundary {
+    /// Records of `lines` lines each.
+    ///
+    /// # Panics
+    ///
+    /// - If `lines` is zero
+    pub fn new(lines: usize) -> Self {
+        assert!(lines > 0, "Records must have at least one line");
+        Self { lines }
+    }
+
+    /// Four line FASTQ records.
+    pub fn fastq() -> Self {
+        Self::new(4)
+    }
+}
+
+impl BoundaryPolicy for LineGroupBoundary {
+    fn split_point(&mut self, window: &[u8]) -> Option<usize> {
+        window
+            .iter()
+            .enumerate()
+            .filter(|(_, &b)| b == b'\n')
+            .enumerate()
+            .filter(|(line, _)| (line + 1) % self.lines == 0)
+            .last()
+            .map(|(_, (end, _))| end + 1)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use super::*;
+    use crate::{decompress_all, ChecksumManifest, CompressionLevel, Writer};
+
+    #[test]
+    fn test_fastq_boundary_policy() {
+        let mut input = vec![];
+        for i in 0..20_000_u32 {
+            let bases = "ACGT".repeat(10 + (i % 13) as usize);
+            let quals = "I".repeat(bases.len());
+            input.extend_from_slice(format!("@read{}\n{}\n+\n{}\n", i, bases, quals).as_bytes());
+        }
+        let mut writer = Writer::new(vec![], CompressionLevel::new(3).unwrap());
+        writer.set_boundary_policy(LineGroupBoundary::fastq());
+        writer.write_all(&input).unwrap();
+        let (compressed, _) = writer.finish().unwrap();
+        assert_eq!(decompress_all(&compressed).unwrap(), input);
+
+        let manifest = ChecksumManifest::from_reader(std::io::Cursor::new(&compressed)).unwrap();
+        assert!(manifest.blocks().len() > 3);
+        let mut rest = input.as_slice();
+        for block in manifest.blocks() {
+            let (data, remaining) = rest.split_at(block.uncompressed_size as usize);
+            rest = remaining;
+            if !data.is_empty() {
+                assert!(data.starts_with(b"@read"));
+                assert_eq!(data.iter().filter(|&&b| b == b'\n').count() % 4, 0);
+            }
+        }
+    }
+}
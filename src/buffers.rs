@@ -0,0 +1,141 @@
+//! A pool of block sized buffers shared by readers, writers, and parallel workers.
+use std::{
+    ops::{Deref, DerefMut},
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use crate::MAX_BGZF_BLOCK_SIZE;
+
+/// The capacity of the buffers handed out, room for any whole block plus the slack compression
+/// works in.
+const BUFFER_CAPACITY: usize = MAX_BGZF_BLOCK_SIZE + MAX_BGZF_BLOCK_SIZE / 8;
+
+/// How many idle buffers the global pool keeps.
+const GLOBAL_MAX_IDLE: usize = 256;
+
+/// A thread safe pool of reusable buffers big enough for any compressed or uncompressed block.
+///
+/// [`Reader`](crate::Reader), [`Writer`](crate::Writer), and the parallel compressors take their
+/// block buffers from [`BufferPool::global`] and return them when done, so that opening and
+/// closing many streams, or compressing many blocks on a pool, doesn't allocate a fresh 64 KiB
+/// buffer each time. A pool keeps at most a set number of idle buffers and frees the rest.
+///
+/// A [`BufferPool`] is a cheap handle: clones share the same buffers.
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::BufferPool;
+///
+/// let pool = BufferPool::new(4);
+/// let mut buffer = pool.get();
+/// buffer.extend_from_slice(b"hello world");
+/// pool.put(buffer);
+/// assert_eq!(pool.idle(), 1);
+/// assert!(pool.get().is_empty());
+/// ```
+#[derive(Clone)]
+pub struct BufferPool {
+    inner: Arc<PoolInner>,
+}
+
+struct PoolInner {
+    idle: Mutex<Vec<Vec<u8>>>,
+    max_idle: usize,
+}
+
+impl BufferPool {
+    /// Create an empty pool that keeps up to `max_idle` idle buffers.
+    pub fn new(max_idle: usize) -> Self {
+        Self { inner: Arc::new(PoolInner { idle: Mutex::new(Vec::new()), max_idle }) }
+    }
+
+    /// The pool shared by everything in this crate, which keeps up to 256 idle buffers.
+    pub fn global() -> &'static BufferPool {
+        static GLOBAL: OnceLock<BufferPool> = OnceLock::new();
+        GLOBAL.get_or_init(|| BufferPool::new(GLOBAL_MAX_IDLE))
+    }
+
+    /// Take an empty buffer with room for a whole block, reusing an idle one if there is one.
+    pub fn get(&self) -> Vec<u8> {
+        self.lock().pop().unwrap_or_else(|| Vec::with_capacity(BUFFER_CAPACITY))
+    }
+
+    /// Return `buffer` to the pool to be reused.
+    ///
+    /// The buffer is freed instead if the pool is full, or if its capacity is too small for a
+    /// block or much larger than one, so that the pool doesn't hold on to outsized allocations.
+    pub fn put(&self, mut buffer: Vec<u8>) {
+        if buffer.capacity() < BUFFER_CAPACITY || buffer.capacity() > 2 * BUFFER_CAPACITY {
+            return;
+        }
+        let mut idle = self.lock();
+        if idle.len() < self.inner.max_idle {
+            buffer.clear();
+            idle.push(buffer);
+        }
+    }
+
+    /// The number of buffers waiting in the pool to be reused.
+    pub fn idle(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Lock the idle buffers, ignoring poisoning since a buffer is valid between uses.
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Vec<u8>>> {
+        self.inner.idle.lock().unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+/// A buffer from the [`BufferPool::global`] pool that goes back to it when dropped.
+#[derive(Clone)]
+pub(crate) struct PooledBuffer(Vec<u8>);
+
+impl PooledBuffer {
+    pub(crate) fn new() -> Self {
+        Self(BufferPool::global().get())
+    }
+}
+
+impl Deref for PooledBuffer {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        &self.0
+    }
+}
+
+impl DerefMut for PooledBuffer {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.0
+    }
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        BufferPool::global().put(std::mem::take(&mut self.0));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_buffers_are_reused_up_to_the_limit() {
+        let pool = BufferPool::new(2);
+        let buffers: Vec<Vec<u8>> = (0..3).map(|_| pool.get()).collect();
+        let first = buffers[0].as_ptr();
+        for buffer in buffers {
+            pool.put(buffer);
+        }
+        assert_eq!(pool.idle(), 2);
+        pool.put(Vec::with_capacity(10));
+        pool.put(Vec::with_capacity(10 * BUFFER_CAPACITY));
+
+        let (a, b) = (pool.get(), pool.get());
+        assert!(a.as_ptr() == first || b.as_ptr() == first);
+        assert_eq!(pool.idle(), 0);
+        assert!(pool.get().capacity() >= BUFFER_CAPACITY);
+    }
+}
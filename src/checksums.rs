@@ -0,0 +1,186 @@
+//! Manifests of the CRC32 and uncompressed size in every block's footer.
+use std::{
+    fs::File,
+    io::{self, BufRead, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use crate::{check_uncompressed_size, core::block_size, read_header, BGZF_HEADER_SIZE};
+
+/// The footer values of one block.
+///
+/// Together the CRC32 and size make a cheap fingerprint of the block's uncompressed data.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct BlockChecksum {
+    /// The offset of the block in the compressed stream
+    pub compressed_offset: u64,
+    /// The CRC32 of the block's uncompressed data
+    pub crc32: u32,
+    /// The number of uncompressed bytes in the block
+    pub uncompressed_size: u32,
+}
+
+/// The [`BlockChecksum`] of every block of BGZF data, including empty blocks such as the EOF
+/// marker.
+///
+/// A manifest is read from the block footers alone, without decompressing anything, and can be
+/// saved as tab separated text to re-check later that the blocks are unchanged.
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{ChecksumManifest, Writer};
+/// use std::io::{Cursor, Write};
+///
+/// let mut writer = Writer::new(vec![], 2.try_into().unwrap());
+/// writer.write_all(b"hello world").unwrap();
+/// let (compressed, _) = writer.finish().unwrap();
+///
+/// let manifest = ChecksumManifest::from_reader(Cursor::new(&compressed)).unwrap();
+/// assert_eq!(manifest.blocks().len(), 2);
+/// assert_eq!(manifest.blocks()[0].crc32, 0x0d4a1185);
+/// assert_eq!(manifest.blocks()[0].uncompressed_size, 11);
+///
+/// let mut tsv = vec![];
+/// manifest.write_tsv(&mut tsv).unwrap();
+/// assert_eq!(ChecksumManifest::read_tsv(tsv.as_slice()).unwrap(), manifest);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChecksumManifest {
+    blocks: Vec<BlockChecksum>,
+}
+
+impl ChecksumManifest {
+    /// The checksums of the blocks, in order.
+    pub fn blocks(&self) -> &[BlockChecksum] {
+        &self.blocks
+    }
+
+    /// Build the manifest of the BGZF file at `path` from its block footers.
+    pub fn from_path<P>(path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Self::from_reader(File::open(path)?)
+    }
+
+    /// Build the manifest of the BGZF data in `reader` from its block footers.
+    ///
+    /// Only each block's header and footer are read, the compressed data is skipped over.
+    pub fn from_reader<R>(mut reader: R) -> io::Result<Self>
+    where
+        R: Read + Seek,
+    {
+        let mut blocks = vec![];
+        let mut header = [0; BGZF_HEADER_SIZE];
+        let mut compressed_offset = 0;
+        loop {
+            match read_header(&mut reader, &mut header)? {
+                0 => break,
+                BGZF_HEADER_SIZE => {}
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "Truncated BGZF block",
+                    ))
+                }
+            }
+            let size =
+                block_size(&header).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            // Skip to the CRC32 and ISIZE fields at the very end of the block.
+            reader.seek(SeekFrom::Current((size - BGZF_HEADER_SIZE - 8) as i64))?;
+            let crc32 = reader.read_u32::<LittleEndian>()?;
+            let uncompressed_size = reader.read_u32::<LittleEndian>()?;
+            check_uncompressed_size(uncompressed_size as usize)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+            blocks.push(BlockChecksum { compressed_offset, crc32, uncompressed_size });
+            compressed_offset += size as u64;
+        }
+        Ok(Self { blocks })
+    }
+
+    /// Read a manifest written by [`ChecksumManifest::write_tsv`].
+    pub fn read_tsv<R>(reader: R) -> io::Result<Self>
+    where
+        R: BufRead,
+    {
+        let mut blocks = vec![];
+        for (number, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.starts_with('#') || line.is_empty() {
+                continue;
+            }
+            let invalid = || {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid checksum manifest line {}: {}", number + 1, line),
+                )
+            };
+            let mut fields = line.split('\t');
+            let mut next = || fields.next().ok_or_else(invalid);
+            let compressed_offset = next()?.parse().map_err(|_| invalid())?;
+            let crc32 = u32::from_str_radix(next()?, 16).map_err(|_| invalid())?;
+            let uncompressed_size = next()?.parse().map_err(|_| invalid())?;
+            blocks.push(BlockChecksum { compressed_offset, crc32, uncompressed_size });
+        }
+        Ok(Self { blocks })
+    }
+
+    /// Write the manifest as tab separated text, one block per line after a header line, with
+    /// the CRC32 in hexadecimal.
+    pub fn write_tsv<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        writeln!(writer, "#compressed_offset\tcrc32\tuncompressed_size")?;
+        for block in &self.blocks {
+            writeln!(
+                writer,
+                "{}\t{:08x}\t{}",
+                block.compressed_offset, block.crc32, block.uncompressed_size
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        compress_parallel, test::test_input, CompressionLevel, Index, BGZF_BLOCK_SIZE, BGZF_EOF,
+    };
+
+    #[test]
+    fn test_checksum_manifest_matches_blocks() {
+        let input = test_input(200_000);
+        let compressed = compress_parallel(&input, CompressionLevel::new(3).unwrap()).unwrap();
+
+        let manifest = ChecksumManifest::from_reader(io::Cursor::new(&compressed)).unwrap();
+        let blocks = manifest.blocks();
+        assert_eq!(blocks.len(), 5);
+        assert_eq!(blocks.last().unwrap().uncompressed_size, 0);
+        for (block, data) in blocks.iter().zip(input.chunks(BGZF_BLOCK_SIZE)) {
+            let mut crc = libdeflater::Crc::new();
+            crc.update(data);
+            assert_eq!(block.crc32, crc.sum());
+            assert_eq!(block.uncompressed_size as usize, data.len());
+        }
+        assert_eq!(
+            blocks[1].compressed_offset,
+            Index::from_reader(io::Cursor::new(&compressed)).unwrap().entries()[0]
+                .compressed_offset
+        );
+
+        let mut tsv = vec![];
+        manifest.write_tsv(&mut tsv).unwrap();
+        assert_eq!(ChecksumManifest::read_tsv(tsv.as_slice()).unwrap(), manifest);
+        assert!(ChecksumManifest::read_tsv(&b"0\tnot hex\t10\n"[..]).is_err());
+
+        let truncated = &compressed[..compressed.len() - BGZF_EOF.len() - 5];
+        assert!(ChecksumManifest::from_reader(io::Cursor::new(truncated)).is_err());
+    }
+}
@@ -0,0 +1,281 @@
+//! Reading and writing BGZF data in cloud object storage through the `object_store` crate.
+//!
+//! `object_store` is asynchronous, so the source and sink here run its requests on a tokio
+//! runtime through a [`Handle`], and must be used from outside of that runtime's tasks, for
+//! example on a plain thread or inside [`tokio::task::spawn_blocking`].
+use std::{io, sync::Arc};
+
+use bytes::Bytes;
+use object_store::{path::Path, MultipartUpload, ObjectStore, PutResult, WriteMultipart};
+use tokio::runtime::Handle;
+
+use crate::{
+    core::block_size, BlockSink, BlockSource, Index, IndexedReader, BGZF_HEADER_SIZE,
+    MAX_BGZF_BLOCK_SIZE,
+};
+
+/// How many bytes [`ObjectStoreSource`] fetches at a time by default.
+const DEFAULT_READAHEAD: usize = 1024 * 1024;
+
+/// The size of each part [`ObjectStoreSink`] uploads by default.
+const DEFAULT_PART_SIZE: usize = 10 * 1024 * 1024;
+
+/// How many parts [`ObjectStoreSink`] uploads at once before waiting.
+const MAX_CONCURRENT_PARTS: usize = 8;
+
+/// Convert an `object_store` error into an [`io::Error`].
+fn into_io_error(error: object_store::Error) -> io::Error {
+    match error {
+        object_store::Error::NotFound { .. } => io::Error::new(io::ErrorKind::NotFound, error),
+        e => io::Error::new(io::ErrorKind::Other, e),
+    }
+}
+
+/// A [`BlockSource`] that fetches blocks from an object in an [`ObjectStore`].
+///
+/// Requests are coalesced by fetching a readahead window of many blocks at once (1 MiB by
+/// default), from which following blocks are served without another request.
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{IndexedReader, TeeWriter};
+/// use object_store::{memory::InMemory, path::Path, ObjectStore};
+/// use std::io::{Read, Write};
+/// use std::sync::Arc;
+///
+/// let (mut data, mut gzi) = (vec![], vec![]);
+/// let mut writer = TeeWriter::new(&mut data, &mut gzi, 2.try_into().unwrap());
+/// writer.write_all(b"hello world").unwrap();
+/// writer.finish().unwrap();
+///
+/// let runtime = tokio::runtime::Runtime::new().unwrap();
+/// let store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+/// runtime.block_on(async {
+///     store.put(&Path::from("hello.gz"), data.into()).await?;
+///     store.put(&Path::from("hello.gz.gzi"), gzi.into()).await
+/// })
+/// .unwrap();
+///
+/// let path = Path::from("hello.gz");
+/// let mut reader =
+///     IndexedReader::from_object_store(store, &path, runtime.handle().clone()).unwrap();
+/// let mut decompressed = String::new();
+/// reader.read_to_string(&mut decompressed).unwrap();
+/// assert_eq!(decompressed, "hello world");
+/// ```
+pub struct ObjectStoreSource {
+    store: Arc<dyn ObjectStore>,
+    path: Path,
+    runtime: Handle,
+    /// The size of the object
+    size: u64,
+    /// How many bytes to fetch at a time
+    readahead: usize,
+    /// The last fetched bytes, starting at `window_offset`
+    window: Bytes,
+    window_offset: u64,
+}
+
+impl ObjectStoreSource {
+    /// Create an [`ObjectStoreSource`] for the object at `path`, making requests on `runtime`.
+    ///
+    /// This looks up the size of the object.
+    pub fn new(store: Arc<dyn ObjectStore>, path: &Path, runtime: Handle) -> io::Result<Self> {
+        let size = runtime.block_on(store.head(path)).map_err(into_io_error)?.size;
+        Ok(Self {
+            store,
+            path: path.clone(),
+            runtime,
+            size,
+            readahead: DEFAULT_READAHEAD,
+            window: Bytes::new(),
+            window_offset: 0,
+        })
+    }
+
+    /// Fetch `bytes` at a time, which is raised to the largest block size if smaller.
+    pub fn set_readahead(&mut self, bytes: usize) {
+        self.readahead = std::cmp::max(bytes, MAX_BGZF_BLOCK_SIZE);
+    }
+
+    /// The size of the object in bytes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// The whole block at `offset` if it is within the window.
+    fn block_in_window(&self, offset: u64) -> io::Result<Option<&[u8]>> {
+        let start = match offset.checked_sub(self.window_offset) {
+            Some(start) if start < self.window.len() as u64 => start as usize,
+            _ => return Ok(None),
+        };
+        let rest = &self.window[start..];
+        if rest.len() < BGZF_HEADER_SIZE {
+            return Ok(None);
+        }
+        let size = block_size(rest).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(rest.get(..size))
+    }
+}
+
+impl BlockSource for ObjectStoreSource {
+    fn fetch_block(&mut self, offset: u64, buf: &mut Vec<u8>) -> io::Result<()> {
+        if self.block_in_window(offset)?.is_none() {
+            let end = std::cmp::min(offset + self.readahead as u64, self.size);
+            if offset >= end {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "Block offset is past the end of the object",
+                ));
+            }
+            let fetch = self.store.get_range(&self.path, offset..end);
+            self.window = self.runtime.block_on(fetch).map_err(into_io_error)?;
+            self.window_offset = offset;
+        }
+        buf.clear();
+        // A block cut short by the end of the object is passed on for the reader to report.
+        match self.block_in_window(offset)? {
+            Some(block) => buf.extend_from_slice(block),
+            None => buf.extend_from_slice(&self.window),
+        }
+        Ok(())
+    }
+}
+
+impl IndexedReader<ObjectStoreSource> {
+    /// Open the BGZF object at `path` in `store` with the index in the `.gzi` object next to it,
+    /// fetching blocks on `runtime` as they are read.
+    pub fn from_object_store(
+        store: Arc<dyn ObjectStore>,
+        path: &Path,
+        runtime: Handle,
+    ) -> io::Result<Self> {
+        let gzi_path = Path::from(format!("{}.gzi", path));
+        let gzi = runtime
+            .block_on(async { store.get(&gzi_path).await?.bytes().await })
+            .map_err(into_io_error)?;
+        let index = Index::read_gzi(gzi.as_ref())?;
+        Ok(Self::new(ObjectStoreSource::new(store, path, runtime)?, index))
+    }
+}
+
+/// A [`BlockSink`] that streams blocks to an object in an [`ObjectStore`] as a multipart upload.
+///
+/// Blocks are gathered into parts of 10 MiB by default, and up to eight parts are uploaded at
+/// once. Object stores put a lower limit on the size of all but the last part, so flushing only
+/// waits for the parts already started; the last part is uploaded and the object created by
+/// [`ObjectStoreSink::complete`]. Dropping the sink without completing it leaves the multipart
+/// upload unfinished, see [`ObjectStoreSink::abort`].
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{decompress_all, ObjectStoreSink, Writer};
+/// use object_store::{memory::InMemory, path::Path, ObjectStore};
+/// use std::io::Write;
+/// use std::sync::Arc;
+///
+/// let runtime = tokio::runtime::Runtime::new().unwrap();
+/// let store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+/// let path = Path::from("hello.gz");
+///
+/// let sink = ObjectStoreSink::new(store.clone(), &path, runtime.handle().clone()).unwrap();
+/// let mut writer = Writer::new(sink, 2.try_into().unwrap());
+/// writer.write_all(b"hello world").unwrap();
+/// let (sink, _) = writer.finish().unwrap();
+/// sink.complete().unwrap();
+///
+/// let stored = runtime.block_on(async { store.get(&path).await?.bytes().await }).unwrap();
+/// assert_eq!(decompress_all(&stored).unwrap(), b"hello world");
+/// ```
+pub struct ObjectStoreSink {
+    upload: WriteMultipart,
+    runtime: Handle,
+}
+
+impl ObjectStoreSink {
+    /// Start a multipart upload to `path` in `store`, making requests on `runtime`.
+    pub fn new(store: Arc<dyn ObjectStore>, path: &Path, runtime: Handle) -> io::Result<Self> {
+        Self::with_part_size(store, path, runtime, DEFAULT_PART_SIZE)
+    }
+
+    /// Start a multipart upload to `path` in `store` with parts of `part_size` bytes.
+    pub fn with_part_size(
+        store: Arc<dyn ObjectStore>,
+        path: &Path,
+        runtime: Handle,
+        part_size: usize,
+    ) -> io::Result<Self> {
+        let upload: Box<dyn MultipartUpload> =
+            runtime.block_on(store.put_multipart(path)).map_err(into_io_error)?;
+        Ok(Self { upload: WriteMultipart::new_with_chunk_size(upload, part_size), runtime })
+    }
+
+    /// Upload the last part and complete the upload, creating the object.
+    pub fn complete(self) -> io::Result<PutResult> {
+        self.runtime.block_on(self.upload.finish()).map_err(into_io_error)
+    }
+
+    /// Abort the upload, cleaning up any parts already uploaded.
+    pub fn abort(self) -> io::Result<()> {
+        self.runtime.block_on(self.upload.abort()).map_err(into_io_error)
+    }
+}
+
+impl BlockSink for ObjectStoreSink {
+    fn emit_block(&mut self, block: &[u8]) -> io::Result<()> {
+        self.runtime
+            .block_on(self.upload.wait_for_capacity(MAX_CONCURRENT_PARTS))
+            .map_err(into_io_error)?;
+        // Parts are uploaded by tasks spawned on the runtime as they fill.
+        let _guard = self.runtime.enter();
+        self.upload.write(block);
+        Ok(())
+    }
+
+    fn flush_blocks(&mut self) -> io::Result<()> {
+        self.runtime.block_on(self.upload.wait_for_capacity(0)).map_err(into_io_error)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Read, Seek, SeekFrom, Write};
+
+    use object_store::memory::InMemory;
+
+    use super::*;
+    use crate::{compress_parallel, test::test_input, CompressionLevel, Writer};
+
+    #[test]
+    fn test_round_trip_through_object_store() {
+        let input = test_input(300_000);
+        let level = CompressionLevel::new(3).unwrap();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let path = Path::from("test.gz");
+
+        let sink =
+            ObjectStoreSink::with_part_size(store.clone(), &path, runtime.handle().clone(), 50_000)
+                .unwrap();
+        let mut writer = Writer::new(sink, level);
+        writer.build_index();
+        writer.write_all(&input).unwrap();
+        writer.write_eof().unwrap();
+        let index = writer.take_index().unwrap();
+        let (sink, _) = writer.finish().unwrap();
+        sink.complete().unwrap();
+
+        let stored = runtime.block_on(async { store.get(&path).await?.bytes().await }).unwrap();
+        assert_eq!(stored, compress_parallel(&input, level).unwrap());
+
+        let mut source = ObjectStoreSource::new(store, &path, runtime.handle().clone()).unwrap();
+        source.set_readahead(0);
+        let mut reader = IndexedReader::new(source, index);
+        reader.seek(SeekFrom::Start(100_000)).unwrap();
+        let mut buf = vec![0; 150_000];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, &input[100_000..250_000]);
+    }
+}
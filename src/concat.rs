@@ -0,0 +1,101 @@
+//! Block-level concatenation of BGZF files.
+use std::io::{self, Read, Write};
+
+use crate::{BGZF_EOF, BUFSIZE};
+
+/// Concatenate the BGZF streams in `inputs` into `output` without decompressing them.
+///
+/// Blocks are copied verbatim, except that each input's trailing EOF marker is dropped and a single
+/// one is written at the end, so the output is a valid BGZF file of all the inputs' data in order.
+/// The index of the output can be made from the inputs' indexes with [`crate::Index::merge`].
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{concat, decompress_all, Writer};
+/// use std::io::Write;
+///
+/// let mut shards = vec![];
+/// for text in [&b"hello "[..], &b"world"[..]] {
+///     let mut writer = Writer::new(vec![], 2.try_into().unwrap());
+///     writer.write_all(text).unwrap();
+///     shards.push(writer.finish().unwrap().0);
+/// }
+///
+/// let mut combined = vec![];
+/// concat(shards.iter().map(|shard| shard.as_slice()), &mut combined).unwrap();
+/// assert_eq!(decompress_all(&combined).unwrap(), b"hello world");
+/// ```
+pub fn concat<I, R, W>(inputs: I, mut output: W) -> io::Result<()>
+where
+    I: IntoIterator<Item = R>,
+    R: Read,
+    W: Write,
+{
+    let mut buffer = vec![0; BUFSIZE];
+    for mut input in inputs {
+        // Hold back the last bytes seen until it is known whether they are the EOF marker.
+        let mut held = 0;
+        loop {
+            let n = match input.read(&mut buffer[held..]) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            let filled = held + n;
+            if filled > BGZF_EOF.len() {
+                let keep = filled - BGZF_EOF.len();
+                output.write_all(&buffer[..keep])?;
+                buffer.copy_within(keep..filled, 0);
+                held = BGZF_EOF.len();
+            } else {
+                held = filled;
+            }
+        }
+        if &buffer[..held] != BGZF_EOF {
+            output.write_all(&buffer[..held])?;
+        }
+    }
+    output.write_all(BGZF_EOF)?;
+    output.flush()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        decompress_all, test::test_input, CompressionLevel, Index, IndexedReader, TeeWriter,
+    };
+
+    #[test]
+    fn test_concat_and_merge_indexes() {
+        let input = test_input(1_000_000);
+        let mut shards = vec![];
+        let mut indexes = vec![];
+        for chunk in input.chunks(300_000) {
+            let (mut data, mut gzi) = (vec![], vec![]);
+            let mut writer = TeeWriter::new(&mut data, &mut gzi, CompressionLevel::new(3).unwrap());
+            writer.write_all(chunk).unwrap();
+            writer.finish().unwrap();
+            shards.push(data);
+            indexes.push(Index::read_gzi(gzi.as_slice()).unwrap());
+        }
+        // An empty shard contributes nothing.
+        shards.insert(1, BGZF_EOF.to_vec());
+        indexes.insert(1, Index::new());
+
+        let mut combined = vec![];
+        concat(shards.iter().map(|shard| shard.as_slice()), &mut combined).unwrap();
+        assert!(combined.ends_with(BGZF_EOF));
+        assert!(!combined[..combined.len() - BGZF_EOF.len()].ends_with(BGZF_EOF));
+        assert_eq!(decompress_all(&combined).unwrap(), input);
+
+        let merged = Index::merge(&indexes);
+        assert_eq!(merged, Index::from_reader(io::Cursor::new(&combined)).unwrap());
+        let mut reader = IndexedReader::new(io::Cursor::new(&combined), merged);
+        let mut slice = vec![];
+        reader.range(299_000..301_000).unwrap().read_to_end(&mut slice).unwrap();
+        assert_eq!(slice, &input[299_000..301_000]);
+    }
+}
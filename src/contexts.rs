@@ -0,0 +1,136 @@
+//! Pools of reusable [`Compressor`] and [`Decompressor`] instances.
+use std::{
+    ops::{Deref, DerefMut},
+    sync::Mutex,
+};
+
+use crate::{CompressionLevel, Compressor, Decompressor};
+
+/// A thread safe pool of reusable (de)compression contexts.
+///
+/// Creating a libdeflate context allocates and initializes sizable tables, so applications that
+/// (de)compress many small independent payloads should reuse them. [`ContextPool::get`] hands out
+/// an idle context, or creates one if none are idle, and the context returns to the pool when the
+/// [`Pooled`] guard is dropped. Share the pool between threads by reference or in an `Arc`.
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{CompressorPool, DecompressorPool};
+///
+/// let compressors = CompressorPool::compressors(2.try_into().unwrap());
+/// let decompressors = DecompressorPool::decompressors();
+///
+/// std::thread::scope(|scope| {
+///     for i in 0..4 {
+///         let (compressors, decompressors) = (&compressors, &decompressors);
+///         scope.spawn(move || {
+///             let payload = format!("payload {}", i);
+///             let mut block = vec![];
+///             compressors.get().compress(payload.as_bytes(), &mut block).unwrap();
+///             let mut decompressed = vec![];
+///             decompressors.get().decompress_block(&block, &mut decompressed).unwrap();
+///             assert_eq!(decompressed, payload.as_bytes());
+///         });
+///     }
+/// });
+/// assert!(compressors.idle() >= 1);
+/// ```
+pub struct ContextPool<T> {
+    idle: Mutex<Vec<T>>,
+    create: Box<dyn Fn() -> T + Send + Sync>,
+}
+
+/// A pool of [`Compressor`]s that all use the same level.
+pub type CompressorPool = ContextPool<Compressor>;
+
+/// A pool of [`Decompressor`]s.
+pub type DecompressorPool = ContextPool<Decompressor>;
+
+impl ContextPool<Compressor> {
+    /// Create an empty pool of [`Compressor`]s using `compression_level`.
+    pub fn compressors(compression_level: CompressionLevel) -> Self {
+        Self::with_factory(move || Compressor::new(compression_level))
+    }
+}
+
+impl ContextPool<Decompressor> {
+    /// Create an empty pool of [`Decompressor`]s.
+    pub fn decompressors() -> Self {
+        Self::with_factory(Decompressor::new)
+    }
+}
+
+impl<T> ContextPool<T> {
+    /// Create an empty pool that makes new contexts with `create`.
+    fn with_factory<F>(create: F) -> Self
+    where
+        F: Fn() -> T + Send + Sync + 'static,
+    {
+        Self { idle: Mutex::new(Vec::new()), create: Box::new(create) }
+    }
+
+    /// Take an idle context from the pool, or create one if there are none.
+    pub fn get(&self) -> Pooled<'_, T> {
+        let context = self.lock().pop().unwrap_or_else(|| (self.create)());
+        Pooled { pool: self, context: Some(context) }
+    }
+
+    /// The number of contexts waiting in the pool to be reused.
+    pub fn idle(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Lock the idle contexts, ignoring poisoning since a context is valid between uses.
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<T>> {
+        self.idle.lock().unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+/// A context borrowed from a [`ContextPool`], which goes back to the pool when dropped.
+pub struct Pooled<'a, T> {
+    pool: &'a ContextPool<T>,
+    /// The context, only taken when dropped
+    context: Option<T>,
+}
+
+impl<T> Deref for Pooled<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.context.as_ref().expect("context is only taken on drop")
+    }
+}
+
+impl<T> DerefMut for Pooled<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.context.as_mut().expect("context is only taken on drop")
+    }
+}
+
+impl<T> Drop for Pooled<'_, T> {
+    fn drop(&mut self) {
+        if let Some(context) = self.context.take() {
+            self.pool.lock().push(context);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_contexts_are_reused() {
+        let pool = CompressorPool::compressors(CompressionLevel::new(3).unwrap());
+        assert_eq!(pool.idle(), 0);
+        {
+            let (first, second) = (pool.get(), pool.get());
+            assert_eq!(first.level, second.level);
+            assert_eq!(pool.idle(), 0);
+        }
+        assert_eq!(pool.idle(), 2);
+        let _reused = pool.get();
+        assert_eq!(pool.idle(), 1);
+    }
+}
@@ -0,0 +1,142 @@
+//! Copying between a BGZF [`Reader`] and [`Writer`] a block at a time.
+use std::io::{self, Read, Write};
+
+use crate::{BlockFooter, Reader, Writer};
+
+/// Copy all remaining data from `reader` to `writer`, returning the number of uncompressed bytes.
+///
+/// Unlike [`io::copy`], this moves whole blocks, each decompressed and then recompressed by
+/// `writer` at its own level. Empty blocks, such as EOF markers, are dropped, and the writer adds
+/// its own EOF marker when finished. When the input is known to be compressed just as `writer`
+/// would compress it, [`copy_raw`] skips the recompression.
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{compress_parallel, copy, Reader, Writer};
+///
+/// let input = b"ACGT".repeat(100_000);
+/// let compressed = compress_parallel(&input, 2.try_into().unwrap()).unwrap();
+///
+/// let mut reader = Reader::new(compressed.as_slice());
+/// let mut writer = Writer::new(vec![], 9.try_into().unwrap());
+/// assert_eq!(copy(&mut reader, &mut writer).unwrap(), input.len() as u64);
+/// let (copied, _) = writer.finish().unwrap();
+/// assert_eq!(copied, compress_parallel(&input, 9.try_into().unwrap()).unwrap());
+/// ```
+pub fn copy<R, W>(reader: &mut Reader<R>, writer: &mut Writer<W>) -> io::Result<u64>
+where
+    R: Read,
+    W: Write,
+{
+    copy_blocks(reader, writer, false)
+}
+
+/// Copy all remaining data from `reader` to `writer` like [`copy`], but pass blocks through
+/// without decompressing or recompressing them where possible.
+///
+/// The level a block was compressed at can't be read back from it, so calling this vouches that
+/// the input was compressed at the writer's level. A block is passed through when it holds exactly
+/// the writer's block size and its header's compression hint matches the writer's; the hint only
+/// tells the fastest and best levels apart from the rest, so it catches few mistakes. Every other
+/// block is recompressed as by [`copy`].
+///
+/// Blocks that are passed through have their checksums left unverified. Passing through is
+/// disabled while the writer has buffered data, adapts its level, or checksums or digests the
+/// stream, and while the reader digests the stream.
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{compress_parallel, copy_raw, Reader, Writer};
+///
+/// let input = b"ACGT".repeat(100_000);
+/// let compressed = compress_parallel(&input, 6.try_into().unwrap()).unwrap();
+///
+/// let mut reader = Reader::new(compressed.as_slice());
+/// let mut writer = Writer::new(vec![], 6.try_into().unwrap());
+/// assert_eq!(copy_raw(&mut reader, &mut writer).unwrap(), input.len() as u64);
+/// let (copied, _) = writer.finish().unwrap();
+/// assert_eq!(copied, compressed);
+/// ```
+pub fn copy_raw<R, W>(reader: &mut Reader<R>, writer: &mut Writer<W>) -> io::Result<u64>
+where
+    R: Read,
+    W: Write,
+{
+    copy_blocks(reader, writer, true)
+}
+
+/// Copy the blocks of `reader` to `writer`, passing through those `writer` accepts if `raw`.
+fn copy_blocks<R, W>(reader: &mut Reader<R>, writer: &mut Writer<W>, raw: bool) -> io::Result<u64>
+where
+    R: Read,
+    W: Write,
+{
+    let mut copied = reader.drain_into(writer)?;
+    while let Some(size) = reader.next_raw_block()? {
+        let (header, rest) = reader.raw_block(size);
+        let uncompressed_size = BlockFooter::from_block(rest)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?
+            .uncompressed_size as usize;
+        if uncompressed_size == 0 {
+            reader.pass_raw_block(size)?;
+        } else if raw && !reader.has_digest() && writer.accepts_raw_block(header, uncompressed_size)
+        {
+            writer.write_raw_block(header, rest, uncompressed_size)?;
+            reader.pass_raw_block(size)?;
+            copied += uncompressed_size as u64;
+        } else {
+            reader.decode_raw_block(size)?;
+            copied += reader.drain_into(writer)?;
+        }
+    }
+    Ok(copied)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{compress_parallel, decompress_all, test::test_input, CompressionLevel, Index};
+
+    #[test]
+    fn test_copy_raw_passes_matching_blocks_through() {
+        let input = test_input(300_000);
+        let compressed = compress_parallel(&input, CompressionLevel::new(3).unwrap()).unwrap();
+
+        // A plain copy recompresses every block at the writer's level.
+        let mut reader = Reader::new(compressed.as_slice());
+        let mut writer = Writer::new(vec![], CompressionLevel::new(6).unwrap());
+        assert_eq!(copy(&mut reader, &mut writer).unwrap(), input.len() as u64);
+        let (copied, _) = writer.finish().unwrap();
+        assert_eq!(copied, compress_parallel(&input, CompressionLevel::new(6).unwrap()).unwrap());
+
+        // Levels 3 and 6 share a compression hint, so full blocks are passed through untouched.
+        let mut reader = Reader::new(compressed.as_slice());
+        let mut buf = vec![0; 100];
+        reader.read_exact(&mut buf).unwrap();
+        let mut writer = Writer::new(vec![], CompressionLevel::new(6).unwrap());
+        writer.write_all(&buf).unwrap();
+        assert_eq!(copy_raw(&mut reader, &mut writer).unwrap(), input.len() as u64 - 100);
+        let (copied, _) = writer.finish().unwrap();
+        assert_eq!(decompress_all(&copied).unwrap(), input);
+
+        let mut reader = Reader::new(compressed.as_slice());
+        let mut writer = Writer::new(vec![], CompressionLevel::new(6).unwrap());
+        copy_raw(&mut reader, &mut writer).unwrap();
+        let (copied, _) = writer.finish().unwrap();
+        // Only the final, partial block is recompressed.
+        let index = Index::from_reader(io::Cursor::new(&compressed)).unwrap();
+        let last_block = index.entries()[index.entries().len() - 2].compressed_offset as usize;
+        assert_eq!(copied[..last_block], compressed[..last_block]);
+        assert_eq!(decompress_all(&copied).unwrap(), input);
+
+        // A different block size means recompressing.
+        let mut reader = Reader::new(compressed.as_slice());
+        let mut writer = Writer::with_capacity(vec![], CompressionLevel::new(3).unwrap(), 10_000);
+        copy_raw(&mut reader, &mut writer).unwrap();
+        let (copied, summary) = writer.finish().unwrap();
+        assert_eq!(summary.blocks, 30);
+        assert_eq!(decompress_all(&copied).unwrap(), input);
+    }
+}
@@ -0,0 +1,395 @@
+//! Sans-I/O BGZF block framing, for runtimes and transports that don't fit [`std::io`].
+//!
+//! An [`Encoder`] takes uncompressed bytes and hands back finished blocks, and a [`Decoder`] takes
+//! compressed bytes and hands back the data of each block. Neither does any I/O, so the caller
+//! moves bytes in and out however it likes, with the same block parsing and codecs as
+//! [`crate::Reader`] and [`crate::Writer`].
+//!
+//! # Example
+//!
+//! ```rust
+//! use bgzf::core::{Decoder, Encoder};
+//!
+//! let mut encoder = Encoder::new(2.try_into().unwrap());
+//! let mut compressed = vec![];
+//! let mut input = &b"hello world"[..];
+//! while !input.is_empty() {
+//!     let consumed = encoder.push(input);
+//!     input = &input[consumed..];
+//!     if let Some(block) = encoder.encode().unwrap() {
+//!         compressed.extend_from_slice(block);
+//!     }
+//! }
+//! compressed.extend_from_slice(encoder.finish().unwrap());
+//!
+//! let mut decoder = Decoder::new();
+//! let mut decompressed = vec![];
+//! let mut input = compressed.as_slice();
+//! while !input.is_empty() {
+//!     let consumed = decoder.push(input);
+//!     input = &input[consumed..];
+//!     if let Some(data) = decoder.decode().unwrap() {
+//!         decompressed.extend_from_slice(data);
+//!     }
+//! }
+//! decoder.finish().unwrap();
+//! assert_eq!(decompressed, b"hello world");
+//! ```
+use std::io;
+
+use crate::{
+    BgzfError, BgzfResult, BlockHeader, CompressionLevel, Compressor, Decompressor, PooledBuffer,
+    BGZF_BLOCK_SIZE, BGZF_EOF, BGZF_HEADER_SIZE,
+};
+
+/// Convert a [`BgzfError`] from a [`Decoder`] or [`Encoder`] into the [`io::Error`] a
+/// [`crate::Reader`] would give.
+pub(crate) fn into_io_error(error: BgzfError) -> io::Error {
+    match error {
+        BgzfError::Io(e) => e,
+        BgzfError::Truncated => {
+            io::Error::new(io::ErrorKind::UnexpectedEof, "Truncated BGZF block")
+        }
+        e => io::Error::new(io::ErrorKind::Other, e),
+    }
+}
+
+/// Check a block header and return the size of the whole block it starts.
+///
+/// `header` must hold at least the 18 byte header.
+pub fn block_size(header: &[u8]) -> BgzfResult<usize> {
+    BlockHeader::parse(header).map(|header| header.block_size())
+}
+
+/// Gathers the bytes of one block: its header, then as many more as the header says the block
+/// holds.
+///
+/// This is the framing shared by the [`Decoder`] and the [`crate::Reader`], which differ only in
+/// where the bytes come from.
+#[derive(Clone)]
+pub(crate) struct Framer {
+    /// The bytes of the current block received so far
+    block: PooledBuffer,
+    /// The size of the current block, once its header has been received and checked
+    size: Option<usize>,
+}
+
+impl Framer {
+    /// Create a [`Framer`] expecting the start of a block.
+    pub(crate) fn new() -> Self {
+        Self { block: PooledBuffer::new(), size: None }
+    }
+
+    /// The bytes of the block received so far.
+    pub(crate) fn block(&self) -> &[u8] {
+        &self.block
+    }
+
+    /// The bytes of the block after its header.
+    pub(crate) fn body(&self) -> &[u8] {
+        &self.block[BGZF_HEADER_SIZE.min(self.block.len())..]
+    }
+
+    /// The size of the block, once its header has been received, failing if the header is bad.
+    pub(crate) fn size(&mut self) -> BgzfResult<Option<usize>> {
+        if self.size.is_none() && self.block.len() >= BGZF_HEADER_SIZE {
+            self.size = Some(block_size(&self.block)?);
+        }
+        Ok(self.size)
+    }
+
+    /// Whether the whole block has been received.
+    pub(crate) fn is_complete(&self) -> bool {
+        self.size == Some(self.block.len())
+    }
+
+    /// The number of bytes received of a block that isn't complete, which is zero between blocks.
+    pub(crate) fn partial(&self) -> usize {
+        if self.is_complete() {
+            0
+        } else {
+            self.block.len()
+        }
+    }
+
+    /// Drop the block, complete or not, and expect the start of the next one.
+    pub(crate) fn clear(&mut self) {
+        self.block.clear();
+        self.size = None;
+    }
+
+    /// The number of bytes still needed to finish the header, or the block once the header has
+    /// been received.
+    fn wanted(&mut self) -> BgzfResult<usize> {
+        Ok(self.size()?.unwrap_or(BGZF_HEADER_SIZE) - self.block.len())
+    }
+
+    /// Take bytes from `input`, returning how many were taken.
+    ///
+    /// Nothing more is taken once the block is complete, or if its header is bad.
+    pub(crate) fn push(&mut self, input: &[u8]) -> usize {
+        let mut taken = 0;
+        while let Ok(wanted @ 1..) = self.wanted() {
+            let amount = std::cmp::min(wanted, input.len() - taken);
+            if amount == 0 {
+                break;
+            }
+            self.block.extend_from_slice(&input[taken..taken + amount]);
+            taken += amount;
+        }
+        taken
+    }
+
+    /// Receive the next block with `read`, which fills the start of the buffer it is given as
+    /// [`io::Read::read`] does, returning its size, or `None` if the input ends cleanly between
+    /// blocks.
+    ///
+    /// A complete block from an earlier call is dropped first. After an error, such as
+    /// [`io::ErrorKind::WouldBlock`], calling this again carries on where it left off.
+    /// Interrupted reads are retried.
+    pub(crate) fn fill_with<F>(&mut self, mut read: F) -> io::Result<Option<usize>>
+    where
+        F: FnMut(&mut [u8]) -> io::Result<usize>,
+    {
+        if self.is_complete() {
+            self.clear();
+        }
+        loop {
+            let wanted = self.wanted().map_err(into_io_error)?;
+            if wanted == 0 {
+                return Ok(self.size);
+            }
+            let start = self.block.len();
+            self.block.resize(start + wanted, 0);
+            let result = read(&mut self.block[start..]);
+            self.block.truncate(start + *result.as_ref().unwrap_or(&0));
+            match result {
+                Ok(0) if start == 0 => return Ok(None),
+                Ok(0) => return Err(into_io_error(BgzfError::Truncated)),
+                Ok(_) => (),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Splits compressed bytes into blocks and decompresses them.
+///
+/// At most one block is buffered, so [`Decoder::push`] stops taking input once it holds a whole
+/// block, until that block is taken with [`Decoder::decode`].
+pub struct Decoder {
+    decompressor: Decompressor,
+    framer: Framer,
+    /// The data of the last decoded block
+    output: Vec<u8>,
+}
+
+impl Decoder {
+    /// Create a [`Decoder`] expecting the start of a block.
+    pub fn new() -> Self {
+        Self {
+            decompressor: Decompressor::new(),
+            framer: Framer::new(),
+            output: Vec::with_capacity(BGZF_BLOCK_SIZE),
+        }
+    }
+
+    /// Take compressed bytes from `input`, returning how many were taken.
+    ///
+    /// Fewer than all of them are taken once a whole block is buffered.
+    pub fn push(&mut self, input: &[u8]) -> usize {
+        // A bad header is reported by `decode`.
+        self.framer.push(input)
+    }
+
+    /// Decompress the buffered block, or return `None` if more input is needed first.
+    ///
+    /// Empty blocks, such as the EOF marker, are returned as empty data.
+    pub fn decode(&mut self) -> BgzfResult<Option<&[u8]>> {
+        self.framer.size()?;
+        if !self.framer.is_complete() {
+            return Ok(None);
+        }
+        let result = self.decompressor.decompress_block(self.framer.block(), &mut self.output);
+        self.framer.clear();
+        result?;
+        Ok(Some(&self.output))
+    }
+
+    /// Check that the input ended between blocks.
+    pub fn finish(&self) -> BgzfResult<()> {
+        if self.framer.block().is_empty() {
+            Ok(())
+        } else {
+            Err(BgzfError::Truncated)
+        }
+    }
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Gathers uncompressed bytes into blocks and compresses them.
+///
+/// At most one block of input is buffered, so [`Encoder::push`] stops taking input once a block
+/// is full, until that block is taken with [`Encoder::encode`].
+pub struct Encoder {
+    compressor: Compressor,
+    /// The size of the blocks to create
+    blocksize: usize,
+    /// The uncompressed bytes of the next block
+    input: Vec<u8>,
+    /// The last compressed output
+    output: PooledBuffer,
+}
+
+impl Encoder {
+    /// Create an [`Encoder`] making blocks of [`BGZF_BLOCK_SIZE`] bytes.
+    pub fn new(compression_level: CompressionLevel) -> Self {
+        Self::with_block_size(compression_level, BGZF_BLOCK_SIZE)
+    }
+
+    /// Create an [`Encoder`] making blocks of `blocksize` bytes, which must be at most
+    /// [`BGZF_BLOCK_SIZE`].
+    pub fn with_block_size(compression_level: CompressionLevel, blocksize: usize) -> Self {
+        assert!(blocksize > 0 && blocksize <= BGZF_BLOCK_SIZE);
+        Self {
+            blocksize,
+            input: Vec::with_capacity(blocksize),
+            ..Self::from_compressor(Compressor::new(compression_level))
+        }
+    }
+
+    /// Create an [`Encoder`] around `compressor` for a [`crate::Writer`], which buffers its input
+    /// and picks where blocks end itself, and only uses [`Encoder::encode_block`].
+    pub(crate) fn from_compressor(compressor: Compressor) -> Self {
+        Self {
+            compressor,
+            blocksize: BGZF_BLOCK_SIZE,
+            input: Vec::new(),
+            output: PooledBuffer::new(),
+        }
+    }
+
+    /// The compressor blocks are made with.
+    pub(crate) fn compressor(&self) -> &Compressor {
+        &self.compressor
+    }
+
+    /// Make the following blocks with `compressor`.
+    pub(crate) fn set_compressor(&mut self, compressor: Compressor) {
+        self.compressor = compressor;
+    }
+
+    /// Take uncompressed bytes from `input`, returning how many were taken.
+    ///
+    /// Fewer than all of them are taken once a whole block is buffered.
+    pub fn push(&mut self, input: &[u8]) -> usize {
+        let amount = std::cmp::min(input.len(), self.blocksize - self.input.len());
+        self.input.extend_from_slice(&input[..amount]);
+        amount
+    }
+
+    /// Compress the buffered block if it is full, or return `None` if it needs more input.
+    pub fn encode(&mut self) -> BgzfResult<Option<&[u8]>> {
+        if self.input.len() < self.blocksize {
+            return Ok(None);
+        }
+        self.flush()
+    }
+
+    /// Compress whatever is buffered into a block, full or not, or return `None` if nothing is.
+    pub fn flush(&mut self) -> BgzfResult<Option<&[u8]>> {
+        if self.input.is_empty() {
+            return Ok(None);
+        }
+        let mut input = std::mem::take(&mut self.input);
+        let result = self.encode_block(&input).map(|_| ());
+        input.clear();
+        self.input = input;
+        result?;
+        Ok(Some(&self.output))
+    }
+
+    /// Compress `bytes`, which must fit in a block, into a block of their own, ignoring anything
+    /// buffered.
+    pub(crate) fn encode_block(&mut self, bytes: &[u8]) -> BgzfResult<&[u8]> {
+        self.compressor.compress(bytes, &mut self.output)?;
+        Ok(&self.output)
+    }
+
+    /// Put together an already compressed block from its `header` and `rest`, to be output as is.
+    pub(crate) fn copy_block(&mut self, header: &[u8], rest: &[u8]) -> &[u8] {
+        self.output.clear();
+        self.output.extend_from_slice(header);
+        self.output.extend_from_slice(rest);
+        &self.output
+    }
+
+    /// Compress whatever is buffered and return it followed by the EOF marker, which ends the
+    /// stream.
+    pub fn finish(&mut self) -> BgzfResult<&[u8]> {
+        if self.flush()?.is_none() {
+            self.output.clear();
+        }
+        self.output.extend_from_slice(BGZF_EOF);
+        Ok(&self.output)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{decompress_all, test::test_input};
+
+    #[test]
+    fn test_round_trip_byte_at_a_time() {
+        let input = test_input(150_000);
+        let mut encoder = Encoder::new(CompressionLevel::new(3).unwrap());
+        let mut compressed = vec![];
+        for chunk in input.chunks(7_000) {
+            let mut chunk = chunk;
+            while !chunk.is_empty() {
+                let consumed = encoder.push(chunk);
+                chunk = &chunk[consumed..];
+                if let Some(block) = encoder.encode().unwrap() {
+                    compressed.extend_from_slice(block);
+                }
+            }
+        }
+        compressed.extend_from_slice(encoder.finish().unwrap());
+        assert_eq!(decompress_all(&compressed).unwrap(), input);
+
+        let mut decoder = Decoder::new();
+        let mut decompressed = vec![];
+        let mut blocks = 0;
+        for byte in compressed.chunks(1) {
+            assert_eq!(decoder.push(byte), 1);
+            if let Some(data) = decoder.decode().unwrap() {
+                decompressed.extend_from_slice(data);
+                blocks += 1;
+            }
+        }
+        decoder.finish().unwrap();
+        assert_eq!(decompressed, input);
+        assert_eq!(blocks, 4);
+    }
+
+    #[test]
+    fn test_decoder_errors() {
+        let mut decoder = Decoder::new();
+        decoder.push(&BGZF_EOF[..20]);
+        assert!(decoder.decode().unwrap().is_none());
+        assert!(matches!(decoder.finish(), Err(BgzfError::Truncated)));
+
+        let mut corrupt = BGZF_EOF.to_vec();
+        corrupt[12] = b'X';
+        let mut decoder = Decoder::new();
+        decoder.push(&corrupt);
+        assert!(matches!(decoder.decode(), Err(BgzfError::InvalidHeader(_))));
+    }
+}
@@ -0,0 +1,128 @@
+//! Combining CRC32s of consecutive pieces of data without rereading them.
+
+/// The reflected CRC-32 polynomial used by gzip.
+const POLY: u32 = 0xedb8_8320;
+
+/// `x^(2^k)` modulo the polynomial, for each `k` up to 31.
+const X2N_TABLE: [u32; 32] = x2n_table();
+
+/// Multiply `a` by `b` modulo the polynomial, in the reflected bit order CRCs use.
+///
+/// `a` must not be zero.
+const fn multmodp(a: u32, mut b: u32) -> u32 {
+    let mut m = 1 << 31;
+    let mut p = 0;
+    loop {
+        if a & m != 0 {
+            p ^= b;
+            if a & (m - 1) == 0 {
+                break;
+            }
+        }
+        m >>= 1;
+        b = if b & 1 != 0 { (b >> 1) ^ POLY } else { b >> 1 };
+    }
+    p
+}
+
+const fn x2n_table() -> [u32; 32] {
+    let mut table = [0; 32];
+    // x^1
+    let mut p = 1 << 30;
+    let mut k = 0;
+    while k < 32 {
+        table[k] = p;
+        p = multmodp(p, p);
+        k += 1;
+    }
+    table
+}
+
+/// `x^(n * 2^k)` modulo the polynomial.
+fn x2nmodp(mut n: u64, mut k: usize) -> u32 {
+    // x^0
+    let mut p = 1 << 31;
+    while n != 0 {
+        if n & 1 != 0 {
+            p = multmodp(X2N_TABLE[k & 31], p);
+        }
+        n >>= 1;
+        k += 1;
+    }
+    p
+}
+
+/// The CRC32 of two pieces of data one after the other, from the CRC32 of each and the length of
+/// the second, as zlib's `crc32_combine` computes it.
+///
+/// This is how [`Reader::stream_crc32`](crate::Reader::stream_crc32) and
+/// [`Writer::stream_crc32`](crate::Writer::stream_crc32) build the checksum of a whole stream from
+/// the CRC32 in each block's footer.
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::crc32_combine;
+///
+/// let crc32 = |data: &[u8]| {
+///     let mut crc = libdeflater::Crc::new();
+///     crc.update(data);
+///     crc.sum()
+/// };
+/// let combined = crc32_combine(crc32(b"hello "), crc32(b"world"), 5);
+/// assert_eq!(combined, crc32(b"hello world"));
+/// ```
+pub fn crc32_combine(crc1: u32, crc2: u32, len2: u64) -> u32 {
+    // Shifting crc1 by len2 bytes multiplies it by x^(8 * len2).
+    multmodp(x2nmodp(len2, 3), crc1) ^ crc2
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Read, Seek, Write};
+
+    use super::*;
+    use crate::{test::test_input, CompressionLevel, Reader, Writer};
+
+    #[test]
+    fn test_stream_crc32() {
+        let crc32 = |data: &[u8]| {
+            let mut crc = libdeflater::Crc::new();
+            crc.update(data);
+            crc.sum()
+        };
+        let input = test_input(1_000_000);
+        for split in [0, 1, 7, 65_280, 999_999, 1_000_000] {
+            let (a, b) = input.split_at(split);
+            assert_eq!(crc32_combine(crc32(a), crc32(b), b.len() as u64), crc32(&input));
+        }
+
+        let mut writer = Writer::new(vec![], CompressionLevel::new(3).unwrap());
+        writer.set_stream_checksum(true);
+        writer.write_all(&input[..100]).unwrap();
+        writer.flush().unwrap();
+        assert_eq!(writer.stream_crc32(), Some(crc32(&input[..100])));
+        writer.write_all(&input[100..]).unwrap();
+        let (compressed, summary) = writer.finish().unwrap();
+        assert_eq!(summary.crc32, Some(crc32(&input)));
+
+        let mut reader = Reader::new(compressed.as_slice());
+        assert_eq!(reader.stream_crc32(), None);
+        reader.set_stream_checksum(true);
+        assert_eq!(reader.stream_crc32(), Some(0));
+        reader.read_to_end(&mut vec![]).unwrap();
+        assert_eq!(reader.stream_crc32(), Some(crc32(&input)));
+
+        let mut reader = Reader::with_prefetch(std::io::Cursor::new(compressed.clone()), 4);
+        reader.set_stream_checksum(true);
+        std::io::copy(&mut reader, &mut std::io::sink()).unwrap();
+        assert_eq!(reader.stream_crc32(), Some(crc32(&input)));
+
+        let mut reader = Reader::new(std::io::Cursor::new(&compressed));
+        reader.set_stream_checksum(true);
+        reader.seek(std::io::SeekFrom::Start(100_000)).unwrap();
+        assert!(reader.stream_crc32().is_some());
+        reader.seek(std::io::SeekFrom::Start(10)).unwrap();
+        assert_eq!(reader.stream_crc32(), None);
+    }
+}
@@ -0,0 +1,221 @@
+//! SHA-256 digests of the decompressed data of every block and of the whole stream.
+use std::{
+    fs::File,
+    io::{self, BufRead, Read, Write},
+    path::Path,
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::{
+    core::{into_io_error, Decoder},
+    BUFSIZE,
+};
+
+/// The SHA-256 digest of the decompressed data of one block.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct BlockDigest {
+    /// The offset of the block in the compressed stream
+    pub compressed_offset: u64,
+    /// The number of uncompressed bytes in the block
+    pub uncompressed_size: u32,
+    /// The SHA-256 digest of the block's uncompressed data
+    pub sha256: [u8; 32],
+}
+
+/// The SHA-256 digests of every block of BGZF data, including empty blocks such as the EOF marker,
+/// and of all of the uncompressed data.
+///
+/// Building a manifest decompresses everything, checking each block's CRC32 on the way. The
+/// manifest can be saved as tab separated text to attest to the content of a file.
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{DigestManifest, Writer};
+/// use std::io::Write;
+///
+/// let mut writer = Writer::new(vec![], 2.try_into().unwrap());
+/// writer.write_all(b"hello world").unwrap();
+/// let (compressed, _) = writer.finish().unwrap();
+///
+/// let manifest = DigestManifest::from_reader(compressed.as_slice()).unwrap();
+/// assert_eq!(manifest.blocks().len(), 2);
+/// assert_eq!(manifest.stream_sha256()[..4], [0xb9, 0x4d, 0x27, 0xb9]);
+///
+/// let mut tsv = vec![];
+/// manifest.write_tsv(&mut tsv).unwrap();
+/// assert_eq!(DigestManifest::read_tsv(tsv.as_slice()).unwrap(), manifest);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DigestManifest {
+    blocks: Vec<BlockDigest>,
+    stream_sha256: [u8; 32],
+}
+
+impl DigestManifest {
+    /// The digests of the blocks, in order.
+    pub fn blocks(&self) -> &[BlockDigest] {
+        &self.blocks
+    }
+
+    /// The SHA-256 digest of all of the uncompressed data.
+    pub fn stream_sha256(&self) -> [u8; 32] {
+        self.stream_sha256
+    }
+
+    /// Build the manifest of the BGZF file at `path`.
+    pub fn from_path<P>(path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Self::from_reader(File::open(path)?)
+    }
+
+    /// Build the manifest of the BGZF data in `reader` by decompressing every block.
+    pub fn from_reader<R>(mut reader: R) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        let mut decoder = Decoder::new();
+        let mut stream = Sha256::new();
+        let mut blocks = vec![];
+        let mut input = vec![0; BUFSIZE];
+        // The compressed offsets of the end of the bytes pushed so far and of the next block.
+        let (mut pushed, mut block_start) = (0, 0);
+        loop {
+            let read = match reader.read(&mut input) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            let mut rest = &input[..read];
+            while !rest.is_empty() {
+                let taken = decoder.push(rest);
+                rest = &rest[taken..];
+                pushed += taken as u64;
+                if let Some(data) = decoder.decode().map_err(into_io_error)? {
+                    stream.update(data);
+                    blocks.push(BlockDigest {
+                        compressed_offset: block_start,
+                        uncompressed_size: data.len() as u32,
+                        sha256: Sha256::digest(data).into(),
+                    });
+                    block_start = pushed;
+                }
+            }
+        }
+        decoder.finish().map_err(into_io_error)?;
+        Ok(Self { blocks, stream_sha256: stream.finalize().into() })
+    }
+
+    /// Read a manifest written by [`DigestManifest::write_tsv`].
+    pub fn read_tsv<R>(reader: R) -> io::Result<Self>
+    where
+        R: BufRead,
+    {
+        let mut blocks = vec![];
+        let mut stream_sha256 = None;
+        for (number, line) in reader.lines().enumerate() {
+            let line = line?;
+            let invalid = || {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid digest manifest line {}: {}", number + 1, line),
+                )
+            };
+            if let Some(digest) = line.strip_prefix("#stream_sha256\t") {
+                stream_sha256 = Some(from_hex(digest).ok_or_else(invalid)?);
+                continue;
+            }
+            if line.starts_with('#') || line.is_empty() {
+                continue;
+            }
+            let mut fields = line.split('\t');
+            let mut next = || fields.next().ok_or_else(invalid);
+            let compressed_offset = next()?.parse().map_err(|_| invalid())?;
+            let uncompressed_size = next()?.parse().map_err(|_| invalid())?;
+            let sha256 = from_hex(next()?).ok_or_else(invalid)?;
+            blocks.push(BlockDigest { compressed_offset, uncompressed_size, sha256 });
+        }
+        let stream_sha256 = stream_sha256.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "Digest manifest has no stream digest")
+        })?;
+        Ok(Self { blocks, stream_sha256 })
+    }
+
+    /// Write the manifest as tab separated text: the digest of the whole stream, then a header
+    /// line, then one line per block, with digests in hexadecimal.
+    pub fn write_tsv<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        writeln!(writer, "#stream_sha256\t{}", to_hex(&self.stream_sha256))?;
+        writeln!(writer, "#compressed_offset\tuncompressed_size\tsha256")?;
+        for block in &self.blocks {
+            writeln!(
+                writer,
+                "{}\t{}\t{}",
+                block.compressed_offset,
+                block.uncompressed_size,
+                to_hex(&block.sha256)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Format `bytes` as lowercase hexadecimal.
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Parse a 32 byte digest from hexadecimal.
+fn from_hex(hex: &str) -> Option<[u8; 32]> {
+    if hex.len() != 64 || !hex.is_ascii() {
+        return None;
+    }
+    let mut digest = [0; 32];
+    for (byte, pair) in digest.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?;
+    }
+    Some(digest)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{compress_parallel, test::test_input, CompressionLevel, Index, BGZF_BLOCK_SIZE};
+
+    #[test]
+    fn test_digest_manifest() {
+        use sha2::{Digest, Sha256};
+
+        let input = test_input(200_000);
+        let compressed = compress_parallel(&input, CompressionLevel::new(3).unwrap()).unwrap();
+
+        let manifest = DigestManifest::from_reader(compressed.as_slice()).unwrap();
+        assert_eq!(manifest.stream_sha256(), <[u8; 32]>::from(Sha256::digest(&input)));
+        let index = Index::from_reader(io::Cursor::new(&compressed)).unwrap();
+        let blocks = manifest.blocks();
+        assert_eq!(blocks.len(), 5);
+        for (i, (block, data)) in blocks.iter().zip(input.chunks(BGZF_BLOCK_SIZE)).enumerate() {
+            assert_eq!(block.sha256, <[u8; 32]>::from(Sha256::digest(data)));
+            assert_eq!(block.uncompressed_size as usize, data.len());
+            if i > 0 {
+                assert_eq!(block.compressed_offset, index.entries()[i - 1].compressed_offset);
+            }
+        }
+
+        let mut tsv = vec![];
+        manifest.write_tsv(&mut tsv).unwrap();
+        assert_eq!(DigestManifest::read_tsv(tsv.as_slice()).unwrap(), manifest);
+        assert!(DigestManifest::read_tsv(&tsv[tsv.iter().position(|&b| b == b'\n').unwrap()..])
+            .is_err());
+
+        let mut corrupt = compressed.clone();
+        corrupt[100] ^= 0xff;
+        assert!(DigestManifest::from_reader(corrupt.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,153 @@
+//! Drop-in replacements for flate2's gzip encoder and decoder that read and write BGZF.
+use std::io::{self, Read, Write};
+
+use crate::{CompressionLevel, Reader, Writer};
+
+/// A BGZF compressor shaped like flate2's `write::GzEncoder`, for code moving from gzip to BGZF.
+///
+/// Only the construction and finishing differ from a [`Writer`]: the compression level is a
+/// [`CompressionLevel`] rather than a `flate2::Compression`, and [`GzEncoder::finish`] returns
+/// just the inner writer. Use a [`Writer`] for everything else BGZF offers.
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{CompressionLevel, GzDecoder, GzEncoder};
+/// use std::io::{Read, Write};
+///
+/// let mut encoder = GzEncoder::new(vec![], CompressionLevel::new(6).unwrap());
+/// encoder.write_all(b"hello world").unwrap();
+/// let compressed = encoder.finish().unwrap();
+///
+/// let mut decoder = GzDecoder::new(compressed.as_slice());
+/// let mut decompressed = String::new();
+/// decoder.read_to_string(&mut decompressed).unwrap();
+/// assert_eq!(decompressed, "hello world");
+/// ```
+pub struct GzEncoder<W>
+where
+    W: Write,
+{
+    inner: Writer<W>,
+}
+
+impl<W> GzEncoder<W>
+where
+    W: Write,
+{
+    /// Compress into `writer` at `level`.
+    pub fn new(writer: W, level: CompressionLevel) -> Self {
+        Self { inner: Writer::new(writer, level) }
+    }
+
+    /// A reference to the inner writer.
+    pub fn get_ref(&self) -> &W {
+        self.inner.get_ref()
+    }
+
+    /// A mutable reference to the inner writer.
+    ///
+    /// Writing to it directly, rather than through this encoder, corrupts the output.
+    pub fn get_mut(&mut self) -> &mut W {
+        self.inner.get_mut()
+    }
+
+    /// Write out any buffered data and the EOF marker block without giving up the inner writer.
+    ///
+    /// Data written afterwards starts new blocks, as for [`Writer::write_eof`].
+    pub fn try_finish(&mut self) -> io::Result<()> {
+        self.inner.write_eof()
+    }
+
+    /// Write out any buffered data and the EOF marker block, and return the inner writer.
+    pub fn finish(self) -> io::Result<W> {
+        self.inner.finish().map(|(writer, _)| writer)
+    }
+}
+
+impl<W> Write for GzEncoder<W>
+where
+    W: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A BGZF decompressor shaped like flate2's `read::GzDecoder`, for code moving from gzip to BGZF.
+///
+/// Unlike flate2's `GzDecoder`, which stops after the first gzip member, this reads every block
+/// of the stream, since a BGZF file is a series of members. Input that isn't BGZF is an error;
+/// use a [`MultiMemberReader`](crate::MultiMemberReader) for streams that mix in ordinary gzip
+/// members.
+pub struct GzDecoder<R> {
+    inner: Reader<R>,
+}
+
+impl<R> GzDecoder<R>
+where
+    R: Read,
+{
+    /// Decompress the BGZF data in `reader`.
+    pub fn new(reader: R) -> Self {
+        Self { inner: Reader::new(reader) }
+    }
+
+    /// A reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        self.inner.get_ref()
+    }
+
+    /// A mutable reference to the inner reader.
+    ///
+    /// Reading from it directly, rather than through this decoder, loses track of where the next
+    /// block starts.
+    pub fn get_mut(&mut self) -> &mut R {
+        self.inner.get_mut()
+    }
+
+    /// Unwrap the inner reader, discarding any data decompressed but not yet read.
+    pub fn into_inner(self) -> R {
+        self.inner.into_inner()
+    }
+}
+
+impl<R> Read for GzDecoder<R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+/// flate2's name for a decoder of every member, which a [`GzDecoder`] already is.
+pub type MultiGzDecoder<R> = GzDecoder<R>;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{test::test_input, BGZF_EOF};
+
+    #[test]
+    fn test_gz_encoder_and_decoder() {
+        let input = test_input(300_000);
+        let mut encoder = GzEncoder::new(vec![], CompressionLevel::new(3).unwrap());
+        encoder.write_all(&input[..100_000]).unwrap();
+        encoder.try_finish().unwrap();
+        assert!(encoder.get_ref().ends_with(BGZF_EOF));
+        encoder.write_all(&input[100_000..]).unwrap();
+        let compressed = encoder.finish().unwrap();
+        assert!(compressed.ends_with(BGZF_EOF));
+
+        let mut decoder = MultiGzDecoder::new(compressed.as_slice());
+        let mut decompressed = vec![];
+        decoder.read_to_end(&mut decompressed).unwrap();
+        assert_eq!(decompressed, input);
+        assert!(decoder.into_inner().is_empty());
+    }
+}
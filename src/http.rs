@@ -0,0 +1,156 @@
+//! Fetching blocks from a web server with HTTP range requests.
+use std::io::{self, Read};
+
+use crate::{BlockSource, Index, IndexedReader, MAX_BGZF_BLOCK_SIZE};
+
+/// Convert a failed request into an [`io::Error`].
+fn into_io_error(error: ureq::Error) -> io::Error {
+    match error {
+        ureq::Error::Status(404, _) => io::Error::new(io::ErrorKind::NotFound, error),
+        // Range Not Satisfiable, the offset is past the end of the file.
+        ureq::Error::Status(416, _) => io::Error::new(io::ErrorKind::UnexpectedEof, error),
+        e => io::Error::new(io::ErrorKind::Other, e),
+    }
+}
+
+/// A [`BlockSource`] that fetches each block from a URL with an HTTP(S) range request.
+///
+/// Each block is fetched with a single request for the largest block size from its offset, so
+/// the server must support range requests. A server that answers with the whole file instead is
+/// reported as an error rather than downloading it.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use bgzf::IndexedReader;
+/// use std::io::{Read, Seek, SeekFrom};
+///
+/// let mut reader = IndexedReader::from_url("https://example.org/genome.fa.gz").unwrap();
+/// reader.seek(SeekFrom::Start(1_000_000)).unwrap();
+/// let mut bases = [0; 60];
+/// reader.read_exact(&mut bases).unwrap();
+/// ```
+pub struct HttpSource {
+    agent: ureq::Agent,
+    url: String,
+}
+
+impl HttpSource {
+    /// Create an [`HttpSource`] for `url` with a default [`ureq::Agent`].
+    pub fn new(url: impl Into<String>) -> Self {
+        Self::with_agent(ureq::Agent::new(), url)
+    }
+
+    /// Create an [`HttpSource`] for `url` that makes its requests with `agent`, for example to set
+    /// timeouts, a proxy, or TLS configuration.
+    pub fn with_agent(agent: ureq::Agent, url: impl Into<String>) -> Self {
+        Self { agent, url: url.into() }
+    }
+
+    /// The URL blocks are fetched from.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+impl BlockSource for HttpSource {
+    fn fetch_block(&mut self, offset: u64, buf: &mut Vec<u8>) -> io::Result<()> {
+        let last = offset + MAX_BGZF_BLOCK_SIZE as u64 - 1;
+        let response = self
+            .agent
+            .get(&self.url)
+            .set("Range", &format!("bytes={}-{}", offset, last))
+            .call()
+            .map_err(into_io_error)?;
+        if response.status() != 206 {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("{} does not support range requests", self.url),
+            ));
+        }
+        buf.clear();
+        response.into_reader().take(MAX_BGZF_BLOCK_SIZE as u64).read_to_end(buf)?;
+        Ok(())
+    }
+}
+
+impl IndexedReader<HttpSource> {
+    /// Open the BGZF file at `url` with the index in the `.gzi` file next to it, fetching blocks
+    /// with range requests as they are read.
+    pub fn from_url(url: &str) -> io::Result<Self> {
+        let source = HttpSource::new(url);
+        let response = source.agent.get(&format!("{}.gzi", url)).call().map_err(into_io_error)?;
+        let index = Index::read_gzi(io::BufReader::new(response.into_reader()))?;
+        Ok(Self::new(source, index))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::{BufRead, BufReader, Seek, SeekFrom, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use super::*;
+    use crate::{test::test_input, TeeWriter};
+
+    /// Serve `files` by path over HTTP, honouring single ranges, and return the base URL.
+    fn serve(files: Vec<(&'static str, Vec<u8>)>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut lines = BufReader::new(stream.try_clone().unwrap()).lines();
+                let request = lines.next().unwrap().unwrap();
+                let path = request.split(' ').nth(1).unwrap().to_string();
+                let mut range = None;
+                for line in lines.map(Result::unwrap).take_while(|line| !line.is_empty()) {
+                    if let Some(spec) = line.strip_prefix("Range: bytes=") {
+                        let (start, end) = spec.split_once('-').unwrap();
+                        range =
+                            Some((start.parse::<usize>().unwrap(), end.parse::<usize>().unwrap()));
+                    }
+                }
+                let file = files.iter().find(|(name, _)| *name == path);
+                let (status, body) = match (file, range) {
+                    (None, _) => ("404 Not Found", &[][..]),
+                    (Some((_, body)), Some((start, end))) => {
+                        ("206 Partial Content", &body[start..=std::cmp::min(end, body.len() - 1)])
+                    }
+                    (Some((_, body)), None) => ("200 OK", &body[..]),
+                };
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    body.len()
+                )
+                .unwrap();
+                stream.write_all(body).unwrap();
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn test_indexed_reader_from_url() {
+        let input = test_input(300_000);
+        let (mut data, mut gzi) = (vec![], vec![]);
+        let mut writer = TeeWriter::new(&mut data, &mut gzi, 3.try_into().unwrap());
+        writer.write_all(&input).unwrap();
+        writer.finish().unwrap();
+        let url = serve(vec![("/test.gz", data), ("/test.gz.gzi", gzi)]);
+
+        let mut reader = IndexedReader::from_url(&format!("{}/test.gz", url)).unwrap();
+        reader.seek(SeekFrom::Start(200_000)).unwrap();
+        let mut buf = vec![0; 70_000];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, &input[200_000..270_000]);
+
+        let error = IndexedReader::from_url(&format!("{}/missing.gz", url)).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    }
+}
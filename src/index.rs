@@ -0,0 +1,465 @@
+//! The `.gzi` index of BGZF block offsets, as written by `bgzip -i`.
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufReader, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use byteorder::{ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
+use crossbeam_channel::{bounded, Receiver};
+
+use crate::{
+    check_uncompressed_size, core::block_size, worker_exited, BlockInfo, Decompressor, ThreadPool,
+    BGZF_HEADER_SIZE, BUFSIZE, MAX_BGZF_BLOCK_SIZE,
+};
+
+/// The number of compressed bytes, in whole blocks, checked together in one job by
+/// [`Index::from_path_verified`]. With [`JOBS_PER_THREAD`] this caps the compressed data held
+/// per pool thread at a few MiB.
+const BYTES_PER_JOB: usize = 1 << 20;
+
+/// The number of jobs allowed in flight per pool thread by [`Index::from_path_verified`].
+const JOBS_PER_THREAD: usize = 2;
+
+/// The start of a block in both the compressed and uncompressed streams.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct IndexEntry {
+    /// The offset of the block in the compressed stream.
+    pub compressed_offset: u64,
+    /// The offset of the block's first byte in the uncompressed stream.
+    pub uncompressed_offset: u64,
+}
+
+/// A `.gzi` index mapping uncompressed offsets to the blocks that hold them.
+///
+/// As in the `.gzi` format, the first block at offset zero is implied and there is one entry for
+/// the end of every data block, so the last entry is where the EOF marker starts.
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::Index;
+///
+/// let mut gzi = vec![];
+/// Index::new().write_gzi(&mut gzi).unwrap();
+/// assert_eq!(Index::read_gzi(gzi.as_slice()).unwrap(), Index::new());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Index {
+    entries: Vec<IndexEntry>,
+}
+
+impl Index {
+    /// Create an empty [`Index`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The entries of the index, in order, excluding the implied first block.
+    pub fn entries(&self) -> &[IndexEntry] {
+        &self.entries
+    }
+
+    /// Build the index of the BGZF file at `path` by scanning its blocks.
+    ///
+    /// See [`Index::from_reader`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::{Index, TeeWriter};
+    /// use std::fs::File;
+    /// use std::io::Write;
+    ///
+    /// let dir = tempfile::tempdir().unwrap();
+    /// let path = dir.path().join("example.gz");
+    /// let mut gzi = vec![];
+    /// let mut writer = TeeWriter::new(File::create(&path).unwrap(), &mut gzi, 2.try_into().unwrap());
+    /// writer.write_all(&vec![b'A'; 100_000]).unwrap();
+    /// writer.finish().unwrap();
+    ///
+    /// assert_eq!(Index::from_path(&path).unwrap(), Index::read_gzi(gzi.as_slice()).unwrap());
+    /// ```
+    pub fn from_path<P>(path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Self::from_reader(File::open(path)?)
+    }
+
+    /// Build the index of the BGZF file at `path` while checking every block's CRC on `pool`.
+    ///
+    /// The calling thread reads the file and builds the index from the block headers and footers,
+    /// as [`Index::from_path`] does, while batches of blocks are decompressed on `pool` to check
+    /// them. With enough threads this runs at the speed of reading the file. Fails with
+    /// [`io::ErrorKind::InvalidData`] naming the first corrupt block found.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::{Index, ThreadPool, Writer};
+    /// use std::io::Write;
+    ///
+    /// let dir = tempfile::tempdir().unwrap();
+    /// let path = dir.path().join("example.gz");
+    /// let mut writer = Writer::from_path(&path, 2.try_into().unwrap()).unwrap();
+    /// writer.write_all(&b"ACGT".repeat(100_000)).unwrap();
+    /// writer.finish().unwrap();
+    ///
+    /// let index = Index::from_path_verified(&path, ThreadPool::global()).unwrap();
+    /// assert_eq!(index, Index::from_path(&path).unwrap());
+    /// ```
+    pub fn from_path_verified<P>(path: P, pool: &ThreadPool) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let mut reader = BufReader::with_capacity(BUFSIZE, File::open(path)?);
+        let mut index = Self::new();
+        let mut header = [0; BGZF_HEADER_SIZE];
+        let mut block = BlockInfo {
+            compressed_offset: 0,
+            compressed_size: 0,
+            uncompressed_offset: 0,
+            uncompressed_size: 0,
+        };
+        let mut pending = VecDeque::new();
+        let max_pending = pool.num_threads() * JOBS_PER_THREAD;
+        let mut batch = Vec::with_capacity(BYTES_PER_JOB + MAX_BGZF_BLOCK_SIZE);
+        let mut batch_offset = 0;
+        loop {
+            match read_header(&mut reader, &mut header)? {
+                0 => break,
+                BGZF_HEADER_SIZE => {}
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "Truncated BGZF block",
+                    ))
+                }
+            }
+            block.compressed_size =
+                block_size(&header).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            let start = batch.len();
+            batch.extend_from_slice(&header);
+            batch.resize(start + block.compressed_size, 0);
+            reader.read_exact(&mut batch[start + BGZF_HEADER_SIZE..])?;
+            block.uncompressed_size = LittleEndian::read_u32(&batch[batch.len() - 4..]) as usize;
+            check_uncompressed_size(block.uncompressed_size)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+            if block.uncompressed_size > 0 {
+                index.add_block(&block);
+            }
+            block.compressed_offset += block.compressed_size as u64;
+            block.uncompressed_offset += block.uncompressed_size as u64;
+
+            if batch.len() >= BYTES_PER_JOB {
+                if pending.len() == max_pending {
+                    wait_for(&mut pending)?;
+                }
+                pending.push_back(spawn_verification(
+                    pool,
+                    std::mem::take(&mut batch),
+                    batch_offset,
+                ));
+                batch = Vec::with_capacity(BYTES_PER_JOB + MAX_BGZF_BLOCK_SIZE);
+                batch_offset = block.compressed_offset;
+            }
+        }
+        if !batch.is_empty() {
+            pending.push_back(spawn_verification(pool, batch, batch_offset));
+        }
+        while !pending.is_empty() {
+            wait_for(&mut pending)?;
+        }
+        Ok(index)
+    }
+
+    /// Build the index of the BGZF data in `reader` by scanning its blocks.
+    ///
+    /// Only each block's header and uncompressed size are read, the compressed data is skipped
+    /// over and nothing is decompressed. Empty blocks, such as the EOF marker, get no entry.
+    pub fn from_reader<R>(mut reader: R) -> io::Result<Self>
+    where
+        R: Read + Seek,
+    {
+        let mut index = Self::new();
+        let mut header = [0; BGZF_HEADER_SIZE];
+        let mut block = BlockInfo {
+            compressed_offset: 0,
+            compressed_size: 0,
+            uncompressed_offset: 0,
+            uncompressed_size: 0,
+        };
+        loop {
+            match read_header(&mut reader, &mut header)? {
+                0 => break,
+                BGZF_HEADER_SIZE => {}
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "Truncated BGZF block",
+                    ))
+                }
+            }
+            block.compressed_size =
+                block_size(&header).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            // Skip to the ISIZE field at the very end of the block.
+            reader
+                .seek(SeekFrom::Current((block.compressed_size - BGZF_HEADER_SIZE - 4) as i64))?;
+            block.uncompressed_size = reader.read_u32::<LittleEndian>()? as usize;
+            check_uncompressed_size(block.uncompressed_size)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+            if block.uncompressed_size > 0 {
+                index.add_block(&block);
+            }
+            block.compressed_offset += block.compressed_size as u64;
+            block.uncompressed_offset += block.uncompressed_size as u64;
+        }
+        Ok(index)
+    }
+
+    /// Merge the indexes of several shards into the index of their concatenation by [`crate::concat`].
+    ///
+    /// Each shard is expected to end with its last data block followed by a single EOF marker, as
+    /// written by this crate's writers, so that its last entry is where its data ends.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::{concat, Index, TeeWriter};
+    /// use std::io::{Cursor, Write};
+    ///
+    /// let mut shards = vec![];
+    /// let mut indexes = vec![];
+    /// for text in [&b"hello "[..], &b"world"[..]] {
+    ///     let (mut data, mut gzi) = (vec![], vec![]);
+    ///     let mut writer = TeeWriter::new(&mut data, &mut gzi, 2.try_into().unwrap());
+    ///     writer.write_all(text).unwrap();
+    ///     writer.finish().unwrap();
+    ///     shards.push(data);
+    ///     indexes.push(Index::read_gzi(gzi.as_slice()).unwrap());
+    /// }
+    ///
+    /// let mut combined = vec![];
+    /// concat(shards.iter().map(|shard| shard.as_slice()), &mut combined).unwrap();
+    /// assert_eq!(Index::merge(&indexes), Index::from_reader(Cursor::new(combined)).unwrap());
+    /// ```
+    pub fn merge<'a, I>(indexes: I) -> Self
+    where
+        I: IntoIterator<Item = &'a Index>,
+    {
+        let mut merged = Self::new();
+        let mut start = IndexEntry { compressed_offset: 0, uncompressed_offset: 0 };
+        for index in indexes {
+            merged.entries.extend(index.entries.iter().map(|entry| IndexEntry {
+                compressed_offset: start.compressed_offset + entry.compressed_offset,
+                uncompressed_offset: start.uncompressed_offset + entry.uncompressed_offset,
+            }));
+            start = merged.entries.last().copied().unwrap_or(start);
+        }
+        merged
+    }
+
+    /// Read an index in the `.gzi` format.
+    pub fn read_gzi<R>(mut reader: R) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        let count = reader.read_u64::<LittleEndian>()?;
+        // Don't trust the count for the allocation, a corrupt file could claim anything.
+        let mut entries = Vec::with_capacity(std::cmp::min(count, 1 << 16) as usize);
+        for _ in 0..count {
+            let compressed_offset = reader.read_u64::<LittleEndian>()?;
+            let uncompressed_offset = reader.read_u64::<LittleEndian>()?;
+            entries.push(IndexEntry { compressed_offset, uncompressed_offset });
+        }
+        Ok(Self { entries })
+    }
+
+    /// Write the index in the `.gzi` format.
+    pub fn write_gzi<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        writer.write_u64::<LittleEndian>(self.entries.len() as u64)?;
+        for entry in &self.entries {
+            writer.write_u64::<LittleEndian>(entry.compressed_offset)?;
+            writer.write_u64::<LittleEndian>(entry.uncompressed_offset)?;
+        }
+        Ok(())
+    }
+
+    /// Record the end of the data block `block`.
+    pub(crate) fn add_block(&mut self, block: &BlockInfo) {
+        self.entries.push(IndexEntry {
+            compressed_offset: block.compressed_offset + block.compressed_size as u64,
+            uncompressed_offset: block.uncompressed_offset + block.uncompressed_size as u64,
+        });
+    }
+}
+
+/// The eventual result of checking a batch of blocks on the pool.
+type PendingCheck = Receiver<io::Result<()>>;
+
+/// Queue the whole blocks in `batch`, which starts at compressed offset `offset`, to be
+/// decompressed on `pool` to check their CRCs.
+fn spawn_verification(pool: &ThreadPool, batch: Vec<u8>, offset: u64) -> PendingCheck {
+    let (sender, receiver) = bounded(1);
+    pool.spawn(move || {
+        let mut decompressor = Decompressor::new();
+        let mut output = Vec::with_capacity(MAX_BGZF_BLOCK_SIZE);
+        let mut start = 0;
+        let result = loop {
+            if start == batch.len() {
+                break Ok(());
+            }
+            let checked = block_size(&batch[start..]).and_then(|size| {
+                decompressor.decompress_block(&batch[start..start + size], &mut output)?;
+                Ok(size)
+            });
+            match checked {
+                Ok(size) => start += size,
+                Err(e) => {
+                    break Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "Corrupt block at compressed offset {}: {}",
+                            offset + start as u64,
+                            e
+                        ),
+                    ))
+                }
+            }
+        };
+        let _ = sender.send(result);
+    });
+    receiver
+}
+
+/// Wait for the oldest batch queued by [`spawn_verification`].
+fn wait_for(pending: &mut VecDeque<PendingCheck>) -> io::Result<()> {
+    let check = pending.pop_front().expect("only called with checks pending");
+    check.recv().unwrap_or_else(|_| Err(io::Error::new(io::ErrorKind::Other, worker_exited())))
+}
+
+/// Read as much of a block header as is available, returning how many bytes were read.
+pub(crate) fn read_header<R>(reader: &mut R, header: &mut [u8]) -> io::Result<usize>
+where
+    R: Read,
+{
+    let mut filled = 0;
+    while filled < header.len() {
+        match reader.read(&mut header[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// The start of every block in an [`Index`], including the implied first one, then the end of the
+/// data, for looking up the block that holds an uncompressed offset.
+#[derive(Debug, Clone)]
+pub(crate) struct BlockStarts(Vec<IndexEntry>);
+
+impl BlockStarts {
+    pub(crate) fn new(index: &Index) -> Self {
+        let mut starts = vec![IndexEntry { compressed_offset: 0, uncompressed_offset: 0 }];
+        starts.extend_from_slice(index.entries());
+        Self(starts)
+    }
+
+    /// The total number of uncompressed bytes.
+    pub(crate) fn uncompressed_len(&self) -> u64 {
+        self.0.last().map_or(0, |entry| entry.uncompressed_offset)
+    }
+
+    /// The number of data blocks.
+    pub(crate) fn blocks(&self) -> usize {
+        self.0.len() - 1
+    }
+
+    /// The start of the `block`th block.
+    pub(crate) fn get(&self, block: usize) -> IndexEntry {
+        self.0[block]
+    }
+
+    /// The number of the block holding the uncompressed offset `pos`.
+    ///
+    /// `pos` must be less than [`BlockStarts::uncompressed_len`].
+    pub(crate) fn containing(&self, pos: u64) -> usize {
+        // The first start is always zero, so at least one entry is at or before `pos`.
+        self.0.partition_point(|entry| entry.uncompressed_offset <= pos) - 1
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::{
+        compress_parallel, test::test_input, CompressionLevel, IndexedReader, PositionedReader,
+        Writer, BGZF_BLOCK_SIZE, BGZF_EOF,
+    };
+
+    #[test]
+    fn test_index_skips_empty_blocks() {
+        let input = test_input(300_000);
+        // Flushing mid-stream leaves EOF markers between the data blocks.
+        let mut compressed = vec![];
+        let mut writer = Writer::new(&mut compressed, CompressionLevel::new(3).unwrap());
+        for chunk in input.chunks(70_000) {
+            writer.write_all(chunk).unwrap();
+            writer.flush().unwrap();
+        }
+        drop(writer);
+
+        let index = Index::from_reader(io::Cursor::new(&compressed)).unwrap();
+        assert_eq!(index.entries().last().unwrap().uncompressed_offset, input.len() as u64);
+        let mut reader = IndexedReader::new(io::Cursor::new(&compressed), index.clone());
+        let positioned = PositionedReader::new(compressed.as_slice(), index);
+        for start in (0..input.len() - 1000).step_by(9_999) {
+            let mut buf = vec![0; 1000];
+            reader.seek(io::SeekFrom::Start(start as u64)).unwrap();
+            reader.read_exact(&mut buf).unwrap();
+            assert_eq!(buf, &input[start..start + 1000]);
+            positioned.read_exact_at(start as u64, &mut buf).unwrap();
+            assert_eq!(buf, &input[start..start + 1000]);
+        }
+
+        let mut truncated = compressed.clone();
+        truncated.truncate(compressed.len() - BGZF_EOF.len() + 5);
+        assert!(Index::from_reader(io::Cursor::new(&truncated)).is_err());
+    }
+
+    #[test]
+    fn test_index_from_path_verified() {
+        let input = test_input(5_000_000);
+        let mut compressed = compress_parallel(&input, CompressionLevel::new(3).unwrap()).unwrap();
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("verified.gz");
+        std::fs::write(&path, &compressed).unwrap();
+        let pool = ThreadPool::new(3);
+        let index = Index::from_path_verified(&path, &pool).unwrap();
+        assert_eq!(index, Index::from_path(&path).unwrap());
+        assert_eq!(index.entries().len(), (input.len() + BGZF_BLOCK_SIZE - 1) / BGZF_BLOCK_SIZE);
+
+        // Flip a bit in the compressed data of a block near the end, past the first batch.
+        let block = index.entries()[index.entries().len() - 2];
+        compressed[block.compressed_offset as usize + 1_000] ^= 0x10;
+        std::fs::write(&path, &compressed).unwrap();
+        assert_eq!(Index::from_path(&path).unwrap(), index);
+        let error = Index::from_path_verified(&path, &pool).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert!(error.to_string().starts_with(&format!(
+            "Corrupt block at compressed offset {}",
+            block.compressed_offset
+        )));
+    }
+}
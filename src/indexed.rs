@@ -0,0 +1,427 @@
+//! Random access to BGZF data through its [`Index`].
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom, Take},
+    num::NonZeroUsize,
+    ops::Range,
+    path::Path,
+};
+
+use bytes::Bytes;
+use lru::LruCache;
+
+use crate::{core::block_size, BlockSource, BlockStarts, Decompressor, Index, BGZF_HEADER_SIZE};
+
+/// A BGZF reader that can seek to any uncompressed offset using an [`Index`].
+///
+/// Blocks are fetched from any [`Read`] + [`Seek`], or from any other storage that implements
+/// [`BlockSource`].
+///
+/// Decompressed blocks can be kept in an LRU cache (see [`IndexedReader::set_cache_capacity`]) so
+/// that repeatedly reading the same regions doesn't decompress the same blocks again.
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{IndexedReader, TeeWriter};
+/// use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+///
+/// let (mut data, mut gzi) = (vec![], vec![]);
+/// let mut writer = TeeWriter::new(&mut data, &mut gzi, 2.try_into().unwrap());
+/// writer.write_all(&b"0123456789".repeat(10_000)).unwrap();
+/// writer.finish().unwrap();
+///
+/// let index = bgzf::Index::read_gzi(gzi.as_slice()).unwrap();
+/// let mut reader = IndexedReader::new(Cursor::new(data), index);
+/// reader.set_cache_capacity(16);
+/// reader.seek(SeekFrom::Start(70_003)).unwrap();
+/// let mut buf = [0; 4];
+/// reader.read_exact(&mut buf).unwrap();
+/// assert_eq!(&buf, b"3456");
+/// ```
+pub struct IndexedReader<R> {
+    reader: R,
+    /// Where each block starts
+    starts: BlockStarts,
+    decompressor: Decompressor,
+    /// The buffer to reuse for compressed blocks
+    compressed_buffer: Vec<u8>,
+    /// The position in `starts` of the block held in `block`, if any
+    block_index: Option<usize>,
+    /// The decompressed current block
+    block: Bytes,
+    /// The current uncompressed position
+    pos: u64,
+    /// Recently decompressed blocks keyed by their compressed offset, if enabled
+    cache: Option<LruCache<u64, Bytes>>,
+}
+
+impl<R> IndexedReader<R> {
+    /// Create an [`IndexedReader`] over `reader` using the `index` of its blocks.
+    pub fn new(reader: R, index: Index) -> Self {
+        Self {
+            reader,
+            starts: BlockStarts::new(&index),
+            decompressor: Decompressor::new(),
+            compressed_buffer: Vec::new(),
+            block_index: None,
+            block: Bytes::new(),
+            pos: 0,
+            cache: None,
+        }
+    }
+
+    /// Keep up to `blocks` decompressed blocks in an LRU cache, or disable caching with zero.
+    ///
+    /// Each cached block takes up to 64 KiB.
+    pub fn set_cache_capacity(&mut self, blocks: usize) {
+        match (NonZeroUsize::new(blocks), self.cache.as_mut()) {
+            (Some(capacity), Some(cache)) => cache.resize(capacity),
+            (Some(capacity), None) => self.cache = Some(LruCache::new(capacity)),
+            (None, _) => self.cache = None,
+        }
+    }
+
+    /// The total number of uncompressed bytes, according to the index.
+    pub fn uncompressed_len(&self) -> u64 {
+        self.starts.uncompressed_len()
+    }
+
+    /// The number of data blocks, according to the index.
+    pub fn block_count(&self) -> usize {
+        self.starts.blocks()
+    }
+
+    /// Seek to the start of the `block`th data block, counting from zero, and return the
+    /// uncompressed offset it starts at.
+    ///
+    /// Blocks are numbered as in the index, so empty blocks such as EOF markers don't count.
+    /// Seeking to block [`IndexedReader::block_count`] goes to the end of the data. This makes it
+    /// easy to split the work of reading a file by blocks, for example between clones of this
+    /// reader.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::{Index, IndexedReader, TeeWriter};
+    /// use std::io::{Cursor, Read, Write};
+    ///
+    /// let (mut data, mut gzi) = (vec![], vec![]);
+    /// let mut writer = TeeWriter::new(&mut data, &mut gzi, 2.try_into().unwrap());
+    /// writer.write_all(&b"0123456789".repeat(10_000)).unwrap();
+    /// writer.finish().unwrap();
+    ///
+    /// let index = Index::read_gzi(gzi.as_slice()).unwrap();
+    /// let mut reader = IndexedReader::new(Cursor::new(data), index);
+    /// assert_eq!(reader.block_count(), 2);
+    /// let start = reader.seek_to_block(1).unwrap();
+    /// assert_eq!(start, 65_280);
+    /// let mut rest = vec![];
+    /// reader.read_to_end(&mut rest).unwrap();
+    /// assert_eq!(rest.len() as u64, 100_000 - start);
+    ///
+    /// assert!(reader.seek_to_block(3).is_err());
+    /// ```
+    pub fn seek_to_block(&mut self, block: usize) -> io::Result<u64> {
+        if block > self.block_count() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Block {} is past the {} blocks in the index", block, self.block_count()),
+            ));
+        }
+        self.pos = self.starts.get(block).uncompressed_offset;
+        Ok(self.pos)
+    }
+
+    /// Get a reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Get a mutable reference to the inner reader.
+    ///
+    /// Any cached blocks are kept, so the data must not be changed.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Unwrap this [`IndexedReader`], returning the inner reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R> Clone for IndexedReader<R>
+where
+    R: Clone,
+{
+    /// Clone this reader into an independent cursor at the same position, with a copy of the
+    /// block cache, so that different threads can read different regions.
+    ///
+    /// Every block is fetched with a seek, but the inner reader's clone should still keep its own
+    /// position, as a [`std::io::Cursor`] does, for clones to be used at the same time. See
+    /// [`crate::PositionedReader`] for sharing one [`File`] between threads instead.
+    fn clone(&self) -> Self {
+        Self {
+            reader: self.reader.clone(),
+            starts: self.starts.clone(),
+            decompressor: Decompressor::new(),
+            compressed_buffer: Vec::new(),
+            block_index: self.block_index,
+            block: self.block.clone(),
+            pos: self.pos,
+            cache: self.cache.clone(),
+        }
+    }
+}
+
+impl<R> IndexedReader<R>
+where
+    R: BlockSource,
+{
+    /// Return a reader over exactly the uncompressed bytes in `range`.
+    ///
+    /// Fails if the range is reversed or extends past the end of the data.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::{Index, IndexedReader, TeeWriter};
+    /// use std::io::{Cursor, Read, Write};
+    ///
+    /// let (mut data, mut gzi) = (vec![], vec![]);
+    /// let mut writer = TeeWriter::new(&mut data, &mut gzi, 2.try_into().unwrap());
+    /// writer.write_all(&b"0123456789".repeat(10_000)).unwrap();
+    /// writer.finish().unwrap();
+    ///
+    /// let index = Index::read_gzi(gzi.as_slice()).unwrap();
+    /// let mut reader = IndexedReader::new(Cursor::new(data), index);
+    /// let mut slice = String::new();
+    /// reader.range(65_278..65_284).unwrap().read_to_string(&mut slice).unwrap();
+    /// assert_eq!(slice, "890123");
+    /// ```
+    pub fn range(&mut self, range: Range<u64>) -> io::Result<Take<&mut Self>> {
+        if range.start > range.end || range.end > self.uncompressed_len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Range {}..{} is not within the {} bytes of data",
+                    range.start,
+                    range.end,
+                    self.uncompressed_len()
+                ),
+            ));
+        }
+        self.seek(SeekFrom::Start(range.start))?;
+        Ok(self.take(range.end - range.start))
+    }
+
+    /// Make the block at `index` in `starts` the current block.
+    fn load_block(&mut self, index: usize) -> io::Result<()> {
+        let offset = self.starts.get(index).compressed_offset;
+        if let Some(block) = self.cache.as_mut().and_then(|cache| cache.get(&offset)) {
+            self.block = block.clone();
+            self.block_index = Some(index);
+            return Ok(());
+        }
+
+        // Empty blocks, such as EOF markers written by a flush, hold nothing an index can point
+        // into, so move past them to the block holding the data.
+        let mut position = offset;
+        let block = loop {
+            self.reader.fetch_block(position, &mut self.compressed_buffer)?;
+            let size = fetched_block_size(&self.compressed_buffer)?;
+            self.compressed_buffer.truncate(size);
+
+            let block = decompress_block(&mut self.decompressor, &self.compressed_buffer)?;
+            if !block.is_empty() {
+                break Bytes::from(block);
+            }
+            position += size as u64;
+        };
+        if let Some(cache) = self.cache.as_mut() {
+            cache.put(offset, block.clone());
+        }
+        self.block = block;
+        self.block_index = Some(index);
+        Ok(())
+    }
+}
+
+impl IndexedReader<File> {
+    /// Open the BGZF file at `path` with the index in the `.gzi` file next to it.
+    pub fn from_path<P>(path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let mut gzi_path = path.as_os_str().to_owned();
+        gzi_path.push(".gzi");
+        let index = Index::read_gzi(io::BufReader::new(File::open(gzi_path)?))?;
+        Ok(Self::new(File::open(path)?, index))
+    }
+}
+
+impl<R> Read for IndexedReader<R>
+where
+    R: BlockSource,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() || self.pos >= self.uncompressed_len() {
+            return Ok(0);
+        }
+        let index = self.starts.containing(self.pos);
+        if self.block_index != Some(index) {
+            self.load_block(index)?;
+        }
+
+        let offset = (self.pos - self.starts.get(index).uncompressed_offset) as usize;
+        if offset >= self.block.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Block is smaller than the index says",
+            ));
+        }
+        let amount = std::cmp::min(buf.len(), self.block.len() - offset);
+        buf[..amount].copy_from_slice(&self.block[offset..offset + amount]);
+        self.pos += amount as u64;
+        Ok(amount)
+    }
+}
+
+impl<R> Seek for IndexedReader<R>
+where
+    R: BlockSource,
+{
+    /// Seek to an uncompressed offset, which only reads data once the next read happens.
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => self.uncompressed_len().checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.pos.checked_add_signed(delta),
+        };
+        self.pos = target.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.pos)
+    }
+}
+
+/// Check a block fetched from a [`BlockSource`] and return its size, which may be less than what
+/// was fetched.
+fn fetched_block_size(fetched: &[u8]) -> io::Result<usize> {
+    let truncated = || io::Error::new(io::ErrorKind::UnexpectedEof, "Fetched a partial block");
+    if fetched.len() < BGZF_HEADER_SIZE {
+        return Err(truncated());
+    }
+    let size = block_size(fetched).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    if fetched.len() < size {
+        return Err(truncated());
+    }
+    Ok(size)
+}
+
+/// Decompress a whole block, header and footer included, into a new buffer.
+pub(crate) fn decompress_block(
+    decompressor: &mut Decompressor,
+    block: &[u8],
+) -> io::Result<Vec<u8>> {
+    let mut decompressed = vec![];
+    decompressor
+        .decompress_block(block, &mut decompressed)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    Ok(decompressed)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::{test::test_input, CompressionLevel, PositionedReader, TeeWriter};
+
+    #[test]
+    fn test_indexed_reader() {
+        let input = test_input(1_000_000);
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test.bgz");
+        let mut writer = TeeWriter::new(
+            File::create(&path).unwrap(),
+            File::create(dir.path().join("test.bgz.gzi")).unwrap(),
+            CompressionLevel::new(3).unwrap(),
+        );
+        writer.write_all(&input).unwrap();
+        writer.finish().unwrap();
+
+        let mut reader = IndexedReader::from_path(&path).unwrap();
+        assert_eq!(reader.uncompressed_len(), input.len() as u64);
+        for &(start, len) in &[(0, 10), (65_270, 20), (500_000, 200_000), (999_990, 10)] {
+            reader.seek(io::SeekFrom::Start(start as u64)).unwrap();
+            let mut buf = vec![0; len];
+            reader.read_exact(&mut buf).unwrap();
+            assert_eq!(buf, &input[start..start + len]);
+        }
+        assert_eq!(reader.read(&mut [0; 10]).unwrap(), 0);
+        assert_eq!(reader.seek(io::SeekFrom::End(-5)).unwrap(), input.len() as u64 - 5);
+        let mut tail = vec![];
+        reader.read_to_end(&mut tail).unwrap();
+        assert_eq!(tail, &input[input.len() - 5..]);
+        assert!(reader.seek(io::SeekFrom::Current(-(input.len() as i64) - 1)).is_err());
+
+        let mut slice = vec![];
+        reader.range(130_000..200_123).unwrap().read_to_end(&mut slice).unwrap();
+        assert_eq!(slice, &input[130_000..200_123]);
+        let (start, end) = (10, 5);
+        assert!(reader.range(start..end).is_err());
+        assert!(reader.range(0..input.len() as u64 + 1).is_err());
+
+        // Positioned reads of one shared file from many threads.
+        let index = Index::read_gzi(File::open(dir.path().join("test.bgz.gzi")).unwrap()).unwrap();
+        let reader = PositionedReader::new(File::open(&path).unwrap(), index);
+        std::thread::scope(|scope| {
+            for thread in 0..4 {
+                let (reader, input) = (&reader, &input);
+                scope.spawn(move || {
+                    for start in (thread * 1000..input.len() - 100_000).step_by(97_000) {
+                        let mut buf = vec![0; 100_000];
+                        reader.read_exact_at(start as u64, &mut buf).unwrap();
+                        assert_eq!(buf, &input[start..start + 100_000]);
+                    }
+                });
+            }
+        });
+        let mut buf = vec![0; 10];
+        assert!(reader.read_exact_at(input.len() as u64 - 5, &mut buf).is_err());
+        let reader = std::sync::Arc::new(PositionedReader::from_path(&path).unwrap());
+        std::thread::scope(|scope| {
+            for thread in 0..4_u64 {
+                let (reader, input) = (std::sync::Arc::clone(&reader), &input);
+                scope.spawn(move || {
+                    let range = thread * 200_000 + 7..thread * 200_000 + 70_007;
+                    let slice = reader.range(range.clone()).unwrap();
+                    assert_eq!(slice, &input[range.start as usize..range.end as usize]);
+                });
+            }
+        });
+        assert!(reader.range(0..input.len() as u64 + 1).is_err());
+
+        // Once cached, blocks are served without touching the data again.
+        let compressed = std::fs::read(&path).unwrap();
+        let index = Index::read_gzi(File::open(dir.path().join("test.bgz.gzi")).unwrap()).unwrap();
+        let mut reader = IndexedReader::new(io::Cursor::new(compressed), index);
+        reader.set_cache_capacity(4);
+        let mut first = vec![0; 100_000];
+        reader.read_exact(&mut first).unwrap();
+        reader.get_mut().get_mut().clear();
+        reader.seek(io::SeekFrom::Start(0)).unwrap();
+        let mut again = vec![0; 100_000];
+        reader.read_exact(&mut again).unwrap();
+        assert_eq!(first, again);
+        assert_eq!(first, &input[..100_000]);
+    }
+}
@@ -0,0 +1,194 @@
+//! Writing compressed blocks on a dedicated I/O thread.
+use std::{
+    io::{self, Write},
+    thread::{self, JoinHandle},
+};
+
+use crossbeam_channel::{bounded, Sender};
+
+use crate::{CompressionLevel, Writer};
+
+/// A message to the I/O thread.
+enum Message {
+    /// Bytes to write
+    Data(Vec<u8>),
+    /// Flush the inner writer, then acknowledge on the given channel
+    Flush(Sender<()>),
+}
+
+/// A sink that hands compressed blocks to a background thread that writes them out.
+///
+/// Writes return as soon as the bytes are queued, so an I/O error surfaces on a later `write`,
+/// `flush`, or [`IoThreadSink::into_inner`] rather than on the call that queued the bytes.
+///
+/// See [`Writer::with_io_thread`].
+pub struct IoThreadSink<W> {
+    /// The queue of messages to the I/O thread
+    sender: Option<Sender<Message>>,
+    /// The I/O thread, which returns the inner writer once the queue is closed
+    handle: Option<JoinHandle<io::Result<W>>>,
+}
+
+impl<W> IoThreadSink<W>
+where
+    W: Write + Send + 'static,
+{
+    /// Spawn a thread writing to `writer` with up to `blocks` writes queued for it.
+    pub fn new(writer: W, blocks: usize) -> Self {
+        let (sender, receiver) = bounded::<Message>(blocks);
+        let handle = thread::Builder::new()
+            .name("bgzf-writer".to_string())
+            .spawn(move || {
+                let mut writer = writer;
+                // Stop at the first error; the closed queue tells the sender to collect it.
+                for message in receiver {
+                    match message {
+                        Message::Data(bytes) => writer.write_all(&bytes)?,
+                        Message::Flush(done) => {
+                            writer.flush()?;
+                            let _ = done.send(());
+                        }
+                    }
+                }
+                Ok(writer)
+            })
+            .expect("Failed to spawn bgzf writer thread");
+        Self { sender: Some(sender), handle: Some(handle) }
+    }
+
+    /// Wait for all queued writes to complete and return the inner writer.
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.sender = None;
+        self.join()
+    }
+
+    /// Queue `message`, or collect the error that stopped the I/O thread.
+    fn send(&mut self, message: Message) -> io::Result<()> {
+        let sent = match self.sender.as_ref() {
+            Some(sender) => sender.send(message).is_ok(),
+            None => false,
+        };
+        if sent {
+            Ok(())
+        } else {
+            self.sender = None;
+            self.join().map(drop)
+        }
+    }
+
+    /// Wait for the I/O thread to exit and return its result.
+    fn join(&mut self) -> io::Result<W> {
+        match self.handle.take() {
+            Some(handle) => handle.join().unwrap_or_else(|_| {
+                Err(io::Error::new(io::ErrorKind::Other, "bgzf writer thread panicked"))
+            }),
+            None => Err(io::Error::new(io::ErrorKind::BrokenPipe, "bgzf writer thread has exited")),
+        }
+    }
+}
+
+impl<W> Write for IoThreadSink<W>
+where
+    W: Write + Send + 'static,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.send(Message::Data(buf.to_vec()))?;
+        Ok(buf.len())
+    }
+
+    /// Wait for every queued write to complete, then flush the inner writer.
+    fn flush(&mut self) -> io::Result<()> {
+        let (done, flushed) = bounded(1);
+        self.send(Message::Flush(done))?;
+        match flushed.recv() {
+            Ok(()) => Ok(()),
+            Err(_) => {
+                self.sender = None;
+                self.join().map(drop)
+            }
+        }
+    }
+}
+
+impl<W> Drop for IoThreadSink<W> {
+    fn drop(&mut self) {
+        // Errors can't be reported from drop, call `into_inner` to observe them.
+        self.sender = None;
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl<W> Writer<IoThreadSink<W>>
+where
+    W: Write + Send + 'static,
+{
+    /// Create a BGZF writer that compresses on the calling thread and writes the compressed
+    /// blocks to `writer` on a background thread, with up to `blocks` blocks queued between them.
+    ///
+    /// Compressing one block overlaps with writing the previous one, which recovers much of the
+    /// speedup of [`crate::ParallelWriter`] when the output is slow, for a single extra thread.
+    /// Since writes complete in the background, an I/O error is reported by a later call, and
+    /// [`Writer::into_pending`] can't tell which data was written.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::{decompress_all, Writer};
+    /// use std::io::Write;
+    ///
+    /// let mut writer = Writer::with_io_thread(vec![], 2.try_into().unwrap(), 4);
+    /// writer.write_all(b"hello world").unwrap();
+    /// let (sink, _summary) = writer.finish().unwrap();
+    /// let compressed = sink.into_inner().unwrap();
+    /// assert_eq!(decompress_all(&compressed).unwrap(), b"hello world");
+    /// ```
+    pub fn with_io_thread(writer: W, compression_level: CompressionLevel, blocks: usize) -> Self {
+        Self::new(IoThreadSink::new(writer, blocks), compression_level)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::test_input;
+
+    #[test]
+    fn test_io_thread_writer_matches_writer() {
+        let input = test_input(300_000);
+        let level = CompressionLevel::new(3).unwrap();
+
+        let mut writer = Writer::new(vec![], level);
+        writer.write_all(&input).unwrap();
+        let (expected, expected_summary) = writer.finish().unwrap();
+
+        let mut writer = Writer::with_io_thread(vec![], level, 2);
+        for chunk in input.chunks(10_000) {
+            writer.write_all(chunk).unwrap();
+        }
+        writer.flush().unwrap();
+        let (sink, summary) = writer.finish().unwrap();
+        assert_eq!(sink.into_inner().unwrap(), expected);
+        assert_eq!(summary, expected_summary);
+    }
+
+    #[test]
+    fn test_io_thread_writer_reports_errors() {
+        struct Failing;
+        impl Write for Failing {
+            fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+                Err(io::Error::new(io::ErrorKind::PermissionDenied, "denied"))
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut writer = Writer::with_io_thread(Failing, CompressionLevel::new(3).unwrap(), 1);
+        writer.write_all(b"hello").unwrap();
+        assert_eq!(writer.flush().unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+        assert!(writer.flush().is_err());
+        drop(writer.into_pending());
+    }
+}
@@ -20,32 +20,211 @@
 //!     Ok(())
 //! }
 //! ```
-#![forbid(unsafe_code)]
+//!
+//! # Features
+//!
+//! - `digest`: implement [`StreamDigest`] for every RustCrypto hash, such as MD5 or SHA-256, to
+//!   digest the uncompressed data a [`Reader`] reads or a [`Writer`] writes.
+//! - `flate2`: enable [`MultiMemberReader`], which reads streams that mix BGZF blocks with ordinary
+//!   gzip members, and conversion of a `flate2::Compression` to a [`CompressionLevel`].
+//! - `futures-io`: enable [`AsyncReader`] and [`AsyncWriter`], which implement the `futures-io`
+//!   traits used by async-std, smol, and other `futures` based runtimes.
+//! - `http`: enable [`HttpSource`] and `IndexedReader::from_url`, which fetch blocks from a web
+//!   server with HTTP(S) range requests.
+//! - `io-uring`: enable `Reader::from_path_uring` and `Writer::from_path_uring`, which overlap file
+//!   I/O with (de)compression using Linux io_uring. This has no effect on other platforms.
+//! - `mmap`: enable `Reader::from_mmap` for decoding blocks directly from a memory mapped file.
+//! - `object-store`: enable [`ObjectStoreSource`] and [`ObjectStoreSink`], which read and write
+//!   objects in cloud storage through the [`object_store`](https://docs.rs/object_store) crate.
+//!   Enable that crate's `aws`, `gcp`, or `azure` features for the stores themselves.
+//! - `read-buf`: implement the unstable `Read::read_buf` for [`Reader`], which fills
+//!   uninitialized `BorrowedBuf`s without the caller zeroing them first. This requires a nightly
+//!   compiler.
+//! - `sha256`: enable [`DigestManifest`], which records the SHA-256 digest of every block's data
+//!   and of the whole stream.
+//! - `tokio`: enable [`AsyncBridgeReader`] and [`AsyncBridgeWriter`], which run a [`Reader`] or
+//!   [`Writer`] on tokio's blocking thread pool behind the tokio `AsyncRead` and `AsyncWrite` traits.
+//! - `tracing`: emit [`tracing`](https://docs.rs/tracing) spans and events for block compression,
+//!   decompression, and flushes. Span durations are reported by subscribers when spans close.
+//!
+//! # Requirements
+//!
+//! The crate needs `std`, including the sans-I/O [`core`] module. Blocks are compressed and
+//! decompressed with libdeflate through the `libdeflater` bindings, which themselves need `std`,
+//! so there is no `no_std` build.
+#![cfg_attr(feature = "read-buf", feature(read_buf, core_io_borrowed_buf, borrowed_buf_init))]
+// Memory mapping a file and submitting io_uring operations are inherently unsafe, so those
+// features relax this to a deny that is allowed only where needed.
+#![cfg_attr(not(any(feature = "mmap", feature = "io-uring")), forbid(unsafe_code))]
+#![cfg_attr(any(feature = "mmap", feature = "io-uring"), deny(unsafe_code))]
 #![allow(clippy::must_use_candidate, clippy::missing_errors_doc, clippy::missing_panics_doc)]
 
 // Re-export the reader and writer to the same level.
+mod adaptive;
+#[cfg(feature = "futures-io")]
+mod async_rw;
+mod block;
+mod boundary;
+mod buffers;
+mod checksums;
+#[cfg(feature = "object-store")]
+mod cloud;
+mod concat;
+mod contexts;
+mod copy;
+pub mod core;
+mod crc;
+#[cfg(feature = "sha256")]
+mod digest;
+mod gz;
+#[cfg(feature = "http")]
+mod http;
+mod index;
+mod indexed;
+mod io_thread;
+#[cfg(feature = "flate2")]
+mod mixed;
+pub mod noodles;
+mod oneshot;
+mod paths;
+mod pipe;
+mod pipeline;
+mod pool;
+mod positioned;
+mod progress;
 mod reader;
+mod rewrite;
+mod rolling;
+mod salvage;
+mod sequenced;
+mod stats;
+mod storage;
+mod stream_digest;
+mod tee;
+pub mod text;
+#[cfg(feature = "tokio")]
+mod tokio_bridge;
+mod transcode;
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+mod uring;
+mod validate;
+mod virtual_offset;
 mod writer;
+pub use adaptive::*;
+#[cfg(feature = "futures-io")]
+pub use async_rw::*;
+pub use block::*;
+pub use boundary::*;
+pub use buffers::*;
+pub use checksums::*;
+#[cfg(feature = "object-store")]
+pub use cloud::*;
+pub use concat::*;
+pub use contexts::*;
+pub use copy::*;
+pub use crc::*;
+#[cfg(feature = "sha256")]
+pub use digest::*;
+pub use gz::*;
+#[cfg(feature = "http")]
+pub use http::*;
+pub use index::*;
+pub use indexed::*;
+pub use io_thread::*;
+#[cfg(feature = "flate2")]
+pub use mixed::*;
+pub use oneshot::*;
+pub use paths::*;
+pub use pipe::*;
+pub use pipeline::*;
+pub use pool::*;
+pub use positioned::*;
+pub use progress::*;
 pub use reader::*;
+pub use rewrite::*;
+pub use rolling::*;
+pub use salvage::*;
+pub use sequenced::*;
+pub use stats::*;
+pub use storage::*;
+pub use stream_digest::*;
+pub use tee::*;
+#[cfg(feature = "tokio")]
+pub use tokio_bridge::*;
+pub use transcode::*;
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+pub use uring::*;
+pub use validate::*;
+pub use virtual_offset::*;
 pub use writer::*;
 
 use std::io;
 
-use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
 use libdeflater::CompressionLvl;
 use thiserror::Error;
 
+// Compile time checks that readers and writers can be sent to other threads whenever their inner
+// types can, and that (de)compressors can also be shared, so that a change to a field or a
+// dependency can't silently break multi-threaded users.
+#[allow(dead_code)]
+fn assert_thread_safety<R, W>()
+where
+    R: Send,
+    W: io::Write + Send,
+{
+    fn is_send<T: Send>() {}
+    fn is_send_sync<T: Send + Sync>() {}
+
+    is_send::<Reader<R>>();
+    is_send::<Reader<PrefetchSource>>();
+    is_send::<IndexedReader<R>>();
+    is_send::<PositionedReader<R>>();
+    is_send::<Writer<W>>();
+    is_send::<IoThreadSink<W>>();
+    is_send::<ParallelWriter<W>>();
+    is_send::<ChunkSender>();
+    is_send::<BlockReceiver>();
+    is_send_sync::<Compressor>();
+    is_send_sync::<Decompressor>();
+    is_send_sync::<CompressorPool>();
+    is_send_sync::<DecompressorPool>();
+    is_send_sync::<ThreadPool>();
+    is_send_sync::<Index>();
+    is_send_sync::<PositionedReader<std::fs::File>>();
+}
+
 /// The maximum uncompressed blocksize for BGZF compression (taken from bgzip), used for initializing blocks.
 pub const BGZF_BLOCK_SIZE: usize = 65280;
 
 /// 128 KB default buffer size, same as pigz.
 pub const BUFSIZE: usize = 128 * 1024;
 
+/// The maximum size of a whole compressed block, including its header and footer.
+///
 /// Default from bgzf: compress(BGZF_BLOCK_SIZE) < BGZF_MAX_BLOCK_SIZE
 /// 65536 which is u16::MAX + 1
-pub(crate) const MAX_BGZF_BLOCK_SIZE: usize = 64 * 1024;
+pub const MAX_BGZF_BLOCK_SIZE: usize = 64 * 1024;
 
-pub(crate) static BGZF_EOF: &[u8] = &[
+/// The empty block that marks the end of BGZF data, as written by htslib.
+///
+/// Tools that assemble BGZF files from raw blocks should end them with this, so that readers can
+/// tell a complete file from a truncated one.
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{decompress_all, Compressor, BGZF_EOF};
+///
+/// let mut file = vec![];
+/// for part in [&b"hello "[..], &b"world"[..]] {
+///     let mut block = vec![];
+///     Compressor::new(2.try_into().unwrap()).compress(part, &mut block).unwrap();
+///     file.extend_from_slice(&block);
+/// }
+/// file.extend_from_slice(BGZF_EOF);
+/// assert_eq!(decompress_all(&file).unwrap(), b"hello world");
+/// ```
+pub static BGZF_EOF: &[u8] = &[
     0x1f, 0x8b, // ID1, ID2
     0x08, // CM = DEFLATE
     0x04, // FLG = FEXTRA
@@ -61,8 +240,10 @@ pub(crate) static BGZF_EOF: &[u8] = &[
     0x00, 0x00, 0x00, 0x00, // ISIZE = 0
 ];
 
-pub(crate) const BGZF_HEADER_SIZE: usize = 18;
-pub(crate) const BGZF_FOOTER_SIZE: usize = 8;
+/// The size of a block's gzip header, including the BGZF extra field.
+pub const BGZF_HEADER_SIZE: usize = 18;
+/// The size of a block's gzip footer, the CRC32 and uncompressed size.
+pub const BGZF_FOOTER_SIZE: usize = 8;
 pub(crate) const BGZF_MAGIC_BYTE_A: u8 = 31;
 pub(crate) const BGZF_MAGIC_BYTE_B: u8 = 139;
 pub(crate) const BGZF_COMPRESSION_METHOD: u8 = 8;
@@ -81,6 +262,23 @@ pub(crate) const BGZF_COMPRESSION_HINT_OTHER: u8 = 0;
 
 const EXTRA: f64 = 0.1;
 
+/// The number of bytes in every block besides its compressed data, the header and footer.
+///
+/// A block's compressed data can be at most `MAX_BGZF_BLOCK_SIZE - block_overhead()` bytes.
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{block_overhead, BGZF_EOF};
+///
+/// // The EOF marker holds the two bytes of an empty deflate stream.
+/// assert_eq!(BGZF_EOF.len(), block_overhead() + 2);
+/// ```
+#[inline]
+pub const fn block_overhead() -> usize {
+    BGZF_HEADER_SIZE + BGZF_FOOTER_SIZE
+}
+
 /// Add 10% of the size of the input data to the size of the output amount to account for
 /// compression levels that actually increase the output datasize for some inputs (i.e totally
 /// random input data).
@@ -94,31 +292,158 @@ type BgzfResult<T> = Result<T, BgzfError>;
 #[non_exhaustive]
 #[derive(Error, Debug)]
 pub enum BgzfError {
+    #[error("Unsupported bgzip compression level: {0}, stored blocks (level 0) are not supported")]
+    BgzipCompressionLevel(i32),
     #[error("Compressed block size ({0}) exceeds max allowed: ({1})")]
     BlockSizeExceeded(usize, usize),
-    #[error("Invalid compression level: {0}")]
+    #[error("Output buffer of {0} bytes is too small for the compressed block")]
+    BufferTooSmall(usize),
+    #[error("Invalid compression level: {0}, expected 1-12")]
     CompressionLevel(u8),
+    #[error("Invalid flate2 compression level: {0}, expected 0-9")]
+    Flate2CompressionLevel(u32),
     #[error(transparent)]
     Io(#[from] io::Error),
     #[error("Invalid checksum, found {found}, expected {expected}")]
     InvalidChecksum { found: u32, expected: u32 },
+    #[error("Empty block at compressed offset {0} is followed by more data")]
+    InteriorEof(u64),
     #[error("Invalid block header: {0}")]
     InvalidHeader(&'static str),
+    #[error("Truncated BGZF block")]
+    Truncated,
+    #[error("Uncompressed block size ({0}) exceeds max allowed: ({1})")]
+    UncompressedSizeExceeded(usize, usize),
+    #[error("Block decompressed to {found} bytes, but its footer says {expected}")]
+    UncompressedSizeMismatch { found: usize, expected: usize },
     #[error("LibDeflater compression error: {0:?}")]
     LibDeflaterCompress(libdeflater::CompressionError),
     #[error(transparent)]
     LibDelfaterDecompress(#[from] libdeflater::DecompressionError),
 }
 
-/// The expected checksum and number of bytes for decompressed data.
-#[derive(Debug, Copy, Clone)]
-struct ChecksumValues {
-    /// The check sum
-    sum: u32,
-    /// The number of bytes that went into the sum
-    amount: u32,
+/// The broad kind of a [`BgzfError`], for deciding how to handle it without matching every
+/// variant.
+///
+/// Categories are stable: new error variants are added to one of these rather than to a new one.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ErrorCategory {
+    /// The data is not valid BGZF, such as a bad header, a failed checksum, or a block that
+    /// doesn't decompress
+    Corruption,
+    /// The data ends part of the way through a block
+    Truncation,
+    /// Reading or writing the underlying stream failed
+    Io,
+    /// An argument was invalid, such as a compression level out of range
+    InvalidInput,
+    /// Compressing a block failed
+    Compression,
+}
+
+impl ErrorCategory {
+    /// The category of an error returned by one of this crate's [`io::Read`] or [`io::Write`]
+    /// implementations, looking through to the [`BgzfError`] inside it if there is one.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::{ErrorCategory, Reader, Writer};
+    /// use std::io::{Read, Write};
+    ///
+    /// let mut writer = Writer::new(vec![], 2.try_into().unwrap());
+    /// writer.write_all(b"hello world").unwrap();
+    /// let (mut compressed, _) = writer.finish().unwrap();
+    ///
+    /// let truncated = Reader::new(&compressed[..30]).read_to_end(&mut vec![]).unwrap_err();
+    /// assert_eq!(ErrorCategory::of(&truncated), ErrorCategory::Truncation);
+    ///
+    /// compressed[20] ^= 0xff;
+    /// let corrupt = Reader::new(compressed.as_slice()).read_to_end(&mut vec![]).unwrap_err();
+    /// assert_eq!(ErrorCategory::of(&corrupt), ErrorCategory::Corruption);
+    /// ```
+    pub fn of(error: &io::Error) -> Self {
+        if let Some(error) = error.get_ref().and_then(|e| e.downcast_ref::<BgzfError>()) {
+            return error.category();
+        }
+        match error.kind() {
+            io::ErrorKind::UnexpectedEof => Self::Truncation,
+            io::ErrorKind::InvalidData => Self::Corruption,
+            _ => Self::Io,
+        }
+    }
+}
+
+impl BgzfError {
+    /// The broad kind of this error.
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            Self::Io(e) => ErrorCategory::of(e),
+            Self::Truncated => ErrorCategory::Truncation,
+            Self::InvalidChecksum { .. }
+            | Self::InteriorEof(_)
+            | Self::InvalidHeader(_)
+            | Self::UncompressedSizeExceeded(..)
+            | Self::UncompressedSizeMismatch { .. }
+            | Self::LibDelfaterDecompress(_) => ErrorCategory::Corruption,
+            Self::BgzipCompressionLevel(_)
+            | Self::BufferTooSmall(_)
+            | Self::CompressionLevel(_)
+            | Self::Flate2CompressionLevel(_) => ErrorCategory::InvalidInput,
+            Self::BlockSizeExceeded(..) | Self::LibDeflaterCompress(_) => {
+                ErrorCategory::Compression
+            }
+        }
+    }
+
+    /// Whether the data itself is invalid.
+    pub fn is_corruption(&self) -> bool {
+        self.category() == ErrorCategory::Corruption
+    }
+
+    /// Whether the data ends part of the way through a block.
+    pub fn is_truncation(&self) -> bool {
+        self.category() == ErrorCategory::Truncation
+    }
+
+    /// Whether reading can usefully go on after this error: corruption is confined to a block,
+    /// which can be skipped as [`salvage`] does, and I/O errors that are interrupted or timed out
+    /// can be retried.
+    pub fn is_recoverable(&self) -> bool {
+        match self {
+            Self::Io(e) => matches!(
+                e.kind(),
+                io::ErrorKind::Interrupted | io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+            ),
+            e => e.is_corruption(),
+        }
+    }
+}
+
+/// The error for work handed to a [`ThreadPool`] that never reported back, i.e. it panicked.
+pub(crate) fn worker_exited() -> BgzfError {
+    BgzfError::Io(io::Error::new(io::ErrorKind::Other, "A worker thread exited without a result"))
 }
 
+/// Location and size information for a single BGZF block.
+///
+/// This is handed to block callbacks registered on the [`Reader`] and [`Writer`], and returned by
+/// [`Reader::peek_block`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BlockInfo {
+    /// The offset of the start of the block in the compressed stream.
+    pub compressed_offset: u64,
+    /// The size of the block in the compressed stream, including header and footer.
+    pub compressed_size: usize,
+    /// The offset of the first byte of the block in the uncompressed stream.
+    pub uncompressed_offset: u64,
+    /// The number of uncompressed bytes held in the block.
+    pub uncompressed_size: usize,
+}
+
+/// A callback invoked with the [`BlockInfo`] of each block read or written.
+pub(crate) type BlockCallback = Box<dyn FnMut(BlockInfo) + Send>;
+
 /// Level of compression to use for for the compressors.
 ///
 /// Valid values are 1-12. See [libdeflater](https://github.com/ebiggers/libdeflate#compression-levels) documentation on levels.
@@ -130,12 +455,16 @@ impl CompressionLevel {
     /// Create a new [`CompressionLevel`] instance.
     ///
     /// Valid levels are 1-12.
-    #[allow(clippy::cast_lossless)]
     pub fn new(level: u8) -> BgzfResult<Self> {
-        // libdeflater::CompressionLvlError contains no information
-        Ok(Self(
-            CompressionLvl::new(level as i32).map_err(|_e| BgzfError::CompressionLevel(level))?,
-        ))
+        // libdeflater::CompressionLvlError contains no information. Not every libdeflater release
+        // this crate allows accepts level 0, so it is rejected here whatever the release.
+        let lvl = (1..=12).contains(&level).then(|| CompressionLvl::new(level.into()).ok());
+        lvl.flatten().map(Self).ok_or(BgzfError::CompressionLevel(level))
+    }
+
+    /// Create a [`CompressionLevel`] from any integer level, saturated to a `u8` for the error.
+    fn from_wide(level: i128) -> BgzfResult<Self> {
+        Self::new(level.clamp(0, u8::MAX.into()) as u8)
     }
 
     /// Get the inner compression level
@@ -161,6 +490,80 @@ impl TryFrom<u8> for CompressionLevel {
     }
 }
 
+impl TryFrom<i32> for CompressionLevel {
+    type Error = BgzfError;
+
+    /// Try to convert an `i32` to a compression level.
+    ///
+    /// Levels outside the range of a `u8` are reported saturated to 0 or 255 in the error.
+    ///
+    /// # Example
+    /// ```rust
+    /// use bgzf::CompressionLevel;
+    ///
+    /// assert_eq!(CompressionLevel::try_from(6_i32).unwrap(), CompressionLevel::new(6).unwrap());
+    /// let error = CompressionLevel::try_from(-1_i32).unwrap_err();
+    /// assert_eq!(error.to_string(), "Invalid compression level: 0, expected 1-12");
+    /// ```
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        Self::from_wide(value.into())
+    }
+}
+
+impl TryFrom<u32> for CompressionLevel {
+    type Error = BgzfError;
+
+    /// Try to convert a `u32` to a compression level.
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        Self::from_wide(value.into())
+    }
+}
+
+impl TryFrom<usize> for CompressionLevel {
+    type Error = BgzfError;
+
+    /// Try to convert a `usize` to a compression level.
+    fn try_from(value: usize) -> Result<Self, Self::Error> {
+        Self::from_wide(value as i128)
+    }
+}
+
+#[cfg(feature = "flate2")]
+impl TryFrom<flate2::Compression> for CompressionLevel {
+    type Error = BgzfError;
+
+    /// Try to convert a flate2 compression level, 0-9, to a compression level, 1-12.
+    ///
+    /// | flate2 | 0 | 1 | 2 | 3 | 4 | 5 | 6 | 7 | 8  | 9  |
+    /// |--------|---|---|---|---|---|---|---|---|----|----|
+    /// | bgzf   | 1 | 1 | 2 | 3 | 4 | 5 | 6 | 8 | 10 | 12 |
+    ///
+    /// Levels 1-6 keep their value, since libdeflate's levels up to 6 trade speed for size much
+    /// as zlib's do, and so flate2's default of 6 stays the default here. Level 0, which stores
+    /// data uncompressed in zlib, becomes the fastest level, 1. Levels 7, 8, and 9 map to 8, 10,
+    /// and 12, so that `Compression::best()` is the best compression libdeflate offers.
+    ///
+    /// # Example
+    /// ```rust
+    /// use bgzf::CompressionLevel;
+    /// use flate2::Compression;
+    ///
+    /// let level = CompressionLevel::try_from(Compression::default()).unwrap();
+    /// assert_eq!(level, CompressionLevel::new(6).unwrap());
+    /// let level = CompressionLevel::try_from(Compression::best()).unwrap();
+    /// assert_eq!(level, CompressionLevel::new(12).unwrap());
+    /// assert!(CompressionLevel::try_from(Compression::new(10)).is_err());
+    /// ```
+    fn try_from(value: flate2::Compression) -> Result<Self, Self::Error> {
+        match value.level() {
+            0 => Self::new(1),
+            level @ 1..=6 => Self::from_wide(level.into()),
+            level @ 7..=9 => Self::from_wide(2 * i128::from(level) - 6),
+            level => Err(BgzfError::Flate2CompressionLevel(level)),
+        }
+    }
+}
+
 impl From<CompressionLevel> for u8 {
     /// Convenience method vor converting [`CompressionLevel`] back to a [`u8`].
     fn from(level: CompressionLevel) -> Self {
@@ -193,6 +596,8 @@ impl From<&CompressionLevel> for u8 {
 pub struct Compressor {
     inner: libdeflater::Compressor,
     level: CompressionLevel,
+    /// The XFL byte written in headers
+    hint: u8,
 }
 
 #[allow(dead_code)]
@@ -206,7 +611,40 @@ impl Compressor {
     /// let compressor = Compressor::new(3.try_into().expect("Invalid compression level"));
     /// ```
     pub fn new(level: CompressionLevel) -> Self {
-        Self { inner: libdeflater::Compressor::new(*level.inner()), level }
+        Self {
+            inner: libdeflater::Compressor::new(*level.inner()),
+            level,
+            hint: compression_hint(level),
+        }
+    }
+
+    /// Create a [`Compressor`] whose blocks match those of htslib's `bgzip -l bgzip_level`.
+    ///
+    /// htslib built with libdeflate, as it is by default, maps its levels onto libdeflate's
+    /// (any negative level means the default, 6, which becomes libdeflate's 7) and leaves the XFL
+    /// header byte zero. This does the same, so blocks are byte for byte identical to `bgzip`'s
+    /// as long as both use the same libdeflate release. Builds of htslib that use zlib instead
+    /// produce different deflate streams, which this can't reproduce.
+    ///
+    /// Levels above 9 are treated as 9, as `bgzip` does. Level 0 is not supported: for it htslib
+    /// writes stored (uncompressed) deflate blocks itself rather than calling libdeflate.
+    pub fn bgzip_compatible(bgzip_level: i32) -> BgzfResult<Self> {
+        // From htslib's bgzf_compress; its level 0 entry is never used.
+        const LEVEL_MAP: [u8; 10] = [0, 1, 2, 3, 5, 6, 7, 8, 10, 12];
+        let level = match bgzip_level {
+            ..=-1 => LEVEL_MAP[6],
+            1..=9 => LEVEL_MAP[bgzip_level as usize],
+            10.. => LEVEL_MAP[9],
+            _ => return Err(BgzfError::BgzipCompressionLevel(bgzip_level)),
+        };
+        let mut compressor = Self::new(CompressionLevel::new(level)?);
+        compressor.hint = BGZF_COMPRESSION_HINT_OTHER;
+        Ok(compressor)
+    }
+
+    /// The XFL byte written in the headers of this compressor's blocks.
+    pub(crate) fn hint(&self) -> u8 {
+        self.hint
     }
 
     #[inline]
@@ -221,6 +659,10 @@ impl Compressor {
 
     /// Compress a block of bytes, adding a header and footer.
     #[inline]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip_all, fields(input_len = input.len()))
+    )]
     pub fn compress(&mut self, input: &[u8], buffer: &mut Vec<u8>) -> BgzfResult<()> {
         buffer.resize_with(
             BGZF_HEADER_SIZE + input.len() + extra_amount(input.len()) + BGZF_FOOTER_SIZE,
@@ -232,40 +674,194 @@ impl Compressor {
             .deflate_compress(input, &mut buffer[BGZF_HEADER_SIZE..])
             .map_err(BgzfError::LibDeflaterCompress)?;
 
-        // Make sure that compressed buffer is smaller than
-        if bytes_written >= MAX_BGZF_BLOCK_SIZE {
-            return Err(BgzfError::BlockSizeExceeded(bytes_written, MAX_BGZF_BLOCK_SIZE));
-        }
+        // Fails if the block would be too large for its size to fit in the header.
+        let mut header = BlockHeader::new(bytes_written + block_overhead())?;
+        header.xfl = self.hint;
         let mut check = libdeflater::Crc::new();
         check.update(input);
 
-        // Add header with total byte sizes
-        let header = header_inner(self.level, bytes_written as u16);
-        buffer[0..BGZF_HEADER_SIZE].copy_from_slice(&header);
+        buffer[0..BGZF_HEADER_SIZE].copy_from_slice(&header.to_bytes());
         buffer.truncate(BGZF_HEADER_SIZE + bytes_written);
+        let footer = BlockFooter { crc32: check.sum(), uncompressed_size: input.len() as u32 };
+        buffer.extend_from_slice(&footer.to_bytes());
 
-        buffer.write_u32::<LittleEndian>(check.sum())?;
-        buffer.write_u32::<LittleEndian>(input.len() as u32)?;
-
+        #[cfg(feature = "tracing")]
+        tracing::trace!(compressed_len = buffer.len(), "compressed block");
         Ok(())
     }
 
+    /// Compress a block of bytes into the start of `output`, adding a header and footer, and
+    /// return the size of the block.
+    ///
+    /// Unlike [`Compressor::compress`] this never allocates. Fails with
+    /// [`BgzfError::BufferTooSmall`] if the block doesn't fit in `output`; a buffer of
+    /// [`MAX_BGZF_BLOCK_SIZE`] bytes fits the block of any input up to [`BGZF_BLOCK_SIZE`] bytes.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::{decompress_all, BgzfError, Compressor, MAX_BGZF_BLOCK_SIZE};
+    ///
+    /// let mut compressor = Compressor::new(2.try_into().unwrap());
+    /// let mut arena = [0; MAX_BGZF_BLOCK_SIZE];
+    /// let len = compressor.compress_into(b"hello world", &mut arena).unwrap();
+    /// assert_eq!(decompress_all(&arena[..len]).unwrap(), b"hello world");
+    ///
+    /// let err = compressor.compress_into(b"hello world", &mut arena[..20]).unwrap_err();
+    /// assert!(matches!(err, BgzfError::BufferTooSmall(20)));
+    /// ```
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip_all, fields(input_len = input.len()))
+    )]
+    pub fn compress_into(&mut self, input: &[u8], output: &mut [u8]) -> BgzfResult<usize> {
+        let too_small = BgzfError::BufferTooSmall(output.len());
+        let end = match output.len().checked_sub(BGZF_FOOTER_SIZE) {
+            Some(end) if end >= BGZF_HEADER_SIZE => end,
+            _ => return Err(too_small),
+        };
+        let bytes_written =
+            match self.inner_mut().deflate_compress(input, &mut output[BGZF_HEADER_SIZE..end]) {
+                Ok(bytes_written) => bytes_written,
+                Err(libdeflater::CompressionError::InsufficientSpace) => return Err(too_small),
+            };
+
+        // Fails if the block would be too large for its size to fit in the header.
+        let mut header = BlockHeader::new(bytes_written + block_overhead())?;
+        header.xfl = self.hint;
+        let mut check = libdeflater::Crc::new();
+        check.update(input);
+        let footer = BlockFooter { crc32: check.sum(), uncompressed_size: input.len() as u32 };
+
+        let size = header.block_size();
+        output[..BGZF_HEADER_SIZE].copy_from_slice(&header.to_bytes());
+        output[size - BGZF_FOOTER_SIZE..size].copy_from_slice(&footer.to_bytes());
+
+        #[cfg(feature = "tracing")]
+        tracing::trace!(compressed_len = size, "compressed block");
+        Ok(size)
+    }
+
     /// Append the EOF block.
     pub fn append_eof(bytes: &mut Vec<u8>) {
         bytes.extend(BGZF_EOF);
     }
+
+    /// Compress each of `chunks` into a block of its own, lazily, reusing this compressor.
+    ///
+    /// Chunks larger than [`BGZF_BLOCK_SIZE`] are split into several blocks and empty chunks are
+    /// skipped. Each block's buffer comes from [`BufferPool::global`], and can be handed back to it
+    /// once used. See [`Compressor::compress_iter_to`] to write the blocks out with a single
+    /// buffer instead.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::{decompress_all, Compressor};
+    ///
+    /// let mut compressor = Compressor::new(2.try_into().unwrap());
+    /// let chunks = [&b"hello "[..], &b""[..], &b"world"[..]];
+    /// let blocks = compressor.compress_iter(chunks).collect::<Result<Vec<_>, _>>().unwrap();
+    /// assert_eq!(blocks.len(), 2);
+    ///
+    /// let mut compressed = blocks.concat();
+    /// Compressor::append_eof(&mut compressed);
+    /// assert_eq!(decompress_all(&compressed).unwrap(), b"hello world");
+    /// ```
+    pub fn compress_iter<'a, 'c, I>(&'a mut self, chunks: I) -> CompressIter<'a, 'c, I::IntoIter>
+    where
+        I: IntoIterator<Item = &'c [u8]>,
+    {
+        CompressIter { compressor: self, chunks: chunks.into_iter(), rest: &[] }
+    }
+
+    /// Compress each of `chunks` into a block of its own and emit the blocks to `sink`, returning
+    /// the number of blocks.
+    ///
+    /// Chunks are split and skipped as by [`Compressor::compress_iter`], but every block is
+    /// compressed into the same buffer. No EOF marker is written.
+    pub fn compress_iter_to<'c, I, S>(&mut self, chunks: I, sink: &mut S) -> io::Result<u64>
+    where
+        I: IntoIterator<Item = &'c [u8]>,
+        S: BlockSink + ?Sized,
+    {
+        let mut block = BufferPool::global().get();
+        let mut count = 0;
+        let mut result = Ok(());
+        for bytes in chunks.into_iter().flat_map(|chunk| chunk.chunks(BGZF_BLOCK_SIZE)) {
+            result = self
+                .compress(bytes, &mut block)
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+                .and_then(|()| sink.emit_block(&block));
+            if result.is_err() {
+                break;
+            }
+            count += 1;
+        }
+        BufferPool::global().put(block);
+        result.map(|()| count)
+    }
 }
 
-/// [`Decompressor`] will decompress a BGZF block.
-struct Decompressor(libdeflater::Decompressor);
+/// The blocks compressed by [`Compressor::compress_iter`].
+pub struct CompressIter<'a, 'c, I> {
+    compressor: &'a mut Compressor,
+    chunks: I,
+    /// The part of the current chunk not yet compressed
+    rest: &'c [u8],
+}
+
+impl<'c, I> Iterator for CompressIter<'_, 'c, I>
+where
+    I: Iterator<Item = &'c [u8]>,
+{
+    type Item = BgzfResult<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.rest.is_empty() {
+            self.rest = self.chunks.next()?;
+        }
+        let (bytes, rest) = self.rest.split_at(std::cmp::min(self.rest.len(), BGZF_BLOCK_SIZE));
+        self.rest = rest;
+        let mut block = BufferPool::global().get();
+        Some(self.compressor.compress(bytes, &mut block).map(|()| block))
+    }
+}
+
+/// [`Decompressor`] will decompress a BGZF block with the [`Decompressor::decompress_block`] method, allowing for reuse of the decompressor itself.
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{Compressor, Decompressor};
+///
+/// let mut block = vec![];
+/// Compressor::new(2.try_into().unwrap()).compress(b"hello world", &mut block).unwrap();
+///
+/// let mut decompressed = vec![];
+/// Decompressor::new().decompress_block(&block, &mut decompressed).unwrap();
+/// assert_eq!(decompressed, b"hello world");
+/// ```
+pub struct Decompressor(libdeflater::Decompressor);
 
 #[allow(dead_code)]
 impl Decompressor {
     /// Create a new [`Decompressor`].
-    fn new() -> Self {
+    pub fn new() -> Self {
         Self(libdeflater::Decompressor::new())
     }
 
+    /// Decompress a whole block, header and footer included, replacing the contents of `output`.
+    pub fn decompress_block(&mut self, block: &[u8], output: &mut Vec<u8>) -> BgzfResult<()> {
+        if core::block_size(block)? != block.len() {
+            return Err(BgzfError::InvalidHeader("Block size does not match the block given"));
+        }
+        let check = BlockFooter::from_block(block)?;
+        output.clear();
+        output.resize(check.uncompressed_size as usize, 0);
+        self.decompress(strip_footer(&block[BGZF_HEADER_SIZE..])?, output, check)
+    }
+
     #[inline]
     fn inner(&self) -> &libdeflater::Decompressor {
         &self.0
@@ -281,22 +877,36 @@ impl Decompressor {
     /// This expects the `output` to be the exact size needed to hold the decompressed input.
     /// This expects the input slice to have the header and footer values removed.
     #[inline]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "trace",
+            skip_all,
+            fields(input_len = input.len(), output_len = output.len())
+        )
+    )]
     fn decompress(
         &mut self,
         input: &[u8],
         output: &mut [u8],
-        checksum_values: ChecksumValues,
+        footer: BlockFooter,
     ) -> BgzfResult<()> {
-        if checksum_values.amount != 0 {
-            let _bytes_decompressed = self.inner_mut().deflate_decompress(input, output)?;
+        if footer.uncompressed_size != 0 {
+            let bytes_decompressed = self.inner_mut().deflate_decompress(input, output)?;
+            if bytes_decompressed != output.len() {
+                return Err(BgzfError::UncompressedSizeMismatch {
+                    found: bytes_decompressed,
+                    expected: output.len(),
+                });
+            }
         }
         let mut new_check = libdeflater::Crc::new();
         new_check.update(output);
 
-        if checksum_values.sum != new_check.sum() {
+        if footer.crc32 != new_check.sum() {
             return Err(BgzfError::InvalidChecksum {
                 found: new_check.sum(),
-                expected: checksum_values.sum,
+                expected: footer.crc32,
             });
         }
         Ok(())
@@ -309,72 +919,32 @@ impl Default for Decompressor {
     }
 }
 
-/// Create an Bgzf style header.
+/// The compression hint (the gzip XFL field) written in headers for `compression_level`.
 #[inline]
-fn header_inner(compression_level: CompressionLevel, compressed_size: u16) -> Vec<u8> {
-    // Determine hint to place in header
+pub(crate) fn compression_hint(compression_level: CompressionLevel) -> u8 {
     // From https://github.com/rust-lang/flate2-rs/blob/b2e976da21c18c8f31132e93a7f803b5e32f2b6d/src/gz/mod.rs#L235
-    let comp_value = if compression_level.inner() >= &CompressionLvl::best() {
+    if compression_level.inner() >= &CompressionLvl::best() {
         BGZF_COMPRESSION_HINT_BEST
     } else if compression_level.inner() <= &CompressionLvl::fastest() {
         BGZF_COMPRESSION_HINT_FASTEST
     } else {
         BGZF_COMPRESSION_HINT_OTHER
-    };
-
-    let mut header: Vec<u8> = Vec::with_capacity(20);
-    header.write_u8(BGZF_MAGIC_BYTE_A).unwrap(); // magic byte
-    header.write_u8(BGZF_MAGIC_BYTE_B).unwrap(); // magic byte
-    header.write_u8(BGZF_COMPRESSION_METHOD).unwrap(); // compression method
-    header.write_u8(BGZF_NAME_COMMENT_EXTRA_FLAG).unwrap(); // name / comment / extraflag
-    header.write_u32::<LittleEndian>(BGZF_DEFAULT_MTIME).unwrap(); // mtime
-    header.write_u8(comp_value).unwrap(); // compression value
-    header.write_u8(BGZF_DEFAULT_OS).unwrap(); // OS
-    header.write_u16::<LittleEndian>(BGZF_EXTRA_FLAG_LEN).unwrap(); // Extra flag len
-    header.write_u8(BGZF_SUBFIELD_ID1).unwrap(); // Bgzf subfield ID 1
-    header.write_u8(BGZF_SUBFIELD_ID2).unwrap(); // Bgzf subfield ID2
-    header.write_u16::<LittleEndian>(BGZF_SUBFIELD_LEN).unwrap(); // Bgzf subfield len
-    header
-        .write_u16::<LittleEndian>(
-            compressed_size + BGZF_HEADER_SIZE as u16 + BGZF_FOOTER_SIZE as u16 - 1,
-        )
-        .unwrap(); // Size of block including header and footer - 1 BLEN
-
-    header
-}
-
-/// Check that the header is as expected for this format
-#[inline]
-fn check_header(bytes: &[u8]) -> BgzfResult<()> {
-    // Check that the extra field flag is set
-    if bytes[3] & 4 != BGZF_NAME_COMMENT_EXTRA_FLAG {
-        Err(BgzfError::InvalidHeader("Extra field flag not set"))
-    } else if bytes[12] != BGZF_SUBFIELD_ID1 || bytes[13] != BGZF_SUBFIELD_ID2 {
-        // Check for BC in SID
-        Err(BgzfError::InvalidHeader("Bad SID"))
-    } else {
-        Ok(())
     }
 }
 
-/// Extract the block size from the header.
+/// Check an uncompressed block size from a footer before anything is allocated for it.
 #[inline]
-fn get_block_size(bytes: &[u8]) -> usize {
-    LittleEndian::read_u16(&bytes[BGZF_BLOCK_SIZE_OFFSET..]) as usize + 1
-}
-
-/// Get the expected uncompressed size and check sum from the footer
-#[inline]
-fn get_footer_values(input: &[u8]) -> ChecksumValues {
-    let check_sum = LittleEndian::read_u32(&input[input.len() - 8..input.len() - 4]);
-    let check_amount = LittleEndian::read_u32(&input[input.len() - 4..]);
-    ChecksumValues { sum: check_sum, amount: check_amount }
+fn check_uncompressed_size(size: usize) -> BgzfResult<()> {
+    if size > MAX_BGZF_BLOCK_SIZE {
+        return Err(BgzfError::UncompressedSizeExceeded(size, MAX_BGZF_BLOCK_SIZE));
+    }
+    Ok(())
 }
 
 /// Strip the footer off of a compressed block.
 #[inline]
-fn strip_footer(input: &[u8]) -> &[u8] {
-    &input[..input.len() - BGZF_FOOTER_SIZE]
+fn strip_footer(input: &[u8]) -> BgzfResult<&[u8]> {
+    input.len().checked_sub(BGZF_FOOTER_SIZE).map(|end| &input[..end]).ok_or(BgzfError::Truncated)
 }
 
 #[cfg(test)]
@@ -390,6 +960,11 @@ mod test {
 
     use super::*;
 
+    /// `len` bytes of test data that compress, but not too well.
+    pub(crate) fn test_input(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8 ^ (i >> 12) as u8).collect()
+    }
+
     #[test]
     fn test_simple_bgzfsync() {
         let dir = tempdir().unwrap();
@@ -431,7 +1006,189 @@ mod test {
         assert_eq!(input.to_vec(), bytes);
     }
 
+    #[test]
+    fn test_malformed_blocks_are_errors() {
+        assert!(matches!(BlockFooter::from_block(&[0; 7]), Err(BgzfError::Truncated)));
+        assert!(matches!(strip_footer(&[0; 7]), Err(BgzfError::Truncated)));
+        assert!(matches!(core::block_size(&BGZF_EOF[..10]), Err(BgzfError::Truncated)));
+        assert!(matches!(core::block_size(&BGZF_EOF[..17]), Err(BgzfError::Truncated)));
+
+        // A header claiming a block smaller than its own header.
+        let mut tiny = BGZF_EOF.to_vec();
+        tiny[16] = 4;
+        assert!(matches!(core::block_size(&tiny), Err(BgzfError::InvalidHeader(_))));
+        assert!(Reader::new(tiny.as_slice()).read_to_end(&mut vec![]).is_err());
+        assert!(decompress_all(&tiny).is_err());
+        assert!(Index::from_reader(io::Cursor::new(&tiny)).is_err());
+
+        let mut output = vec![];
+        let mut decompressor = Decompressor::new();
+        assert!(decompressor.decompress_block(&BGZF_EOF[..20], &mut output).is_err());
+        assert!(decompressor.decompress_block(&tiny, &mut output).is_err());
+    }
+
+    #[test]
+    fn test_oversized_isize_is_rejected() {
+        let mut block = BGZF_EOF.to_vec();
+        let len = block.len();
+        block[len - 4..].copy_from_slice(&u32::MAX.to_le_bytes());
+        let err = Reader::new(block.as_slice()).read_to_end(&mut vec![]).unwrap_err();
+        let inner = err.into_inner().unwrap().downcast::<BgzfError>().unwrap();
+        assert!(
+            matches!(*inner, BgzfError::UncompressedSizeExceeded(size, _) if size == u32::MAX as usize)
+        );
+        assert!(decompress_all(&block).is_err());
+        assert!(Index::from_reader(io::Cursor::new(&block)).is_err());
+    }
+
+    #[test]
+    fn test_compression_level_conversions() {
+        for level in 1..=12_u8 {
+            let expected = CompressionLevel::new(level).unwrap();
+            assert_eq!(CompressionLevel::try_from(u32::from(level)).unwrap(), expected);
+            assert_eq!(CompressionLevel::try_from(i32::from(level)).unwrap(), expected);
+            assert_eq!(CompressionLevel::try_from(usize::from(level)).unwrap(), expected);
+        }
+        let message = |error: BgzfError| error.to_string();
+        assert_eq!(
+            message(CompressionLevel::new(0).unwrap_err()),
+            "Invalid compression level: 0, expected 1-12"
+        );
+        assert_eq!(
+            message(CompressionLevel::new(13).unwrap_err()),
+            "Invalid compression level: 13, expected 1-12"
+        );
+        assert_eq!(
+            message(CompressionLevel::try_from(20_u32).unwrap_err()),
+            "Invalid compression level: 20, expected 1-12"
+        );
+        assert_eq!(
+            message(CompressionLevel::try_from(i32::MIN).unwrap_err()),
+            "Invalid compression level: 0, expected 1-12"
+        );
+        assert_eq!(
+            message(CompressionLevel::try_from(usize::MAX).unwrap_err()),
+            "Invalid compression level: 255, expected 1-12"
+        );
+        assert!(CompressionLevel::try_from(u32::MAX).is_err());
+    }
+
+    #[test]
+    fn test_error_categories() {
+        let checksum = BgzfError::InvalidChecksum { found: 1, expected: 2 };
+        assert!(checksum.is_corruption() && checksum.is_recoverable() && !checksum.is_truncation());
+        assert!(BgzfError::Truncated.is_truncation() && !BgzfError::Truncated.is_recoverable());
+        let eof = BgzfError::Io(io::ErrorKind::UnexpectedEof.into());
+        assert_eq!(eof.category(), ErrorCategory::Truncation);
+        let timeout = BgzfError::Io(io::ErrorKind::TimedOut.into());
+        assert_eq!(timeout.category(), ErrorCategory::Io);
+        assert!(timeout.is_recoverable());
+        assert!(!BgzfError::Io(io::ErrorKind::PermissionDenied.into()).is_recoverable());
+        let level = CompressionLevel::new(13).unwrap_err();
+        assert_eq!(level.category(), ErrorCategory::InvalidInput);
+
+        let wrapped = io::Error::new(io::ErrorKind::Other, BgzfError::InvalidHeader("bad magic"));
+        assert_eq!(ErrorCategory::of(&wrapped), ErrorCategory::Corruption);
+        assert_eq!(ErrorCategory::of(&io::ErrorKind::BrokenPipe.into()), ErrorCategory::Io);
+    }
+
+    #[test]
+    fn test_bgzip_compatible_writer() {
+        let input = test_input(200_000);
+
+        // Blocks as htslib's bgzf_compress makes them with libdeflate.
+        let bgzip = |level: i32| {
+            let mut deflater = libdeflater::Compressor::new(CompressionLvl::new(level).unwrap());
+            let mut output = vec![];
+            for chunk in input.chunks(BGZF_BLOCK_SIZE) {
+                let mut deflated = vec![0; MAX_BGZF_BLOCK_SIZE];
+                let len = deflater.deflate_compress(chunk, &mut deflated).unwrap();
+                output.extend_from_slice(b"\x1f\x8b\x08\x04\0\0\0\0\0\xff\x06\0BC\x02\0");
+                output.extend_from_slice(&((len + 25) as u16).to_le_bytes());
+                output.extend_from_slice(&deflated[..len]);
+                output.extend_from_slice(&libdeflater::crc32(chunk).to_le_bytes());
+                output.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+            }
+            output.extend_from_slice(BGZF_EOF);
+            output
+        };
+
+        for (bgzip_level, libdeflate_level) in [(-1, 7), (-5, 7), (1, 1), (4, 5), (9, 12), (11, 12)]
+        {
+            let mut writer = Writer::bgzip_compatible(vec![], bgzip_level).unwrap();
+            for chunk in input.chunks(30_000) {
+                writer.write_all(chunk).unwrap();
+            }
+            let (compressed, _) = writer.finish().unwrap();
+            assert_eq!(compressed, bgzip(libdeflate_level), "level {}", bgzip_level);
+            assert_eq!(decompress_all(&compressed).unwrap(), input);
+        }
+        let error = Writer::bgzip_compatible(vec![], 0).err().unwrap();
+        assert!(matches!(error, BgzfError::BgzipCompressionLevel(0)));
+    }
+
+    #[test]
+    fn test_compress_iter() {
+        let input = test_input(300_000);
+        let level = CompressionLevel::new(3).unwrap();
+        let mut expected = compress_parallel(&input, level).unwrap();
+        expected.truncate(expected.len() - BGZF_EOF.len());
+
+        // Block sized chunks, and one oversized chunk with empty ones around it.
+        let mut compressor = Compressor::new(level);
+        let blocks: Vec<Vec<u8>> =
+            compressor.compress_iter(input.chunks(BGZF_BLOCK_SIZE)).map(Result::unwrap).collect();
+        assert_eq!(blocks.len(), 5);
+        assert_eq!(blocks.concat(), expected);
+        let chunks = [&[][..], &input[..], &[][..]];
+        let blocks: Vec<Vec<u8>> = compressor.compress_iter(chunks).map(Result::unwrap).collect();
+        assert_eq!(blocks.concat(), expected);
+
+        let mut sink = vec![];
+        assert_eq!(compressor.compress_iter_to(chunks, &mut sink).unwrap(), 5);
+        assert_eq!(sink, expected);
+    }
+
+    #[cfg(feature = "flate2")]
+    #[test]
+    fn test_compression_level_from_flate2() {
+        let levels: Vec<u8> = (0..=9)
+            .map(|level| {
+                CompressionLevel::try_from(flate2::Compression::new(level)).unwrap().into()
+            })
+            .collect();
+        assert_eq!(levels, vec![1, 1, 2, 3, 4, 5, 6, 8, 10, 12]);
+        let error = CompressionLevel::try_from(flate2::Compression::new(10)).unwrap_err();
+        assert_eq!(error.to_string(), "Invalid flate2 compression level: 10, expected 0-9");
+    }
+
+    #[test]
+    fn test_compress_into() {
+        let input = test_input(BGZF_BLOCK_SIZE);
+        let mut compressor = Compressor::new(CompressionLevel::new(3).unwrap());
+        let mut expected = vec![];
+        compressor.compress(&input, &mut expected).unwrap();
+
+        let mut arena = vec![0; MAX_BGZF_BLOCK_SIZE];
+        let len = compressor.compress_into(&input, &mut arena).unwrap();
+        assert_eq!(&arena[..len], expected.as_slice());
+
+        let mut exact = vec![0; expected.len()];
+        assert_eq!(compressor.compress_into(&input, &mut exact).unwrap(), expected.len());
+        assert_eq!(exact, expected);
+
+        for size in [0, block_overhead() - 1, expected.len() - 1] {
+            let err = compressor.compress_into(&input, &mut arena[..size]).unwrap_err();
+            assert!(matches!(err, BgzfError::BufferTooSmall(n) if n == size));
+            assert_eq!(err.category(), ErrorCategory::InvalidInput);
+        }
+
+        let len = compressor.compress_into(&[], &mut arena).unwrap();
+        assert_eq!(decompress_all(&arena[..len]).unwrap(), b"");
+    }
+
     const DICT_SIZE: usize = 32768;
+
     proptest! {
         #[test]
         fn proptest_bgzf(
@@ -468,5 +1225,27 @@ mod test {
             // Assert decompressed output is equal to input
             assert_eq!(input.clone(), bytes);
         }
+
+        #[test]
+        fn proptest_corrupt_input_never_panics(
+            len in 0..200_000_usize,
+            corruptions in prop::collection::vec((any::<prop::sample::Index>(), any::<u8>()), 1..8),
+            truncate in any::<prop::sample::Index>(),
+        ) {
+            let input = test_input(len);
+            let mut data = compress_parallel(&input, CompressionLevel::new(1).unwrap()).unwrap();
+            for (position, byte) in corruptions {
+                let i = position.index(data.len());
+                data[i] = byte;
+            }
+            data.truncate(truncate.index(data.len() + 1));
+
+            // Errors are expected, panics and huge allocations are not.
+            let _ = Reader::new(data.as_slice()).read_to_end(&mut vec![]);
+            let _ = decompress_all(&data);
+            if let Ok(index) = Index::from_reader(io::Cursor::new(&data)) {
+                let _ = IndexedReader::new(io::Cursor::new(&data), index).read_to_end(&mut vec![]);
+            }
+        }
     }
 }
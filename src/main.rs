@@ -0,0 +1,157 @@
+//! The `bgzf` command line tool.
+//!
+//! ```text
+//! bgzf stat [--json] FILE
+//! ```
+use std::{env, fmt::Write as _, process::ExitCode};
+
+use bgzf::{BlockStats, Histogram};
+
+const USAGE: &str = "Usage: bgzf stat [--json] FILE
+
+Commands:
+  stat    Print the block counts, byte totals, and histograms of block sizes and
+          compression ratios of a BGZF file
+
+Options:
+  --json  Print the statistics as JSON rather than as text";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        ["stat", rest @ ..] => match parse_stat_args(rest) {
+            Some((path, json)) => stat(path, json),
+            None => usage_error(),
+        },
+        ["-h" | "--help"] => {
+            println!("{}", USAGE);
+            ExitCode::SUCCESS
+        }
+        _ => usage_error(),
+    }
+}
+
+fn usage_error() -> ExitCode {
+    eprintln!("{}", USAGE);
+    ExitCode::from(2)
+}
+
+/// The file and whether to print JSON, or `None` if the arguments aren't one file and `--json`.
+fn parse_stat_args<'a>(args: &[&'a str]) -> Option<(&'a str, bool)> {
+    match args {
+        [path] if !path.starts_with('-') => Some((path, false)),
+        ["--json", path] | [path, "--json"] if !path.starts_with('-') => Some((path, true)),
+        _ => None,
+    }
+}
+
+fn stat(path: &str, json: bool) -> ExitCode {
+    match BlockStats::from_path(path) {
+        Ok(stats) => {
+            print!("{}", if json { stats_json(path, &stats) } else { stats_text(path, &stats) });
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("bgzf stat: {}: {}", path, e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn stats_text(path: &str, stats: &BlockStats) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "File:               {}", path);
+    let _ = writeln!(out, "Data blocks:        {}", stats.blocks);
+    let _ = writeln!(out, "Empty blocks:       {}", stats.empty_blocks);
+    let _ = writeln!(out, "Compressed bytes:   {}", stats.compressed_bytes);
+    let _ = writeln!(out, "Uncompressed bytes: {}", stats.uncompressed_bytes);
+    let _ = writeln!(out, "Ratio:              {}", optional(stats.ratio(), 2));
+    for (name, histogram, precision) in histograms(stats) {
+        let _ = writeln!(
+            out,
+            "\n{} (min {}, mean {}, max {})",
+            name,
+            optional(histogram.min(), precision),
+            optional(histogram.mean(), precision),
+            optional(histogram.max(), precision),
+        );
+        let mut buckets = histogram.buckets().peekable();
+        while let Some((start, count)) = buckets.next() {
+            let end = match buckets.peek() {
+                Some(_) => format!("{:.*}", precision, start + histogram.bucket_width()),
+                None => String::from("+"),
+            };
+            let _ = writeln!(out, "  {:>8.*} - {:<8} {}", precision, start, end, count);
+        }
+    }
+    out
+}
+
+fn stats_json(path: &str, stats: &BlockStats) -> String {
+    let mut out = String::new();
+    let _ = write!(
+        out,
+        "{{\"file\":{},\"blocks\":{},\"empty_blocks\":{},\"compressed_bytes\":{},\
+         \"uncompressed_bytes\":{},\"ratio\":{}",
+        json_string(path),
+        stats.blocks,
+        stats.empty_blocks,
+        stats.compressed_bytes,
+        stats.uncompressed_bytes,
+        json_number(stats.ratio()),
+    );
+    out.push_str(",\"histograms\":{");
+    for (i, (name, histogram, _)) in histograms(stats).into_iter().enumerate() {
+        let key = name.to_lowercase().replace(' ', "_");
+        let _ = write!(
+            out,
+            "{}\"{}\":{{\"bucket_width\":{},\"min\":{},\"mean\":{},\"max\":{},\"counts\":[",
+            if i == 0 { "" } else { "," },
+            key,
+            histogram.bucket_width(),
+            json_number(histogram.min()),
+            json_number(histogram.mean()),
+            json_number(histogram.max()),
+        );
+        for (i, (_, count)) in histogram.buckets().enumerate() {
+            let _ = write!(out, "{}{}", if i == 0 { "" } else { "," }, count);
+        }
+        out.push_str("]}");
+    }
+    out.push_str("}}\n");
+    out
+}
+
+/// Each histogram with its name and the number of decimal places to print its values with.
+fn histograms(stats: &BlockStats) -> [(&'static str, &Histogram, usize); 3] {
+    [
+        ("Compressed size", &stats.compressed_size, 0),
+        ("Uncompressed size", &stats.uncompressed_size, 0),
+        ("Ratio", &stats.ratio, 2),
+    ]
+}
+
+fn optional(value: Option<f64>, precision: usize) -> String {
+    value.map_or_else(|| String::from("NA"), |v| format!("{:.*}", precision, v))
+}
+
+fn json_number(value: Option<f64>) -> String {
+    value.map_or_else(|| String::from("null"), |v| v.to_string())
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if u32::from(c) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", u32::from(c));
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -0,0 +1,317 @@
+//! Reading streams of BGZF blocks mixed with ordinary gzip members.
+use std::io::{self, BufRead, Read};
+
+use flate2::bufread::GzDecoder;
+
+use crate::{
+    core::block_size, reader::is_plausible_header, BgzfError, BlockCallback, BlockInfo,
+    Decompressor, BGZF_HEADER_SIZE, BUFSIZE, MAX_BGZF_BLOCK_SIZE,
+};
+
+/// The first two bytes of every gzip member.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// A reader of gzip streams whose members may be BGZF blocks or ordinary gzip members without
+/// the `BC` subfield, decoding all of them in order like flate2's `MultiGzDecoder`.
+///
+/// This is for files that were only partly written as BGZF, for example by concatenating the
+/// output of `gzip` and `bgzip`, which a [`Reader`](crate::Reader) rejects. BGZF blocks are
+/// decompressed whole, while ordinary members, whose compressed size isn't known up front, are
+/// decompressed as a stream with flate2. Only BGZF blocks are addressable, so only they are
+/// reported to the [`MultiMemberReader::set_block_callback`] callback.
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{MultiMemberReader, Writer};
+/// use flate2::{write::GzEncoder, Compression};
+/// use std::io::{Read, Write};
+///
+/// let mut data = vec![];
+/// let mut gzip = GzEncoder::new(&mut data, Compression::default());
+/// gzip.write_all(b"hello ").unwrap();
+/// gzip.finish().unwrap();
+/// let mut bgzf = Writer::new(&mut data, 2.try_into().unwrap());
+/// bgzf.write_all(b"world").unwrap();
+/// bgzf.finish().unwrap();
+///
+/// let mut reader = MultiMemberReader::new(data.as_slice());
+/// let mut decompressed = String::new();
+/// reader.read_to_string(&mut decompressed).unwrap();
+/// assert_eq!(decompressed, "hello world");
+/// assert_eq!(reader.gzip_members(), 1);
+/// ```
+pub struct MultiMemberReader<R> {
+    /// The compressed input, while between members or in a BGZF block
+    source: Option<Buffered<R>>,
+    /// The ordinary gzip member being decompressed, which holds the input meanwhile
+    member: Option<GzDecoder<Buffered<R>>>,
+    /// The decompressed data of the current BGZF block
+    block: Vec<u8>,
+    /// The position of the next byte to read in `block`
+    pos: usize,
+    decompressor: Decompressor,
+    /// The number of uncompressed bytes decoded so far
+    uncompressed_offset: u64,
+    /// The number of ordinary gzip members started so far
+    gzip_members: u64,
+    /// The callback to invoke after each BGZF block is read
+    block_callback: Option<BlockCallback>,
+}
+
+impl<R> MultiMemberReader<R>
+where
+    R: Read,
+{
+    /// Create a reader of the gzip members in `reader`.
+    pub fn new(reader: R) -> Self {
+        Self {
+            source: Some(Buffered {
+                buf: Vec::with_capacity(BUFSIZE),
+                pos: 0,
+                consumed: 0,
+                reader,
+            }),
+            member: None,
+            block: Vec::with_capacity(MAX_BGZF_BLOCK_SIZE),
+            pos: 0,
+            decompressor: Decompressor::new(),
+            uncompressed_offset: 0,
+            gzip_members: 0,
+            block_callback: None,
+        }
+    }
+
+    /// Register a callback to be invoked with the [`BlockInfo`] of each BGZF block read.
+    ///
+    /// Offsets count the bytes of every member, so they locate blocks in the mixed stream, but
+    /// ordinary gzip members aren't reported.
+    pub fn set_block_callback<F>(&mut self, callback: F)
+    where
+        F: FnMut(BlockInfo) + Send + 'static,
+    {
+        self.block_callback = Some(Box::new(callback));
+    }
+
+    /// The number of ordinary gzip members, without the `BC` subfield, started so far.
+    pub fn gzip_members(&self) -> u64 {
+        self.gzip_members
+    }
+
+    /// Start the next member, returning false at the end of the input.
+    fn next_member(&mut self) -> io::Result<bool> {
+        let mut source = self.source.take().expect("input is held by the reader between members");
+        match self.start_member(&mut source) {
+            Ok(Start::Gzip) => {
+                self.member = Some(GzDecoder::new(source));
+                self.gzip_members += 1;
+                Ok(true)
+            }
+            result => {
+                self.source = Some(source);
+                result.map(|start| start == Start::Block)
+            }
+        }
+    }
+
+    /// Decode the next member if it is a BGZF block, or find what else comes next.
+    fn start_member(&mut self, source: &mut Buffered<R>) -> io::Result<Start> {
+        let compressed_offset = source.consumed;
+        let available = source.fill_to(BGZF_HEADER_SIZE)?;
+        if available.is_empty() {
+            return Ok(Start::End);
+        }
+        if available.len() < BGZF_HEADER_SIZE || !is_plausible_header(available) {
+            return if available.starts_with(&GZIP_MAGIC) {
+                Ok(Start::Gzip)
+            } else {
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    BgzfError::InvalidHeader("Not a gzip member"),
+                ))
+            };
+        }
+
+        let size =
+            block_size(available).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let block = source.fill_to(size)?;
+        if block.len() < size {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Truncated BGZF block"));
+        }
+        self.decompressor
+            .decompress_block(&block[..size], &mut self.block)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        source.consume(size);
+        self.pos = 0;
+        if let Some(callback) = self.block_callback.as_mut() {
+            callback(BlockInfo {
+                compressed_offset,
+                compressed_size: size,
+                uncompressed_offset: self.uncompressed_offset,
+                uncompressed_size: self.block.len(),
+            });
+        }
+        self.uncompressed_offset += self.block.len() as u64;
+        Ok(Start::Block)
+    }
+}
+
+/// What [`MultiMemberReader::start_member`] found next in the input.
+#[derive(PartialEq, Eq)]
+enum Start {
+    /// The end of the input
+    End,
+    /// A BGZF block, now decoded
+    Block,
+    /// An ordinary gzip member
+    Gzip,
+}
+
+impl<R> Read for MultiMemberReader<R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.pos < self.block.len() {
+                let n = std::cmp::min(buf.len(), self.block.len() - self.pos);
+                buf[..n].copy_from_slice(&self.block[self.pos..self.pos + n]);
+                self.pos += n;
+                return Ok(n);
+            }
+            if let Some(member) = self.member.as_mut() {
+                let n = member.read(buf)?;
+                if n > 0 || buf.is_empty() {
+                    self.uncompressed_offset += n as u64;
+                    return Ok(n);
+                }
+                // The member is done, carry on with the rest of the input.
+                self.source = self.member.take().map(GzDecoder::into_inner);
+            }
+            if buf.is_empty() || !self.next_member()? {
+                return Ok(0);
+            }
+        }
+    }
+}
+
+/// A buffered reader that can look ahead a whole block and counts the bytes consumed.
+struct Buffered<R> {
+    buf: Vec<u8>,
+    /// The position of the next unconsumed byte in `buf`
+    pos: usize,
+    /// The number of bytes consumed so far
+    consumed: u64,
+    reader: R,
+}
+
+impl<R> Buffered<R>
+where
+    R: Read,
+{
+    /// Buffer at least `len` bytes, or as many as remain, and return everything buffered.
+    fn fill_to(&mut self, len: usize) -> io::Result<&[u8]> {
+        if self.buf.len() - self.pos < len {
+            self.buf.drain(..self.pos);
+            self.pos = 0;
+            let missing = len - self.buf.len();
+            (&mut self.reader).take(missing as u64).read_to_end(&mut self.buf)?;
+        }
+        Ok(&self.buf[self.pos..])
+    }
+}
+
+impl<R> Read for Buffered<R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = std::cmp::min(buf.len(), available.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R> BufRead for Buffered<R>
+where
+    R: Read,
+{
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos == self.buf.len() {
+            self.fill_to(BUFSIZE)
+        } else {
+            Ok(&self.buf[self.pos..])
+        }
+    }
+
+    fn consume(&mut self, amt: usize) {
+        let amt = std::cmp::min(amt, self.buf.len() - self.pos);
+        self.pos += amt;
+        self.consumed += amt as u64;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use super::*;
+    use crate::{compress_parallel, test::test_input, CompressionLevel};
+
+    #[test]
+    fn test_multi_member_reader() {
+        use flate2::{write::GzEncoder, Compression};
+
+        let input = test_input(300_000);
+        let parts: Vec<&[u8]> = input.chunks(70_000).collect();
+        // Alternate BGZF data and ordinary gzip members, starting with gzip.
+        let mut data = vec![];
+        for (i, part) in parts.iter().enumerate() {
+            if i % 2 == 0 {
+                let mut gzip = GzEncoder::new(&mut data, Compression::fast());
+                gzip.write_all(part).unwrap();
+                gzip.finish().unwrap();
+            } else {
+                data.extend(compress_parallel(part, CompressionLevel::new(3).unwrap()).unwrap());
+            }
+        }
+
+        let blocks = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+        let mut reader = MultiMemberReader::new(data.as_slice());
+        let sink = std::sync::Arc::clone(&blocks);
+        reader.set_block_callback(move |block| sink.lock().unwrap().push(block));
+        let mut decompressed = vec![];
+        // Read in small pieces so that members end part way through reads.
+        let mut buf = [0; 1000];
+        loop {
+            let n = reader.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            decompressed.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(decompressed, input);
+        assert_eq!(reader.gzip_members(), 3);
+
+        // Every reported block is a BGZF block at the offsets it was reported at.
+        let blocks = blocks.lock().unwrap();
+        assert!(blocks.len() >= 4);
+        for block in blocks.iter() {
+            let start = block.compressed_offset as usize;
+            let mut block_data = vec![];
+            Decompressor::new()
+                .decompress_block(&data[start..start + block.compressed_size], &mut block_data)
+                .unwrap();
+            let uncompressed = block.uncompressed_offset as usize;
+            assert_eq!(block_data, input[uncompressed..uncompressed + block.uncompressed_size]);
+        }
+
+        // Truncated members and other data are errors.
+        let mut sink = vec![];
+        assert!(MultiMemberReader::new(&data[..100]).read_to_end(&mut sink).is_err());
+        data.extend_from_slice(b"not gzip data");
+        assert!(MultiMemberReader::new(data.as_slice()).read_to_end(&mut sink).is_err());
+    }
+}
@@ -0,0 +1,300 @@
+//! The names of the most used parts of the `noodles-bgzf` API, on top of this crate's reader and
+//! writer.
+//!
+//! Code written against `noodles_bgzf` can try this crate out by changing its import to
+//! `use bgzf::noodles as noodles_bgzf;`, as long as it sticks to the [`Reader`], [`Writer`], and
+//! [`VirtualPosition`] methods here. Compressed offsets in virtual positions count from where the
+//! reader or writer started, as they do in noodles.
+//!
+//! # Example
+//!
+//! ```rust
+//! use bgzf::noodles as noodles_bgzf;
+//! use std::io::{Read, Write};
+//!
+//! let mut writer = noodles_bgzf::Writer::new(vec![]);
+//! writer.write_all(b"hello").unwrap();
+//! writer.flush().unwrap();
+//! let start = writer.virtual_position();
+//! writer.write_all(b" world").unwrap();
+//! let compressed = writer.finish().unwrap();
+//!
+//! let mut reader = noodles_bgzf::Reader::new(std::io::Cursor::new(compressed));
+//! reader.seek(start).unwrap();
+//! let mut rest = String::new();
+//! reader.read_to_string(&mut rest).unwrap();
+//! assert_eq!(rest, " world");
+//! ```
+use std::{
+    fmt,
+    io::{self, Read, Seek, SeekFrom, Write},
+};
+
+use thiserror::Error;
+
+use crate::{CompressionLevel, VirtualOffset};
+
+/// A BGZF virtual position, shaped like `noodles_bgzf::VirtualPosition`.
+///
+/// This is a [`VirtualOffset`] under noodles' method names, and converts to and from one.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VirtualPosition(VirtualOffset);
+
+impl VirtualPosition {
+    /// The smallest virtual position.
+    pub const MIN: Self = Self(VirtualOffset::MIN);
+
+    /// The largest virtual position.
+    pub const MAX: Self = Self(VirtualOffset::MAX);
+
+    /// The offset of the block in the compressed data.
+    pub fn compressed(self) -> u64 {
+        self.0.compressed_offset()
+    }
+
+    /// The offset within the uncompressed contents of the block.
+    pub fn uncompressed(self) -> u16 {
+        self.0.uncompressed_offset()
+    }
+}
+
+impl From<u64> for VirtualPosition {
+    fn from(value: u64) -> Self {
+        Self(VirtualOffset::from(value))
+    }
+}
+
+impl From<VirtualPosition> for u64 {
+    fn from(position: VirtualPosition) -> Self {
+        position.0.into()
+    }
+}
+
+impl From<VirtualOffset> for VirtualPosition {
+    fn from(offset: VirtualOffset) -> Self {
+        Self(offset)
+    }
+}
+
+impl From<VirtualPosition> for VirtualOffset {
+    fn from(position: VirtualPosition) -> Self {
+        position.0
+    }
+}
+
+impl TryFrom<(u64, u16)> for VirtualPosition {
+    type Error = CompressedOffsetError;
+
+    fn try_from((compressed, uncompressed): (u64, u16)) -> Result<Self, Self::Error> {
+        if compressed > VirtualOffset::MAX_COMPRESSED_OFFSET {
+            return Err(CompressedOffsetError(compressed));
+        }
+        Ok(Self(VirtualOffset::new(compressed, uncompressed)))
+    }
+}
+
+impl fmt::Display for VirtualPosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// The error converting a compressed offset too large for a [`VirtualPosition`].
+#[derive(Error, Debug, Copy, Clone, PartialEq, Eq)]
+#[error("Compressed offset {0} does not fit in a virtual position")]
+pub struct CompressedOffsetError(pub u64);
+
+/// A BGZF reader shaped like `noodles_bgzf::Reader`.
+pub struct Reader<R> {
+    inner: crate::Reader<R>,
+    /// The compressed offset `inner` started at, after the last seek
+    base: u64,
+}
+
+impl<R> Reader<R>
+where
+    R: Read,
+{
+    /// Read the BGZF data in `inner`.
+    pub fn new(inner: R) -> Self {
+        Self { inner: crate::Reader::new(inner), base: 0 }
+    }
+
+    /// A reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        self.inner.get_ref()
+    }
+
+    /// A mutable reference to the inner reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        self.inner.get_mut()
+    }
+
+    /// Unwrap the inner reader, discarding any data decompressed but not yet read.
+    pub fn into_inner(self) -> R {
+        self.inner.into_inner()
+    }
+
+    /// The virtual position of the next byte to be read.
+    pub fn virtual_position(&self) -> VirtualPosition {
+        let offset = self.inner.virtual_position();
+        VirtualPosition(VirtualOffset::new(
+            self.base + offset.compressed_offset(),
+            offset.uncompressed_offset(),
+        ))
+    }
+}
+
+impl<R> Reader<R>
+where
+    R: Read + Seek,
+{
+    /// Move to `pos`, such as a position from a BAI, CSI, or tabix index, returning it.
+    ///
+    /// Fails if `pos` is further into its block than the block's data goes.
+    pub fn seek(&mut self, pos: VirtualPosition) -> io::Result<VirtualPosition> {
+        self.inner.get_mut().seek(SeekFrom::Start(pos.compressed()))?;
+        self.inner.restart();
+        self.base = pos.compressed();
+        let block_size = self.inner.peek_block()?.map_or(0, |block| block.uncompressed_size);
+        if usize::from(pos.uncompressed()) > block_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Virtual position {} is past the end of its block", pos),
+            ));
+        }
+        self.inner.skip(pos.uncompressed().into())?;
+        Ok(pos)
+    }
+}
+
+impl<R> Read for Reader<R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+/// Builds a [`Writer`], like `noodles_bgzf::writer::Builder`.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Builder {
+    compression_level: Option<CompressionLevel>,
+}
+
+impl Builder {
+    /// Compress at `compression_level` rather than the default of 6.
+    pub fn set_compression_level(mut self, compression_level: CompressionLevel) -> Self {
+        self.compression_level = Some(compression_level);
+        self
+    }
+
+    /// Build a writer that writes to `writer`.
+    pub fn build_from_writer<W>(self, writer: W) -> Writer<W>
+    where
+        W: Write,
+    {
+        let compression_level = self.compression_level.unwrap_or_else(default_compression_level);
+        Writer { inner: crate::Writer::new(writer, compression_level) }
+    }
+}
+
+/// A BGZF writer shaped like `noodles_bgzf::Writer`.
+pub struct Writer<W>
+where
+    W: Write,
+{
+    inner: crate::Writer<W>,
+}
+
+impl<W> Writer<W>
+where
+    W: Write,
+{
+    /// Write BGZF data to `inner` at the default compression level of 6.
+    pub fn new(inner: W) -> Self {
+        Builder::default().build_from_writer(inner)
+    }
+
+    /// A reference to the inner writer.
+    pub fn get_ref(&self) -> &W {
+        self.inner.get_ref()
+    }
+
+    /// A mutable reference to the inner writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        self.inner.get_mut()
+    }
+
+    /// The virtual position the next byte written will have.
+    pub fn virtual_position(&self) -> VirtualPosition {
+        VirtualPosition(self.inner.virtual_position())
+    }
+
+    /// Write out any buffered data and the EOF marker block without giving up the inner writer.
+    pub fn try_finish(&mut self) -> io::Result<()> {
+        self.inner.write_eof()
+    }
+
+    /// Write out any buffered data and the EOF marker block, and return the inner writer.
+    pub fn finish(self) -> io::Result<W> {
+        self.inner.finish().map(|(writer, _)| writer)
+    }
+}
+
+impl<W> Write for Writer<W>
+where
+    W: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// The compression level noodles and libdeflate default to.
+fn default_compression_level() -> CompressionLevel {
+    CompressionLevel::new(6).expect("6 is a valid compression level")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_noodles_virtual_positions() {
+        let records: Vec<Vec<u8>> =
+            (0..20_000_u32).map(|i| format!("record {}\n", i * 7919).into_bytes()).collect();
+        let mut writer = Builder::default()
+            .set_compression_level(CompressionLevel::new(3).unwrap())
+            .build_from_writer(vec![]);
+        let mut positions = vec![];
+        for record in &records {
+            positions.push(writer.virtual_position());
+            writer.write_all(record).unwrap();
+        }
+        let compressed = writer.finish().unwrap();
+
+        let mut reader = crate::Reader::new(compressed.as_slice());
+        for (record, &position) in records.iter().zip(&positions) {
+            assert_eq!(VirtualPosition::from(reader.virtual_position()), position);
+            let mut buf = vec![0; record.len()];
+            reader.read_exact(&mut buf).unwrap();
+        }
+
+        let mut reader = Reader::new(std::io::Cursor::new(compressed));
+        for i in (0..records.len()).rev().step_by(997) {
+            assert_eq!(reader.seek(positions[i]).unwrap(), positions[i]);
+            assert_eq!(reader.virtual_position(), positions[i]);
+            let mut buf = vec![0; records[i].len()];
+            reader.read_exact(&mut buf).unwrap();
+            assert_eq!(buf, records[i]);
+        }
+        let past_end = VirtualPosition::try_from((0, u16::MAX)).unwrap();
+        assert!(reader.seek(past_end).is_err());
+    }
+}
@@ -0,0 +1,278 @@
+//! One-shot compression and decompression of in-memory buffers.
+use std::{
+    io,
+    panic::{self, AssertUnwindSafe},
+    thread,
+};
+
+use crossbeam_channel::unbounded;
+
+use crate::{
+    core::block_size, strip_footer, worker_exited, BgzfError, BgzfResult, BlockFooter, BlockInfo,
+    BufferPool, CompressionLevel, Compressor, Decompressor, ThreadPool, BGZF_BLOCK_SIZE,
+    BGZF_HEADER_SIZE,
+};
+
+/// The number of jobs to split work into per worker thread, to smooth out uneven blocks.
+const JOBS_PER_THREAD: usize = 4;
+
+/// Compress all of `input` into BGZF blocks in parallel, on as many threads as the
+/// [`ThreadPool::global`] pool has.
+///
+/// The returned buffer holds the concatenated blocks followed by the EOF marker, and is
+/// byte-for-byte what a [`crate::Writer`] with the same level and default block size produces.
+/// The chunks of `input` are compressed in place rather than copied first.
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{compress_parallel, Reader};
+/// use std::io::Read;
+///
+/// let input = b"A".repeat(1_000_000);
+/// let compressed = compress_parallel(&input, 2.try_into().unwrap()).unwrap();
+///
+/// let mut decompressed = vec![];
+/// Reader::new(compressed.as_slice()).read_to_end(&mut decompressed).unwrap();
+/// assert_eq!(decompressed, input);
+/// ```
+pub fn compress_parallel(input: &[u8], compression_level: CompressionLevel) -> BgzfResult<Vec<u8>> {
+    let pool = ThreadPool::global();
+    let num_blocks = (input.len() + BGZF_BLOCK_SIZE - 1) / BGZF_BLOCK_SIZE;
+    let num_jobs = num_jobs(pool, num_blocks);
+    let bytes_per_job = (num_blocks + num_jobs - 1) / num_jobs * BGZF_BLOCK_SIZE;
+
+    let jobs = (0..num_jobs).map(|index| {
+        let start = std::cmp::min(index * bytes_per_job, input.len());
+        let end = std::cmp::min(start + bytes_per_job, input.len());
+        let chunk = &input[start..end];
+        move || {
+            let mut compressor = Compressor::new(compression_level);
+            let mut output = Vec::with_capacity(chunk.len());
+            let mut block = BufferPool::global().get();
+            for bytes in chunk.chunks(BGZF_BLOCK_SIZE) {
+                compressor.compress(bytes, &mut block)?;
+                output.extend_from_slice(&block);
+            }
+            BufferPool::global().put(block);
+            Ok(output)
+        }
+    });
+    let outputs = run_in_order(pool, jobs)?;
+
+    let mut compressed = Vec::with_capacity(outputs.iter().map(Vec::len).sum());
+    for output in outputs {
+        compressed.extend_from_slice(&output);
+    }
+    Compressor::append_eof(&mut compressed);
+    Ok(compressed)
+}
+
+/// Decompress every block of the BGZF data in `input`.
+///
+/// The output is allocated up front from the sizes recorded in the block footers, and each block
+/// is decompressed directly into place.
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{decompress_all, Writer};
+/// use std::io::Write;
+///
+/// let mut compressed = vec![];
+/// let mut writer = Writer::new(&mut compressed, 2.try_into().unwrap());
+/// writer.write_all(b"hello world").unwrap();
+/// drop(writer);
+///
+/// assert_eq!(decompress_all(&compressed).unwrap(), b"hello world");
+/// ```
+pub fn decompress_all(input: &[u8]) -> BgzfResult<Vec<u8>> {
+    let blocks = scan_blocks(input)?;
+    let mut output = vec![0; blocks.iter().map(|b| b.uncompressed_size).sum()];
+    let mut decompressor = Decompressor::new();
+    for block in &blocks {
+        let start = block.uncompressed_offset as usize;
+        let end = start + block.uncompressed_size;
+        decompress_block(&mut decompressor, input, block, &mut output[start..end])?;
+    }
+    Ok(output)
+}
+
+/// Decompress every block of the BGZF data in `input` in parallel, on as many threads as the
+/// [`ThreadPool::global`] pool has.
+///
+/// See [`decompress_all`]. The blocks are decompressed in place rather than copied first.
+pub fn decompress_all_parallel(input: &[u8]) -> BgzfResult<Vec<u8>> {
+    decompress_on_pool(input, ThreadPool::global())
+}
+
+/// Decompress every block of the BGZF data in `input` in parallel, on as many threads as `pool`
+/// has.
+pub(crate) fn decompress_on_pool(input: &[u8], pool: &ThreadPool) -> BgzfResult<Vec<u8>> {
+    let blocks = scan_blocks(input)?;
+    let num_jobs = num_jobs(pool, blocks.len());
+    let blocks_per_job = (blocks.len() + num_jobs - 1) / num_jobs;
+
+    let jobs = blocks.chunks(blocks_per_job.max(1)).map(|group| {
+        move || {
+            let mut output = vec![0; group.iter().map(|b| b.uncompressed_size).sum()];
+            let mut decompressor = Decompressor::new();
+            let mut start = 0;
+            for block in group {
+                let end = start + block.uncompressed_size;
+                decompress_block(&mut decompressor, input, block, &mut output[start..end])?;
+                start = end;
+            }
+            Ok(output)
+        }
+    });
+    let outputs = run_in_order(pool, jobs)?;
+
+    let mut decompressed = Vec::with_capacity(blocks.iter().map(|b| b.uncompressed_size).sum());
+    for output in outputs {
+        decompressed.extend_from_slice(&output);
+    }
+    Ok(decompressed)
+}
+
+/// Locate every block in `input` from its header and footer, without decompressing anything.
+fn scan_blocks(input: &[u8]) -> BgzfResult<Vec<BlockInfo>> {
+    let mut blocks = vec![];
+    let mut compressed_offset = 0;
+    let mut uncompressed_offset = 0;
+    while compressed_offset < input.len() {
+        let remaining = &input[compressed_offset..];
+        if remaining.len() < BGZF_HEADER_SIZE {
+            return Err(truncated());
+        }
+        let size = block_size(remaining)?;
+        if remaining.len() < size {
+            return Err(truncated());
+        }
+
+        let check = BlockFooter::from_block(&remaining[..size])?;
+        blocks.push(BlockInfo {
+            compressed_offset: compressed_offset as u64,
+            compressed_size: size,
+            uncompressed_offset,
+            uncompressed_size: check.uncompressed_size as usize,
+        });
+        compressed_offset += size;
+        uncompressed_offset += u64::from(check.uncompressed_size);
+    }
+    Ok(blocks)
+}
+
+/// Decompress the `block` located in `input` into `output`, which must be exactly its size.
+fn decompress_block(
+    decompressor: &mut Decompressor,
+    input: &[u8],
+    block: &BlockInfo,
+    output: &mut [u8],
+) -> BgzfResult<()> {
+    let start = block.compressed_offset as usize;
+    let bytes = &input[start..start + block.compressed_size];
+    let check = BlockFooter::from_block(bytes)?;
+    decompressor.decompress(strip_footer(&bytes[BGZF_HEADER_SIZE..])?, output, check)
+}
+
+/// The error for input that ends partway through a block.
+fn truncated() -> BgzfError {
+    BgzfError::Io(io::Error::new(io::ErrorKind::UnexpectedEof, "Truncated BGZF block"))
+}
+
+/// The number of jobs to split `num_items` units of work into on `pool`, which is at least one.
+fn num_jobs(pool: &ThreadPool, num_items: usize) -> usize {
+    std::cmp::min(num_items, pool.num_threads() * JOBS_PER_THREAD).max(1)
+}
+
+/// Run each of `jobs` on as many scoped threads as `pool` has, returning their results in the
+/// order the jobs were given.
+///
+/// The jobs may borrow from the caller, which [`ThreadPool::spawn`] doesn't allow, so they run on
+/// threads of their own rather than on the pool's workers.
+fn run_in_order<T, J, I>(pool: &ThreadPool, jobs: I) -> BgzfResult<Vec<T>>
+where
+    T: Send,
+    J: FnOnce() -> BgzfResult<T> + Send,
+    I: IntoIterator<Item = J>,
+{
+    let (job_sender, job_receiver) = unbounded();
+    let mut num_jobs = 0;
+    for job in jobs.into_iter().enumerate() {
+        // The receiver is still held, so this can't fail.
+        let _ = job_sender.send(job);
+        num_jobs += 1;
+    }
+    drop(job_sender);
+
+    let (sender, receiver) = unbounded();
+    thread::scope(|scope| {
+        for i in 0..std::cmp::min(num_jobs, pool.num_threads()) {
+            let jobs = job_receiver.clone();
+            let sender = sender.clone();
+            thread::Builder::new()
+                .name(format!("bgzf-scoped-{}", i))
+                .spawn_scoped(scope, move || {
+                    for (index, job) in jobs {
+                        // A panicking job sends nothing back, which is reported as an error below.
+                        if let Ok(result) = panic::catch_unwind(AssertUnwindSafe(job)) {
+                            let _ = sender.send((index, result));
+                        }
+                    }
+                })
+                .expect("Failed to spawn bgzf worker thread");
+        }
+    });
+    drop(sender);
+
+    let mut results: Vec<Option<T>> = (0..num_jobs).map(|_| None).collect();
+    for (index, result) in receiver {
+        results[index] = Some(result?);
+    }
+    results.into_iter().map(|result| result.ok_or_else(worker_exited)).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use super::*;
+    use crate::{test::test_input, Writer, BGZF_EOF};
+
+    #[test]
+    fn test_compress_parallel_matches_writer() {
+        let input = test_input(1_000_000);
+
+        let mut expected = vec![];
+        let mut writer = Writer::new(&mut expected, CompressionLevel::new(6).unwrap());
+        writer.write_all(&input).unwrap();
+        drop(writer);
+
+        let compressed = compress_parallel(&input, CompressionLevel::new(6).unwrap()).unwrap();
+        assert_eq!(compressed, expected);
+        assert_eq!(compress_parallel(&[], CompressionLevel::new(6).unwrap()).unwrap(), BGZF_EOF);
+    }
+
+    #[test]
+    fn test_decompress_all() {
+        let input = test_input(1_000_000);
+        let mut compressed = vec![];
+        let mut writer =
+            Writer::with_capacity(&mut compressed, CompressionLevel::new(3).unwrap(), 4000);
+        for chunk in input.chunks(100_000) {
+            writer.write_all(chunk).unwrap();
+            writer.flush().unwrap();
+        }
+        drop(writer);
+
+        assert_eq!(decompress_all(&compressed).unwrap(), input);
+        assert_eq!(decompress_all_parallel(&compressed).unwrap(), input);
+        assert!(decompress_all(&[]).unwrap().is_empty());
+        assert!(decompress_all_parallel(&[]).unwrap().is_empty());
+
+        let truncated = &compressed[..compressed.len() - 10];
+        assert!(matches!(decompress_all(truncated), Err(BgzfError::Io(_))));
+        assert!(matches!(decompress_all_parallel(truncated), Err(BgzfError::Io(_))));
+    }
+}
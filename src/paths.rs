@@ -0,0 +1,347 @@
+//! Compressing and decompressing whole files in one call.
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    process,
+};
+
+use crate::{
+    core::block_size, oneshot::decompress_on_pool, read_header, BlockSink, CompressionLevel,
+    ParallelWriter, Reader, ThreadPool, Writer, BGZF_HEADER_SIZE, BUFSIZE,
+};
+
+/// The number of blocks decompressed together per thread by [`decompress_path`].
+const BLOCKS_PER_THREAD: usize = 16;
+
+/// Compress the file at `input` into a BGZF file at `output`, returning the number of bytes
+/// compressed.
+///
+/// Compression runs on `threads` threads: one compresses on the calling thread, more on a
+/// [`ThreadPool`] of that size, and `None` uses the [`ThreadPool::global`] pool. The output is
+/// the same whatever the number of threads. It is written to a temporary file next to `output`
+/// that is renamed into place once complete, so `output` never holds a partial file; the
+/// temporary file is removed on error.
+///
+/// # Panics
+///
+/// - If `threads` is `Some(0)`
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{compress_path, decompress_path};
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let (plain, compressed, restored) =
+///     (dir.path().join("a.txt"), dir.path().join("a.txt.gz"), dir.path().join("b.txt"));
+/// std::fs::write(&plain, b"ACGT".repeat(100_000)).unwrap();
+///
+/// compress_path(&plain, &compressed, 6.try_into().unwrap(), None).unwrap();
+/// assert_eq!(decompress_path(&compressed, &restored, Some(2)).unwrap(), 400_000);
+/// assert_eq!(std::fs::read(&restored).unwrap(), b"ACGT".repeat(100_000));
+/// ```
+pub fn compress_path<P, Q>(
+    input: P,
+    output: Q,
+    compression_level: CompressionLevel,
+    threads: Option<usize>,
+) -> io::Result<u64>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let mut reader = File::open(input)?;
+    write_atomically(output.as_ref(), |file| match threads {
+        Some(1) => {
+            let mut writer = Writer::new(file, compression_level);
+            let copied = io::copy(&mut reader, &mut writer)?;
+            let (file, _) = writer.finish()?;
+            Ok((copied, file))
+        }
+        _ => {
+            let pool = threads.map(ThreadPool::new);
+            let pool = pool.as_ref().unwrap_or_else(|| ThreadPool::global());
+            let mut writer = ParallelWriter::with_pool(file, compression_level, pool);
+            let copied = io::copy(&mut reader, &mut writer)?;
+            Ok((copied, writer.finish()?))
+        }
+    })
+}
+
+/// Decompress the BGZF file at `input` into a file at `output`, returning the number of bytes
+/// decompressed.
+///
+/// `threads` is as for [`compress_path`]. With more than one thread, batches of blocks are read
+/// and then decompressed in parallel. The output is written to a temporary file next to
+/// `output` and renamed into place once complete.
+///
+/// # Panics
+///
+/// - If `threads` is `Some(0)`
+pub fn decompress_path<P, Q>(input: P, output: Q, threads: Option<usize>) -> io::Result<u64>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let input = File::open(input)?;
+    write_atomically(output.as_ref(), |file| {
+        let mut file = BufWriter::new(file);
+        let copied = match threads {
+            Some(1) => io::copy(&mut Reader::new(input), &mut file)?,
+            _ => {
+                let pool = threads.map(ThreadPool::new);
+                let pool = pool.as_ref().unwrap_or_else(|| ThreadPool::global());
+                decompress_batches(BufReader::new(input), &mut file, pool)?
+            }
+        };
+        Ok((copied, file.into_inner().map_err(io::IntoInnerError::into_error)?))
+    })
+}
+
+/// Decompress `reader` to `writer` in batches of blocks on `pool`, returning the number of bytes
+/// decompressed.
+fn decompress_batches<R, W>(mut reader: R, writer: &mut W, pool: &ThreadPool) -> io::Result<u64>
+where
+    R: Read,
+    W: Write,
+{
+    let batch_blocks = pool.num_threads() * BLOCKS_PER_THREAD;
+    let mut batch = Vec::with_capacity(BUFSIZE);
+    let mut header = [0; BGZF_HEADER_SIZE];
+    let mut total = 0;
+    loop {
+        batch.clear();
+        let mut blocks = 0;
+        while blocks < batch_blocks {
+            match read_header(&mut reader, &mut header)? {
+                0 => break,
+                BGZF_HEADER_SIZE => {}
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "Truncated BGZF block",
+                    ))
+                }
+            }
+            let size =
+                block_size(&header).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            batch.extend_from_slice(&header);
+            let start = batch.len();
+            batch.resize(start + size - BGZF_HEADER_SIZE, 0);
+            reader.read_exact(&mut batch[start..])?;
+            blocks += 1;
+        }
+        if blocks == 0 {
+            return Ok(total);
+        }
+        let decompressed = decompress_on_pool(&batch, pool)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        writer.write_all(&decompressed)?;
+        total += decompressed.len() as u64;
+    }
+}
+
+/// A file written under a temporary name next to its path and only renamed into place once the
+/// [`Writer`] writing it finishes.
+///
+/// If the writer is dropped without finishing, or finishing fails, the temporary file is removed,
+/// so a crashed or failed job never leaves a truncated file at the path. See
+/// [`Writer::create_atomic`].
+pub struct AtomicFile {
+    file: File,
+    /// Where the file is written until it is complete
+    temp: PathBuf,
+    /// Where the file is moved once it is complete
+    path: PathBuf,
+    committed: bool,
+}
+
+impl AtomicFile {
+    /// Create a temporary file to be moved to `path` once complete.
+    pub fn create<P>(path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref().to_path_buf();
+        let temp = temp_path(&path);
+        let file = File::create(&temp)?;
+        Ok(Self { file, temp, path, committed: false })
+    }
+
+    /// The path the file is moved to once complete.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The path the file is written to until it is complete.
+    pub fn temp_path(&self) -> &Path {
+        &self.temp
+    }
+
+    /// Whether the file has been moved into place.
+    pub fn is_committed(&self) -> bool {
+        self.committed
+    }
+}
+
+impl BlockSink for AtomicFile {
+    fn emit_block(&mut self, block: &[u8]) -> io::Result<()> {
+        self.file.write_all(block)
+    }
+
+    fn flush_blocks(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+
+    /// Sync the file to disk and rename it into place.
+    fn finish_blocks(&mut self) -> io::Result<()> {
+        self.file.sync_all()?;
+        fs::rename(&self.temp, &self.path)?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = fs::remove_file(&self.temp);
+        }
+    }
+}
+
+impl Writer<AtomicFile> {
+    /// Create a BGZF writer for a file at `path` that only appears there once
+    /// [`Writer::finish`] succeeds.
+    ///
+    /// The data is written to a hidden temporary file in the same directory, which `finish`
+    /// syncs to disk and renames to `path`, replacing any file already there. The temporary file
+    /// is removed if the writer is dropped without finishing or finishing fails.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::{decompress_all, Writer};
+    /// use std::io::Write;
+    ///
+    /// let dir = tempfile::tempdir().unwrap();
+    /// let path = dir.path().join("example.gz");
+    ///
+    /// let mut writer = Writer::create_atomic(&path, 2.try_into().unwrap()).unwrap();
+    /// writer.write_all(b"hello world").unwrap();
+    /// assert!(!path.exists());
+    /// writer.finish().unwrap();
+    /// assert_eq!(decompress_all(&std::fs::read(&path).unwrap()).unwrap(), b"hello world");
+    ///
+    /// let dropped = dir.path().join("dropped.gz");
+    /// let mut writer = Writer::create_atomic(&dropped, 2.try_into().unwrap()).unwrap();
+    /// writer.write_all(b"hello world").unwrap();
+    /// drop(writer);
+    /// assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    /// ```
+    pub fn create_atomic<P>(path: P, compression_level: CompressionLevel) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        AtomicFile::create(path).map(|file| Self::new(file, compression_level))
+    }
+}
+
+/// Create a temporary file next to `path`, pass it to `write`, and rename it to `path` once
+/// `write` returns it, removing it instead if anything fails.
+fn write_atomically<F, T>(path: &Path, write: F) -> io::Result<T>
+where
+    F: FnOnce(File) -> io::Result<(T, File)>,
+{
+    let temp = temp_path(path);
+    let result = File::create(&temp).and_then(write).and_then(|(value, file)| {
+        file.sync_all()?;
+        drop(file);
+        fs::rename(&temp, path)?;
+        Ok(value)
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+/// A hidden temporary path in the same directory as `path`, so that renaming it is atomic.
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(format!(".{}.tmp", process::id()));
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod test {
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::{compress_parallel, test::test_input};
+
+    #[test]
+    fn test_compress_and_decompress_path() {
+        let dir = tempdir().unwrap();
+        let input = test_input(1_000_000);
+        let plain = dir.path().join("input.bin");
+        std::fs::write(&plain, &input).unwrap();
+        let level = CompressionLevel::new(3).unwrap();
+
+        // The output is the same however many threads compress it.
+        let expected = compress_parallel(&input, level).unwrap();
+        for threads in [Some(1), Some(3), None] {
+            let compressed = dir.path().join("input.bin.gz");
+            assert_eq!(compress_path(&plain, &compressed, level, threads).unwrap(), 1_000_000);
+            assert_eq!(std::fs::read(&compressed).unwrap(), expected);
+
+            let restored = dir.path().join("restored.bin");
+            assert_eq!(decompress_path(&compressed, &restored, threads).unwrap(), 1_000_000);
+            assert_eq!(std::fs::read(&restored).unwrap(), input);
+        }
+
+        // A failure leaves neither the output nor a temporary file behind.
+        let truncated = dir.path().join("truncated.gz");
+        std::fs::write(&truncated, &expected[..expected.len() / 2]).unwrap();
+        for threads in [Some(1), Some(2)] {
+            let output = dir.path().join("failed.bin");
+            assert!(decompress_path(&truncated, &output, threads).is_err());
+            assert!(!output.exists());
+        }
+        assert!(compress_path(dir.path().join("missing"), dir.path().join("x.gz"), level, None)
+            .is_err());
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 4);
+    }
+
+    #[test]
+    fn test_writer_create_atomic() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test.gz");
+        let input = test_input(200_000);
+        let level = CompressionLevel::new(3).unwrap();
+        std::fs::write(&path, b"old").unwrap();
+
+        let mut writer = Writer::create_atomic(&path, level).unwrap();
+        let temp = writer.get_ref().temp_path().to_path_buf();
+        assert_eq!(temp.parent(), path.parent());
+        writer.write_all(&input).unwrap();
+        writer.flush().unwrap();
+        assert!(temp.exists());
+        assert_eq!(std::fs::read(&path).unwrap(), b"old");
+
+        let (file, _) = writer.finish().unwrap();
+        assert!(file.is_committed());
+        drop(file);
+        assert!(!temp.exists());
+        assert_eq!(std::fs::read(&path).unwrap(), compress_parallel(&input, level).unwrap());
+
+        let mut writer = Writer::create_atomic(&path, level).unwrap();
+        writer.write_all(b"partial").unwrap();
+        drop(writer);
+        assert!(!temp.exists());
+        assert_eq!(std::fs::read(&path).unwrap(), compress_parallel(&input, level).unwrap());
+    }
+}
@@ -0,0 +1,199 @@
+//! Filtering or rewriting a BGZF stream on a pipeline of threads.
+use std::{
+    io::{self, Read, Write},
+    thread::{self, ScopedJoinHandle},
+};
+
+use crossbeam_channel::{bounded, Sender};
+
+use crate::{CompressionLevel, ParallelWriter, Reader, ThreadPool, BGZF_BLOCK_SIZE};
+
+/// The settings for [`pipe`].
+#[derive(Debug, Copy, Clone)]
+pub struct PipeOptions {
+    /// The compression level of the output blocks
+    pub compression_level: CompressionLevel,
+    /// The number of decompressed bytes read into each chunk passed to the transform
+    pub chunk_size: usize,
+    /// The number of chunks queued between each pair of threads
+    pub queue_len: usize,
+    /// Whether to end each chunk after its last newline, so that chunks hold whole lines
+    pub line_aligned: bool,
+}
+
+impl PipeOptions {
+    /// Output blocks at `compression_level`, passing the transform chunks of four blocks' worth
+    /// of data that may end part way through a line.
+    pub fn new(compression_level: CompressionLevel) -> Self {
+        Self {
+            compression_level,
+            chunk_size: 4 * BGZF_BLOCK_SIZE,
+            queue_len: 4,
+            line_aligned: false,
+        }
+    }
+}
+
+/// Decompress the BGZF data in `reader`, pass it through `transform`, and compress the result to
+/// `writer`.
+///
+/// Decompression runs on one thread and `transform` on another, connected by bounded queues of
+/// [`PipeOptions::queue_len`] chunks, while the calling thread writes the output through a
+/// [`ParallelWriter`] on the [`ThreadPool::global`] pool. `transform` is called once per chunk of
+/// decompressed data, in order, and appends its output for the chunk to the given buffer. The
+/// output ends with the EOF marker, and the inner writer is returned.
+///
+/// The first error from any stage stops the pipeline and is returned; an error reading the input
+/// takes precedence, then one from `transform`, then one writing the output.
+///
+/// # Panics
+///
+/// - If [`PipeOptions::chunk_size`] or [`PipeOptions::queue_len`] is zero
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{decompress_all, pipe, PipeOptions, Writer};
+/// use std::io::Write;
+///
+/// let mut writer = Writer::new(vec![], 2.try_into().unwrap());
+/// for i in 0..100_000 {
+///     writeln!(writer, "chr{}\t{}", i % 3 + 1, i).unwrap();
+/// }
+/// let (compressed, _) = writer.finish().unwrap();
+///
+/// // Keep only the lines on chr2.
+/// let mut options = PipeOptions::new(2.try_into().unwrap());
+/// options.line_aligned = true;
+/// let filtered = pipe(compressed.as_slice(), vec![], options, |chunk, output| {
+///     for line in chunk.split_inclusive(|&b| b == b'\n') {
+///         if line.starts_with(b"chr2\t") {
+///             output.extend_from_slice(line);
+///         }
+///     }
+///     Ok(())
+/// })
+/// .unwrap();
+///
+/// let lines = decompress_all(&filtered).unwrap();
+/// assert_eq!(lines.iter().filter(|&&b| b == b'\n').count(), 33_333);
+/// ```
+pub fn pipe<R, W, F>(reader: R, writer: W, options: PipeOptions, mut transform: F) -> io::Result<W>
+where
+    R: Read + Send,
+    W: Write,
+    F: FnMut(&[u8], &mut Vec<u8>) -> io::Result<()> + Send,
+{
+    assert!(options.chunk_size > 0, "Chunk size must be greater than zero");
+    assert!(options.queue_len > 0, "Queue length must be greater than zero");
+    let (chunk_sender, chunk_receiver) = bounded::<Vec<u8>>(options.queue_len);
+    let (output_sender, output_receiver) = bounded::<Vec<u8>>(options.queue_len);
+
+    thread::scope(|scope| {
+        let reading = thread::Builder::new()
+            .name("bgzf-pipe-reader".to_string())
+            .spawn_scoped(scope, move || read_chunks(reader, &options, chunk_sender))
+            .expect("Failed to spawn bgzf pipe reader thread");
+        let transforming = thread::Builder::new()
+            .name("bgzf-pipe-transform".to_string())
+            .spawn_scoped(scope, move || {
+                // A closed output queue means writing failed, which is reported on its own.
+                for chunk in chunk_receiver {
+                    let mut output = Vec::with_capacity(chunk.len());
+                    transform(&chunk, &mut output)?;
+                    if output_sender.send(output).is_err() {
+                        break;
+                    }
+                }
+                Ok(())
+            })
+            .expect("Failed to spawn bgzf pipe transform thread");
+
+        let mut output =
+            ParallelWriter::with_pool(writer, options.compression_level, ThreadPool::global());
+        let written = output_receiver.iter().try_for_each(|bytes| output.write_all(&bytes));
+        // Close the queue so that the other threads stop if writing failed.
+        drop(output_receiver);
+
+        join(reading)?;
+        join(transforming)?;
+        written?;
+        output.finish()
+    })
+}
+
+/// Decompress `reader` into chunks of `options.chunk_size` bytes and queue them on `chunks`.
+fn read_chunks<R>(reader: R, options: &PipeOptions, chunks: Sender<Vec<u8>>) -> io::Result<()>
+where
+    R: Read,
+{
+    let mut reader = Reader::new(reader);
+    let mut carry = vec![];
+    loop {
+        let mut chunk = std::mem::take(&mut carry);
+        let read = (&mut reader).take(options.chunk_size as u64).read_to_end(&mut chunk)?;
+        if read > 0 && options.line_aligned {
+            match chunk.iter().rposition(|&b| b == b'\n') {
+                Some(end) => carry = chunk.split_off(end + 1),
+                // Keep reading until the line ends.
+                None => {
+                    carry = chunk;
+                    continue;
+                }
+            }
+        }
+        // A closed queue means a later stage failed, which is reported on its own.
+        if !chunk.is_empty() && chunks.send(chunk).is_err() {
+            return Ok(());
+        }
+        if read == 0 {
+            return Ok(());
+        }
+    }
+}
+
+/// Wait for a pipeline thread and return its result.
+fn join(handle: ScopedJoinHandle<'_, io::Result<()>>) -> io::Result<()> {
+    handle
+        .join()
+        .unwrap_or_else(|_| Err(io::Error::new(io::ErrorKind::Other, "bgzf pipe thread panicked")))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{compress_parallel, decompress_all, test::test_input, BGZF_EOF};
+
+    #[test]
+    fn test_pipe() {
+        let input = test_input(1_000_000);
+        let compressed = compress_parallel(&input, CompressionLevel::new(3).unwrap()).unwrap();
+
+        // Small chunks and queues, so that the threads wait on each other.
+        let mut options = PipeOptions::new(CompressionLevel::new(3).unwrap());
+        options.chunk_size = 10_000;
+        options.queue_len = 1;
+        let mut chunks = 0;
+        let output = pipe(compressed.as_slice(), vec![], options, |chunk, output| {
+            chunks += 1;
+            output.extend(chunk.iter().map(|b| b.wrapping_add(1)));
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(chunks, 100);
+        let expected: Vec<u8> = input.iter().map(|b| b.wrapping_add(1)).collect();
+        assert_eq!(decompress_all(&output).unwrap(), expected);
+        assert!(output.ends_with(BGZF_EOF));
+
+        // Errors from the transform and from reading the input are returned.
+        let failed = pipe(compressed.as_slice(), vec![], options, |_, _| {
+            Err(std::io::Error::new(std::io::ErrorKind::Other, "transform failed"))
+        });
+        assert_eq!(failed.unwrap_err().to_string(), "transform failed");
+        let failed = pipe(&compressed[..compressed.len() / 2], vec![], options, |chunk, output| {
+            output.extend_from_slice(chunk);
+            Ok(())
+        });
+        assert!(failed.is_err());
+    }
+}
@@ -0,0 +1,741 @@
+//! A parallel compression pipeline, and the [`ParallelWriter`] built on it.
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    io::{self, Read, Write},
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+use bytes::{Bytes, BytesMut};
+use crossbeam_channel::{bounded, Receiver, Sender};
+
+use crate::{
+    worker_exited, BgzfError, BgzfResult, BufferPool, CompressionLevel, Compressor, ThreadPool,
+    BGZF_BLOCK_SIZE, BGZF_EOF, BUFSIZE, MAX_BGZF_BLOCK_SIZE,
+};
+
+/// The number of blocks allowed in flight per pool thread when no capacity is given.
+const BLOCKS_PER_THREAD: usize = 2;
+
+/// The eventual result of compressing one block on the pool.
+type PendingBlock = Receiver<BgzfResult<Vec<u8>>>;
+
+thread_local! {
+    /// The compressor reused by each pool thread for pipeline blocks.
+    static COMPRESSOR: RefCell<Option<Compressor>> = const { RefCell::new(None) };
+}
+
+/// Compress `chunk` into a single block with the calling pool thread's compressor.
+fn compress_chunk(compression_level: CompressionLevel, chunk: &[u8]) -> BgzfResult<Vec<u8>> {
+    COMPRESSOR.with(|cell| {
+        let mut cell = cell.borrow_mut();
+        let compressor = match cell.as_mut() {
+            Some(compressor) if compressor.level == compression_level => compressor,
+            _ => cell.insert(Compressor::new(compression_level)),
+        };
+        let mut block = BufferPool::global().get();
+        compressor.compress(chunk, &mut block).map(|()| block)
+    })
+}
+
+/// Queue `chunk` to be compressed into a single block on `pool`.
+fn spawn_compression(
+    pool: &ThreadPool,
+    compression_level: CompressionLevel,
+    chunk: Bytes,
+) -> PendingBlock {
+    let (sender, receiver) = bounded(1);
+    pool.spawn(move || {
+        let _ = sender.send(compress_chunk(compression_level, &chunk));
+    });
+    receiver
+}
+
+/// The jobs one [`ParallelWriter`] has on its pool, kept to at most `limit` at once so that the
+/// writer only takes a share of a pool's threads.
+struct JobLimiter {
+    /// The most jobs allowed on the pool at once
+    limit: usize,
+    /// The jobs on the pool, queued or running
+    running: usize,
+    /// The chunks waiting for one of those jobs to finish, with where to send their blocks
+    waiting: VecDeque<(Bytes, Sender<BgzfResult<Vec<u8>>>)>,
+}
+
+/// A [`JobLimiter`] shared between a writer and its jobs.
+type SharedLimiter = Arc<Mutex<JobLimiter>>;
+
+/// Lock `limiter`, which is never left inconsistent by a panic.
+fn lock(limiter: &SharedLimiter) -> MutexGuard<'_, JobLimiter> {
+    limiter.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Queue `chunk` to be compressed into a single block on `pool` as soon as `limiter` allows.
+fn spawn_limited(
+    pool: &ThreadPool,
+    compression_level: CompressionLevel,
+    limiter: &SharedLimiter,
+    chunk: Bytes,
+) -> PendingBlock {
+    let (sender, receiver) = bounded(1);
+    let mut jobs = lock(limiter);
+    if jobs.running < jobs.limit {
+        jobs.running += 1;
+        drop(jobs);
+        run_limited(pool.clone(), compression_level, Arc::clone(limiter), chunk, sender);
+    } else {
+        jobs.waiting.push_back((chunk, sender));
+    }
+    receiver
+}
+
+/// Compress `chunk` on `pool`, then hand the job's place on the pool to the oldest chunk waiting
+/// in `limiter`, if it is still within the limit.
+fn run_limited(
+    pool: ThreadPool,
+    compression_level: CompressionLevel,
+    limiter: SharedLimiter,
+    chunk: Bytes,
+    sender: Sender<BgzfResult<Vec<u8>>>,
+) {
+    pool.clone().spawn(move || {
+        // A panic drops the sender, which the writer sees as an error, but must not leak the place.
+        let result =
+            panic::catch_unwind(AssertUnwindSafe(|| compress_chunk(compression_level, &chunk)));
+        if let Ok(result) = result {
+            let _ = sender.send(result);
+        }
+        drop(sender);
+        let next = {
+            let mut jobs = lock(&limiter);
+            let next = if jobs.running <= jobs.limit { jobs.waiting.pop_front() } else { None };
+            if next.is_none() {
+                jobs.running -= 1;
+            }
+            next
+        };
+        if let Some((chunk, sender)) = next {
+            run_limited(pool, compression_level, limiter, chunk, sender);
+        }
+    });
+}
+
+/// Wait for a block queued by [`spawn_compression`].
+fn wait_for(pending: &PendingBlock) -> BgzfResult<Vec<u8>> {
+    pending.recv().unwrap_or_else(|_| Err(worker_exited()))
+}
+
+/// Create a parallel compression pipeline with room for `capacity` blocks in flight.
+///
+/// Uncompressed chunks handed to the [`ChunkSender`] are compressed on `pool`, and the
+/// [`BlockReceiver`] yields the compressed blocks in the order their chunks were sent. Once
+/// `capacity` blocks are waiting to be received, [`ChunkSender::send`] blocks.
+///
+/// The pipeline produces bare blocks; whoever writes them out is responsible for appending the
+/// EOF marker (see [`crate::Compressor::append_eof`]).
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{compression_pipeline, Compressor, Reader, ThreadPool};
+/// use std::io::Read;
+/// use std::thread;
+///
+/// let (mut sender, receiver) = compression_pipeline(2.try_into().unwrap(), ThreadPool::global(), 8);
+/// let consumer = thread::spawn(move || {
+///     let mut output = vec![];
+///     for block in receiver {
+///         output.extend(block.unwrap());
+///     }
+///     Compressor::append_eof(&mut output);
+///     output
+/// });
+///
+/// sender.send(&b"hello "[..]).unwrap();
+/// sender.send(&b"world"[..]).unwrap();
+/// drop(sender);
+///
+/// let mut decompressed = vec![];
+/// Reader::new(consumer.join().unwrap().as_slice()).read_to_end(&mut decompressed).unwrap();
+/// assert_eq!(decompressed, b"hello world");
+/// ```
+pub fn compression_pipeline(
+    compression_level: CompressionLevel,
+    pool: &ThreadPool,
+    capacity: usize,
+) -> (ChunkSender, BlockReceiver) {
+    let (sender, receiver) = bounded(capacity);
+    (
+        ChunkSender { compression_level, pool: pool.clone(), queue: sender },
+        BlockReceiver { queue: receiver },
+    )
+}
+
+/// The producer side of a [`compression_pipeline`].
+///
+/// Dropping the sender ends the stream of blocks.
+pub struct ChunkSender {
+    compression_level: CompressionLevel,
+    pool: ThreadPool,
+    queue: Sender<PendingBlock>,
+}
+
+impl ChunkSender {
+    /// Send a chunk of uncompressed bytes to be compressed.
+    ///
+    /// Each chunk becomes one block, except that chunks larger than [`BGZF_BLOCK_SIZE`] are split
+    /// into several. Empty chunks are ignored. Fails if the [`BlockReceiver`] has been dropped.
+    pub fn send<B>(&mut self, chunk: B) -> BgzfResult<()>
+    where
+        B: Into<Bytes>,
+    {
+        let chunk = chunk.into();
+        let mut start = 0;
+        while start < chunk.len() {
+            let end = std::cmp::min(start + BGZF_BLOCK_SIZE, chunk.len());
+            let pending =
+                spawn_compression(&self.pool, self.compression_level, chunk.slice(start..end));
+            self.queue.send(pending).map_err(|_| {
+                BgzfError::Io(io::Error::new(
+                    io::ErrorKind::BrokenPipe,
+                    "The pipeline's block receiver has been dropped",
+                ))
+            })?;
+            start = end;
+        }
+        Ok(())
+    }
+}
+
+/// The consumer side of a [`compression_pipeline`], yielding compressed blocks in order.
+///
+/// The blocks' buffers come from [`BufferPool::global`], and can be handed back to it once
+/// written out.
+pub struct BlockReceiver {
+    queue: Receiver<PendingBlock>,
+}
+
+impl BlockReceiver {
+    /// Wait for the next compressed block.
+    ///
+    /// Returns `None` once the [`ChunkSender`] has been dropped and every block has been received.
+    pub fn recv(&self) -> Option<BgzfResult<Vec<u8>>> {
+        self.queue.recv().ok().map(|pending| wait_for(&pending))
+    }
+}
+
+impl Iterator for BlockReceiver {
+    type Item = BgzfResult<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.recv()
+    }
+}
+
+/// A BGZF writer that compresses blocks in parallel on a [`ThreadPool`].
+///
+/// Compressed blocks are written to the inner writer, in order, from the thread calling `write`,
+/// so a [`ParallelWriter`] never starts threads of its own.
+///
+/// The output is byte for byte the same as a [`crate::Writer`]'s with the same level and block
+/// size, whatever the number of threads, the cap on blocks in flight, or how the input is split
+/// into writes, as long as neither is flushed before finishing. (A [`crate::Writer`] also writes
+/// an empty block on flush.)
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::ParallelWriter;
+/// use std::io::Write;
+///
+/// let mut writer = ParallelWriter::new(vec![], 2.try_into().unwrap());
+/// writer.write_all(&b"A".repeat(1_000_000)).unwrap();
+/// let compressed = writer.finish().unwrap();
+/// assert!(compressed.len() < 1_000_000);
+/// ```
+pub struct ParallelWriter<W>
+where
+    W: Write,
+{
+    /// The internal buffer to use
+    uncompressed_buffer: BytesMut,
+    /// The size of the blocks to create
+    blocksize: usize,
+    compression_level: CompressionLevel,
+    pool: ThreadPool,
+    /// The cap on this writer's jobs on the pool, and the chunks waiting under it
+    limiter: SharedLimiter,
+    /// The blocks being compressed, in output order
+    pending: VecDeque<PendingBlock>,
+    /// The maximum number of blocks to have in flight, if set rather than scaled with the pool
+    capacity: Option<usize>,
+    /// The cap on the memory used for blocks in flight, if any
+    memory_limit: Option<usize>,
+    /// The inner writer, taken by `finish`
+    writer: Option<W>,
+}
+
+impl<W> ParallelWriter<W>
+where
+    W: Write,
+{
+    /// Create a new [`ParallelWriter`] that compresses on the [`ThreadPool::global`] pool.
+    pub fn new(writer: W, compression_level: CompressionLevel) -> Self {
+        Self::with_pool(writer, compression_level, ThreadPool::global())
+    }
+
+    /// Create a new [`ParallelWriter`] that compresses on the [`ThreadPool::global`] pool, but on
+    /// at most `threads` of its workers at once rather than all [`crate::default_threads`] of them.
+    ///
+    /// See [`ParallelWriter::set_threads`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `threads` is zero.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::{default_threads, ParallelWriter};
+    ///
+    /// let writer = ParallelWriter::with_threads(vec![], 2.try_into().unwrap(), 3);
+    /// assert_eq!(writer.num_threads(), 3);
+    /// let writer = ParallelWriter::new(vec![], 2.try_into().unwrap());
+    /// assert_eq!(writer.num_threads(), default_threads());
+    /// ```
+    pub fn with_threads(writer: W, compression_level: CompressionLevel, threads: usize) -> Self {
+        let mut writer = Self::with_pool(writer, compression_level, ThreadPool::global());
+        writer.set_threads(threads);
+        writer
+    }
+
+    /// Create a new [`ParallelWriter`] that compresses on `pool`.
+    pub fn with_pool(writer: W, compression_level: CompressionLevel, pool: &ThreadPool) -> Self {
+        Self {
+            uncompressed_buffer: BytesMut::with_capacity(BUFSIZE),
+            blocksize: BGZF_BLOCK_SIZE,
+            compression_level,
+            pool: pool.clone(),
+            limiter: Arc::new(Mutex::new(JobLimiter {
+                limit: pool.num_threads(),
+                running: 0,
+                waiting: VecDeque::new(),
+            })),
+            pending: VecDeque::new(),
+            capacity: None,
+            memory_limit: None,
+            writer: Some(writer),
+        }
+    }
+
+    /// Cap the number of blocks in flight, queued for compression or compressed and waiting to be
+    /// written, at `blocks`, two per pool thread by default.
+    ///
+    /// Once the cap is reached, `write` waits for the oldest block and writes it to the inner
+    /// writer before queueing another, so a slow inner writer holds up the caller rather than
+    /// letting blocks pile up. Memory use is then bounded by about 128 KiB per block in flight,
+    /// and [`ParallelWriter::set_memory_limit`] lowers the cap to fit a byte budget.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `blocks` is zero.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::ParallelWriter;
+    /// use std::io::Write;
+    ///
+    /// let mut writer = ParallelWriter::new(vec![], 2.try_into().unwrap());
+    /// writer.set_max_in_flight(4);
+    /// assert_eq!(writer.max_in_flight(), 4);
+    /// writer.write_all(&b"A".repeat(1_000_000)).unwrap();
+    /// writer.finish().unwrap();
+    /// ```
+    pub fn set_max_in_flight(&mut self, blocks: usize) {
+        assert!(blocks > 0, "At least one block must be allowed in flight");
+        self.capacity = Some(blocks);
+    }
+
+    /// The most pool threads compressing blocks for this writer at once, which may be shared with
+    /// others. This is the pool's size unless [`ParallelWriter::set_threads`] was called.
+    pub fn num_threads(&self) -> usize {
+        lock(&self.limiter).limit
+    }
+
+    /// Compress blocks on at most `threads` of the pool's workers at once, for example to give
+    /// cores back to other work while a long running stream is quiet.
+    ///
+    /// This caps the writer's jobs on the pool rather than starting or stopping threads: chunks
+    /// beyond the cap wait for one of the writer's jobs to finish. A cap above the pool's size
+    /// leaves every worker to the writer. Unless [`ParallelWriter::set_max_in_flight`] was
+    /// called, the cap on blocks in flight follows the new number of threads. The output is the
+    /// same either way.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `threads` is zero.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::ParallelWriter;
+    /// use std::io::Write;
+    ///
+    /// let mut writer = ParallelWriter::with_threads(vec![], 2.try_into().unwrap(), 4);
+    /// writer.write_all(&b"A".repeat(1_000_000)).unwrap();
+    /// writer.set_threads(1);
+    /// assert_eq!((writer.num_threads(), writer.max_in_flight()), (1, 2));
+    /// writer.write_all(&b"C".repeat(1_000_000)).unwrap();
+    /// writer.finish().unwrap();
+    /// ```
+    pub fn set_threads(&mut self, threads: usize) {
+        assert!(threads > 0, "A writer needs at least one thread");
+        let mut jobs = lock(&self.limiter);
+        jobs.limit = threads;
+        // Start the waiting chunks that a higher cap now lets onto the pool.
+        while jobs.running < jobs.limit {
+            let Some((chunk, sender)) = jobs.waiting.pop_front() else {
+                break;
+            };
+            jobs.running += 1;
+            let limiter = Arc::clone(&self.limiter);
+            run_limited(self.pool.clone(), self.compression_level, limiter, chunk, sender);
+        }
+    }
+
+    /// The most blocks allowed in flight, see [`ParallelWriter::set_max_in_flight`].
+    pub fn max_in_flight(&self) -> usize {
+        let blocks = self.capacity.unwrap_or(self.num_threads() * BLOCKS_PER_THREAD);
+        match self.memory_limit {
+            Some(limit) => {
+                let fit = limit.saturating_sub(self.blocksize) / self.block_memory();
+                blocks.min(fit).max(1)
+            }
+            None => blocks,
+        }
+    }
+
+    /// Cap the memory this writer may use for its buffers and blocks in flight at `limit` bytes,
+    /// by lowering [`ParallelWriter::max_in_flight`] as far as needed.
+    ///
+    /// Each block in flight takes up to a block of uncompressed data plus its compressed form, on
+    /// top of the block being filled. Fails with [`io::ErrorKind::OutOfMemory`] if not even one
+    /// block in flight fits in `limit`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::ParallelWriter;
+    /// use std::io::{ErrorKind, Write};
+    ///
+    /// let mut writer = ParallelWriter::with_threads(vec![], 2.try_into().unwrap(), 8);
+    /// assert_eq!(writer.max_in_flight(), 16);
+    /// writer.set_memory_limit(1024 * 1024).unwrap();
+    /// assert_eq!(writer.max_in_flight(), 7);
+    /// assert!(writer.max_memory() <= 1024 * 1024);
+    ///
+    /// let err = writer.set_memory_limit(64 * 1024).unwrap_err();
+    /// assert_eq!(err.kind(), ErrorKind::OutOfMemory);
+    /// ```
+    pub fn set_memory_limit(&mut self, limit: usize) -> io::Result<()> {
+        if limit < self.blocksize + self.block_memory() {
+            return Err(io::Error::new(
+                io::ErrorKind::OutOfMemory,
+                format!("A memory limit of {} bytes leaves no room for a block in flight", limit),
+            ));
+        }
+        self.memory_limit = Some(limit);
+        Ok(())
+    }
+
+    /// The most memory this writer's buffers and blocks in flight may take up, in bytes.
+    pub fn max_memory(&self) -> usize {
+        self.blocksize + self.max_in_flight() * self.block_memory()
+    }
+
+    /// The most memory one block in flight takes up, uncompressed and compressed.
+    fn block_memory(&self) -> usize {
+        self.blocksize + MAX_BGZF_BLOCK_SIZE
+    }
+
+    /// Set the number of uncompressed bytes per block, as [`crate::Writer::with_capacity`] does.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `blocksize` is zero or more than [`BGZF_BLOCK_SIZE`].
+    pub fn set_blocksize(&mut self, blocksize: usize) {
+        assert!(blocksize > 0 && blocksize <= BGZF_BLOCK_SIZE);
+        self.blocksize = blocksize;
+    }
+
+    /// Read `reader` to the end straight into the uncompressed buffer, queueing each full block.
+    pub(crate) fn fill_from<R>(&mut self, reader: &mut R) -> io::Result<()>
+    where
+        R: Read,
+    {
+        loop {
+            let start = self.uncompressed_buffer.len();
+            self.uncompressed_buffer.resize(self.blocksize, 0);
+            let result = reader.read(&mut self.uncompressed_buffer[start..]);
+            let read = *result.as_ref().unwrap_or(&0);
+            self.uncompressed_buffer.truncate(start + read);
+            match result {
+                Ok(0) => return Ok(()),
+                Ok(_) => (),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+            if self.uncompressed_buffer.len() == self.blocksize {
+                let chunk = self.uncompressed_buffer.split().freeze();
+                self.send(chunk)?;
+            }
+        }
+    }
+
+    /// Write out all buffered data followed by the EOF marker, and return the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        let result = self.try_finish();
+        let writer = self.writer.take().expect("writer is only taken by finish");
+        result.map(|()| writer)
+    }
+
+    /// Write out all buffered data followed by the EOF marker.
+    fn try_finish(&mut self) -> io::Result<()> {
+        self.flush()?;
+        let writer = self.writer.as_mut().expect("writer is only taken by finish");
+        writer.write_all(BGZF_EOF)?;
+        writer.flush()
+    }
+
+    /// Queue `chunk` for compression, first writing out completed blocks if at capacity.
+    fn send(&mut self, chunk: Bytes) -> io::Result<()> {
+        while self.pending.len() >= self.max_in_flight() {
+            self.write_next()?;
+        }
+        let pending = spawn_limited(&self.pool, self.compression_level, &self.limiter, chunk);
+        self.pending.push_back(pending);
+        Ok(())
+    }
+
+    /// Wait for the oldest pending block and write it to the inner writer.
+    fn write_next(&mut self) -> io::Result<()> {
+        if let Some(pending) = self.pending.pop_front() {
+            let block = wait_for(&pending).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+            self.writer.as_mut().expect("writer is only taken by finish").write_all(&block)?;
+            BufferPool::global().put(block);
+        }
+        Ok(())
+    }
+}
+
+impl<W> Write for ParallelWriter<W>
+where
+    W: Write,
+{
+    /// Buffer up to a block's worth of `buf`, returning how many bytes were written.
+    ///
+    /// A full buffer is queued for compression before taking more, so that an error leaves `buf`
+    /// unwritten and the buffer never holds more than a block.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.uncompressed_buffer.len() >= self.blocksize {
+            let chunk = self.uncompressed_buffer.split().freeze();
+            self.send(chunk)?;
+        }
+        let amount = std::cmp::min(buf.len(), self.blocksize - self.uncompressed_buffer.len());
+        self.uncompressed_buffer.extend_from_slice(&buf[..amount]);
+        Ok(amount)
+    }
+
+    /// Flush this output stream, waiting for all buffered data to be compressed and written.
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.uncompressed_buffer.is_empty() {
+            let chunk = self.uncompressed_buffer.split().freeze();
+            self.send(chunk)?;
+        }
+        while !self.pending.is_empty() {
+            self.write_next()?;
+        }
+        self.writer.as_mut().expect("writer is only taken by finish").flush()
+    }
+}
+
+impl<W> Drop for ParallelWriter<W>
+where
+    W: Write,
+{
+    fn drop(&mut self) {
+        // Errors can't be reported from drop, call `finish` to observe them.
+        if self.writer.is_some() {
+            let _ = self.try_finish();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{compress_parallel, decompress_all, test::test_input, ChecksumManifest, Writer};
+
+    #[test]
+    fn test_parallel_writer_set_threads_caps_jobs_on_the_pool() {
+        let input = test_input(4 * BGZF_BLOCK_SIZE);
+        let level = CompressionLevel::new(3).unwrap();
+        let mut writer = Writer::new(vec![], level);
+        writer.write_all(&input).unwrap();
+        let (expected, _) = writer.finish().unwrap();
+
+        // Hold up both workers, so that the writer's jobs queue up behind them.
+        let pool = ThreadPool::new(2);
+        let (release, blocked) = bounded::<()>(0);
+        for _ in 0..2 {
+            let blocked = blocked.clone();
+            pool.spawn(move || {
+                let _ = blocked.recv();
+            });
+        }
+        let mut writer = ParallelWriter::with_pool(vec![], level, &pool);
+        writer.set_threads(1);
+        writer.set_max_in_flight(8);
+        writer.write_all(&input).unwrap();
+        {
+            let jobs = lock(&writer.limiter);
+            assert_eq!((jobs.running, jobs.waiting.len()), (1, 2));
+        }
+        drop(release);
+        assert_eq!(writer.finish().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_parallel_writer_matches_writer() {
+        let input = test_input(1_000_000);
+
+        let mut expected = vec![];
+        let mut writer = Writer::new(&mut expected, CompressionLevel::new(6).unwrap());
+        writer.write_all(&input).unwrap();
+        drop(writer);
+
+        let pool = ThreadPool::new(3);
+        let mut writer =
+            ParallelWriter::with_pool(vec![], CompressionLevel::new(6).unwrap(), &pool);
+        for chunk in input.chunks(10_000) {
+            writer.write_all(chunk).unwrap();
+        }
+        assert_eq!(writer.finish().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_parallel_writer_set_threads() {
+        let input = test_input(900_000);
+        let level = CompressionLevel::new(3).unwrap();
+        let mut writer = Writer::new(vec![], level);
+        writer.write_all(&input).unwrap();
+        let (expected, _) = writer.finish().unwrap();
+
+        let mut writer = ParallelWriter::with_threads(vec![], level, 2);
+        for (i, chunk) in input.chunks(100_000).enumerate() {
+            writer.set_threads([2, 5, 1][i % 3]);
+            assert_eq!(writer.max_in_flight(), 2 * writer.num_threads());
+            writer.write_all(chunk).unwrap();
+        }
+        writer.set_max_in_flight(3);
+        writer.set_threads(4);
+        assert_eq!(writer.max_in_flight(), 3);
+        assert_eq!(writer.finish().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_parallel_writer_is_deterministic() {
+        let input = test_input(700_000);
+        let level = CompressionLevel::new(6).unwrap();
+        for blocksize in [BGZF_BLOCK_SIZE, 10_000, 999] {
+            let mut writer = Writer::with_capacity(vec![], level, blocksize);
+            writer.write_all(&input).unwrap();
+            let (expected, _) = writer.finish().unwrap();
+
+            for (threads, in_flight) in [(1, 1), (2, 3), (3, 1), (8, 16)] {
+                let pool = ThreadPool::new(threads);
+                let mut writer = ParallelWriter::with_pool(vec![], level, &pool);
+                writer.set_blocksize(blocksize);
+                writer.set_max_in_flight(in_flight);
+                // Writes of uneven sizes that don't line up with blocks.
+                let mut rest = input.as_slice();
+                let mut size = 1;
+                while !rest.is_empty() {
+                    let (chunk, remaining) = rest.split_at(std::cmp::min(size, rest.len()));
+                    writer.write_all(chunk).unwrap();
+                    rest = remaining;
+                    size = size * 7 % 100_003;
+                }
+                assert_eq!(writer.finish().unwrap(), expected, "{} threads", threads);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parallel_writer_max_in_flight() {
+        /// A sink that other threads can look into while it is being written.
+        struct SharedSink(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+        impl Write for SharedSink {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let input = test_input(1_000_000);
+        let level = CompressionLevel::new(3).unwrap();
+        let output = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+        let mut writer =
+            ParallelWriter::with_pool(SharedSink(output.clone()), level, &ThreadPool::new(4));
+        writer.set_max_in_flight(2);
+
+        // A write takes no more than a block, so the buffer stays within the memory bound.
+        assert_eq!(writer.write(&input).unwrap(), BGZF_BLOCK_SIZE);
+        let blocks = 10;
+        writer.write_all(&input[BGZF_BLOCK_SIZE..blocks * BGZF_BLOCK_SIZE]).unwrap();
+        // The last block is still buffered, and every block but the two in flight before it has
+        // been written by the time `write` returns.
+        let written = output.lock().unwrap().clone();
+        let checksums = ChecksumManifest::from_reader(io::Cursor::new(&written)).unwrap();
+        assert_eq!(checksums.blocks().len(), blocks - 3);
+
+        writer.write_all(&input[blocks * BGZF_BLOCK_SIZE..]).unwrap();
+        writer.finish().unwrap();
+        let written = output.lock().unwrap().clone();
+        assert_eq!(written, compress_parallel(&input, level).unwrap());
+    }
+
+    #[test]
+    fn test_compression_pipeline() {
+        let input = test_input(1_000_000);
+        let pool = ThreadPool::new(2);
+        let (mut sender, receiver) =
+            compression_pipeline(CompressionLevel::new(3).unwrap(), &pool, 2);
+
+        let consumer = std::thread::spawn(move || {
+            let mut output = vec![];
+            for block in receiver {
+                output.extend(block.unwrap());
+            }
+            Compressor::append_eof(&mut output);
+            output
+        });
+        for chunk in input.chunks(300_000) {
+            sender.send(chunk.to_vec()).unwrap();
+        }
+        sender.send(vec![]).unwrap();
+        drop(sender);
+
+        assert_eq!(decompress_all(&consumer.join().unwrap()).unwrap(), input);
+    }
+}
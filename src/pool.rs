@@ -0,0 +1,163 @@
+//! A thread pool that can be shared by many parallel BGZF streams.
+use std::{
+    env,
+    num::NonZeroUsize,
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, OnceLock},
+    thread,
+};
+
+use crossbeam_channel::{unbounded, Sender};
+
+/// The environment variable that sets the number of threads in the [`ThreadPool::global`] pool,
+/// like the `-@` option of `bgzip` or `-p` of `pigz`.
+pub const THREADS_ENV_VAR: &str = "BGZF_THREADS";
+
+/// The number of threads to use when none is given: [`THREADS_ENV_VAR`] if it is set to a
+/// positive number, or else [`std::thread::available_parallelism`].
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{default_threads, ThreadPool};
+///
+/// assert!(default_threads() >= 1);
+/// assert_eq!(ThreadPool::global().num_threads(), default_threads());
+/// ```
+pub fn default_threads() -> usize {
+    env::var(THREADS_ENV_VAR)
+        .ok()
+        .and_then(|value| parse_threads(&value))
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, NonZeroUsize::get))
+}
+
+/// Parse a number of threads, ignoring anything that isn't a positive number.
+fn parse_threads(value: &str) -> Option<usize> {
+    value.trim().parse().ok().filter(|&threads| threads > 0)
+}
+
+/// A unit of work run on a [`ThreadPool`].
+type Job = Box<dyn FnOnce() + Send>;
+
+/// A fixed size pool of worker threads used for parallel compression and decompression.
+///
+/// A [`ThreadPool`] is a cheap handle: clones share the same workers, so any number of streams can
+/// be served by one set of threads. The workers exit once every handle has been dropped and all
+/// queued work has completed.
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::ThreadPool;
+///
+/// let pool = ThreadPool::new(4);
+/// let shared = pool.clone();
+/// assert_eq!(shared.num_threads(), 4);
+/// assert!(ThreadPool::global().num_threads() >= 1);
+/// ```
+#[derive(Clone)]
+pub struct ThreadPool {
+    inner: Arc<PoolInner>,
+}
+
+struct PoolInner {
+    sender: Sender<Job>,
+    num_threads: usize,
+}
+
+impl ThreadPool {
+    /// Create a new pool with `num_threads` workers.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `num_threads` is zero or a worker thread cannot be spawned.
+    pub fn new(num_threads: usize) -> Self {
+        assert!(num_threads > 0, "A thread pool needs at least one thread");
+        let (sender, receiver) = unbounded::<Job>();
+        for i in 0..num_threads {
+            let receiver = receiver.clone();
+            thread::Builder::new()
+                .name(format!("bgzf-worker-{}", i))
+                .spawn(move || {
+                    for job in receiver {
+                        // A panicking job drops whatever it would have sent back, which the caller
+                        // sees as a disconnected channel. Keep the worker alive for other jobs.
+                        let _ = panic::catch_unwind(AssertUnwindSafe(job));
+                    }
+                })
+                .expect("Failed to spawn bgzf worker thread");
+        }
+        Self { inner: Arc::new(PoolInner { sender, num_threads }) }
+    }
+
+    /// The process wide default pool, sized by [`default_threads`].
+    ///
+    /// The pool is created on first use, so [`THREADS_ENV_VAR`] must be set before then.
+    pub fn global() -> &'static ThreadPool {
+        static GLOBAL: OnceLock<ThreadPool> = OnceLock::new();
+        GLOBAL.get_or_init(|| ThreadPool::new(default_threads()))
+    }
+
+    /// The number of worker threads in the pool.
+    pub fn num_threads(&self) -> usize {
+        self.inner.num_threads
+    }
+
+    /// Queue `job` to run on one of the pool's workers.
+    ///
+    /// This lets applications run their own work on the same threads as their BGZF streams.
+    pub fn spawn<F>(&self, job: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        // The workers hold the receiver for as long as the pool exists, so this can't fail.
+        self.inner.sender.send(Box::new(job)).expect("bgzf worker threads have exited");
+    }
+}
+
+impl Default for ThreadPool {
+    /// A handle to the [`ThreadPool::global`] pool.
+    fn default() -> Self {
+        Self::global().clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crossbeam_channel::bounded;
+
+    use super::*;
+
+    #[test]
+    fn test_pool_runs_jobs_from_many_handles() {
+        let pool = ThreadPool::new(3);
+        let (sender, receiver) = bounded(100);
+        for i in 0..100 {
+            let handle = pool.clone();
+            let sender = sender.clone();
+            handle.spawn(move || sender.send(i).unwrap());
+        }
+        drop(sender);
+        let mut results: Vec<i32> = receiver.iter().collect();
+        results.sort_unstable();
+        assert_eq!(results, (0..100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_parse_threads() {
+        assert_eq!(parse_threads("8"), Some(8));
+        assert_eq!(parse_threads(" 2\n"), Some(2));
+        assert_eq!(parse_threads("0"), None);
+        assert_eq!(parse_threads("-1"), None);
+        assert_eq!(parse_threads("all"), None);
+    }
+
+    #[test]
+    fn test_pool_survives_panicking_job() {
+        let pool = ThreadPool::new(1);
+        let (sender, receiver) = bounded(1);
+        pool.spawn(|| panic!("boom"));
+        pool.spawn(move || sender.send(42).unwrap());
+        assert_eq!(receiver.recv().unwrap(), 42);
+    }
+}
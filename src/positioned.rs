@@ -0,0 +1,183 @@
+//! Concurrent random access to BGZF data through positioned reads.
+use std::{fs::File, io, ops::Range, path::Path};
+
+use positioned_io::ReadAt;
+
+use crate::{
+    core::block_size, decompress_block, BlockStarts, DecompressorPool, Index, BGZF_HEADER_SIZE,
+};
+
+/// An immutable, index-backed BGZF reader whose reads take an uncompressed offset.
+///
+/// Reads go through `&self` using positioned reads of the source (`pread` for a
+/// [`std::fs::File`]), so there is no seek cursor and one reader can be shared by many threads,
+/// for example in an `Arc` by the handlers of a web service answering region queries. Calls take
+/// decompressors from a shared pool rather than creating their own. It implements
+/// [`positioned_io::ReadAt`] over the uncompressed data.
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{Index, PositionedReader, TeeWriter};
+/// use std::io::Write;
+///
+/// let (mut data, mut gzi) = (vec![], vec![]);
+/// let mut writer = TeeWriter::new(&mut data, &mut gzi, 2.try_into().unwrap());
+/// writer.write_all(&b"0123456789".repeat(10_000)).unwrap();
+/// writer.finish().unwrap();
+///
+/// let reader = PositionedReader::new(data, Index::read_gzi(gzi.as_slice()).unwrap());
+/// let mut buf = [0; 6];
+/// reader.read_exact_at(65_278, &mut buf).unwrap();
+/// assert_eq!(&buf, b"890123");
+/// ```
+pub struct PositionedReader<R> {
+    source: R,
+    /// Where each block starts
+    starts: BlockStarts,
+    /// Decompressors shared by concurrent calls
+    decompressors: DecompressorPool,
+}
+
+impl<R> PositionedReader<R>
+where
+    R: ReadAt,
+{
+    /// Create a [`PositionedReader`] over `source` using the `index` of its blocks.
+    pub fn new(source: R, index: Index) -> Self {
+        Self {
+            source,
+            starts: BlockStarts::new(&index),
+            decompressors: DecompressorPool::decompressors(),
+        }
+    }
+
+    /// The total number of uncompressed bytes, according to the index.
+    pub fn uncompressed_len(&self) -> u64 {
+        self.starts.uncompressed_len()
+    }
+
+    /// Fill `buf` with the uncompressed bytes starting at `pos`.
+    ///
+    /// Fails with [`io::ErrorKind::UnexpectedEof`] if there are not enough bytes after `pos`.
+    pub fn read_exact_at(&self, pos: u64, buf: &mut [u8]) -> io::Result<()> {
+        if self.read_at(pos, buf)? < buf.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "failed to fill whole buffer",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Read exactly the uncompressed bytes in `range`.
+    ///
+    /// Fails if the range is reversed or extends past the end of the data.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::{Index, PositionedReader, TeeWriter};
+    /// use std::io::Write;
+    /// use std::sync::Arc;
+    ///
+    /// let (mut data, mut gzi) = (vec![], vec![]);
+    /// let mut writer = TeeWriter::new(&mut data, &mut gzi, 2.try_into().unwrap());
+    /// writer.write_all(&b"0123456789".repeat(10_000)).unwrap();
+    /// writer.finish().unwrap();
+    ///
+    /// let index = Index::read_gzi(gzi.as_slice()).unwrap();
+    /// let reader = Arc::new(PositionedReader::new(data, index));
+    /// let handles: Vec<_> = (0..4)
+    ///     .map(|i| {
+    ///         let reader = Arc::clone(&reader);
+    ///         std::thread::spawn(move || reader.range(i * 20_000..i * 20_000 + 3).unwrap())
+    ///     })
+    ///     .collect();
+    /// for handle in handles {
+    ///     assert_eq!(handle.join().unwrap(), b"012");
+    /// }
+    /// ```
+    pub fn range(&self, range: Range<u64>) -> io::Result<Vec<u8>> {
+        if range.start > range.end || range.end > self.uncompressed_len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Range {}..{} is not within the {} bytes of data",
+                    range.start,
+                    range.end,
+                    self.uncompressed_len()
+                ),
+            ));
+        }
+        let mut buf = vec![0; (range.end - range.start) as usize];
+        self.read_exact_at(range.start, &mut buf)?;
+        Ok(buf)
+    }
+
+    /// Unwrap this [`PositionedReader`], returning the source.
+    pub fn into_inner(self) -> R {
+        self.source
+    }
+}
+
+impl PositionedReader<File> {
+    /// Open the BGZF file at `path` with the index in the `.gzi` file next to it.
+    pub fn from_path<P>(path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let mut gzi_path = path.as_os_str().to_owned();
+        gzi_path.push(".gzi");
+        let index = Index::read_gzi(io::BufReader::new(File::open(gzi_path)?))?;
+        Ok(Self::new(File::open(path)?, index))
+    }
+}
+
+impl<R> ReadAt for PositionedReader<R>
+where
+    R: ReadAt,
+{
+    /// Read the uncompressed bytes at `pos` into `buf`, filling it unless the data ends first.
+    fn read_at(&self, pos: u64, buf: &mut [u8]) -> io::Result<usize> {
+        let mut decompressor = self.decompressors.get();
+        let mut compressed = vec![0; BGZF_HEADER_SIZE];
+        let mut copied = 0;
+        while copied < buf.len() && pos + (copied as u64) < self.uncompressed_len() {
+            let current = pos + copied as u64;
+            let start = self.starts.get(self.starts.containing(current));
+
+            // Move past empty blocks to the block holding the data, as in `IndexedReader`.
+            let mut position = start.compressed_offset;
+            let block = loop {
+                compressed.resize(BGZF_HEADER_SIZE, 0);
+                self.source.read_exact_at(position, &mut compressed)?;
+                let size = block_size(&compressed)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                compressed.resize(size, 0);
+                self.source.read_exact_at(
+                    position + BGZF_HEADER_SIZE as u64,
+                    &mut compressed[BGZF_HEADER_SIZE..],
+                )?;
+                let block = decompress_block(&mut decompressor, &compressed)?;
+                if !block.is_empty() {
+                    break block;
+                }
+                position += size as u64;
+            };
+
+            let offset = (current - start.uncompressed_offset) as usize;
+            if offset >= block.len() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Block is smaller than the index says",
+                ));
+            }
+            let amount = std::cmp::min(buf.len() - copied, block.len() - offset);
+            buf[copied..copied + amount].copy_from_slice(&block[offset..offset + amount]);
+            copied += amount;
+        }
+        Ok(copied)
+    }
+}
@@ -0,0 +1,96 @@
+//! Progress reporting for long running reads and writes.
+
+/// A sink for progress updates from a [`crate::Reader`] or [`crate::Writer`].
+///
+/// Updates are delivered once per block. Any `FnMut(u64, Option<u64>)` closure implements this
+/// trait.
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::Writer;
+/// use std::io::Write;
+///
+/// let mut writer = Writer::new(vec![], 2.try_into().unwrap());
+/// writer.set_progress(
+///     |processed: u64, total: Option<u64>| eprintln!("{} of {:?} bytes", processed, total),
+///     Some(11),
+/// );
+/// writer.write_all(b"hello world").unwrap();
+/// writer.flush().unwrap();
+/// ```
+pub trait Progress: Send {
+    /// Report that `processed` bytes out of `total`, if known, have been processed so far.
+    fn update(&mut self, processed: u64, total: Option<u64>);
+}
+
+impl<F> Progress for F
+where
+    F: FnMut(u64, Option<u64>) + Send,
+{
+    fn update(&mut self, processed: u64, total: Option<u64>) {
+        self(processed, total);
+    }
+}
+
+/// A registered [`Progress`] along with the total number of bytes expected, if known.
+pub(crate) struct ProgressTracker {
+    progress: Box<dyn Progress>,
+    total: Option<u64>,
+}
+
+impl ProgressTracker {
+    pub(crate) fn new<P>(progress: P, total: Option<u64>) -> Self
+    where
+        P: Progress + 'static,
+    {
+        Self { progress: Box::new(progress), total }
+    }
+
+    #[inline]
+    pub(crate) fn update(&mut self, processed: u64) {
+        self.progress.update(processed, self.total);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Read, Write};
+
+    use tempfile::tempdir;
+
+    use crate::{test::test_input, CompressionLevel, Reader, Writer};
+
+    #[test]
+    fn test_progress() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("output.txt.gz");
+        let input = test_input(200_000);
+
+        let written = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+        let mut writer = Writer::from_path(&path, CompressionLevel::new(3).unwrap()).unwrap();
+        let sink = std::sync::Arc::clone(&written);
+        writer.set_progress(
+            move |processed, total| sink.lock().unwrap().push((processed, total)),
+            Some(input.len() as u64),
+        );
+        writer.write_all(&input).unwrap();
+        drop(writer);
+        assert_eq!(
+            written.lock().unwrap().last(),
+            Some(&(input.len() as u64, Some(input.len() as u64)))
+        );
+
+        let read = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+        let sink = std::sync::Arc::clone(&read);
+        let mut reader = Reader::from_path_with_progress(&path, move |processed, total| {
+            sink.lock().unwrap().push((processed, total));
+        })
+        .unwrap();
+        reader.read_to_end(&mut vec![]).unwrap();
+        let file_len = std::fs::metadata(&path).unwrap().len();
+        let read = read.lock().unwrap();
+        assert!(read.windows(2).all(|w| w[0].0 < w[1].0));
+        assert_eq!(read.last(), Some(&(file_len, Some(file_len))));
+    }
+}
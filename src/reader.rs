@@ -1,19 +1,29 @@
 //! A Reader for BGZF compressed data.
 use std::{
     fs::File,
-    io::{self, Read},
+    io::{self, BufRead, Read, Seek, SeekFrom, Write},
     path::Path,
+    thread,
 };
 
 use bytes::{Buf, BytesMut};
+use crossbeam_channel::{bounded, unbounded, Receiver, Sender};
 
 use crate::{
-    check_header, get_block_size, get_footer_values, strip_footer, Decompressor, BGZF_BLOCK_SIZE,
-    BGZF_HEADER_SIZE, BUFSIZE,
+    core::{block_size, into_io_error, Framer},
+    crc32_combine, strip_footer, BgzfError, BlockCallback, BlockFooter, BlockHeader, BlockInfo,
+    Decompressor, IndexEntry, Progress, ProgressTracker, StreamDigest, VirtualOffset,
+    BGZF_BLOCK_SIZE, BGZF_EOF, BGZF_FOOTER_SIZE, BGZF_HEADER_SIZE, BUFSIZE, MAX_BGZF_BLOCK_SIZE,
 };
 
+/// The number of uncompressed bytes between the blocks a [`Reader`] remembers for seeking back.
+const CHECKPOINT_INTERVAL: u64 = 1024 * 1024;
+
 /// A BGZF reader.
 ///
+/// Empty blocks, which htslib writes as flush markers, may appear anywhere in the stream and are
+/// skipped, so the end of the data is only reported once the inner reader is exhausted.
+///
 /// # Example
 ///
 /// ```rust
@@ -35,15 +45,32 @@ use crate::{
 ///     Ok(())
 /// }
 /// ```
-pub struct Reader<R>
+pub struct Reader<R> {
+    /// The block being read, kept in part when the inner reader would block
+    framer: Framer,
+    /// The size of a block read by [`Reader::peek_block`] and not yet decoded
+    peeked: Option<usize>,
+    /// The total number of uncompressed bytes, once found by seeking from the end
+    uncompressed_len: Option<u64>,
+    decoder: BlockDecoder,
+    /// How far a [`PrefetchSource`]'s thread may decompress ahead, for a prefetching reader
+    prefetch: Option<PrefetchCredits>,
+    /// For a clone, its own position in the inner reader, and how to seek there
+    anchor: Option<(u64, SwapPosition<R>)>,
+    reader: R,
+}
+
+/// Seek a reader to a position, returning where it was before.
+type SwapPosition<R> = fn(&mut R, u64) -> io::Result<u64>;
+
+/// Seek `reader` to `position`, returning where it was before.
+fn swap_position<R>(reader: &mut R, position: u64) -> io::Result<u64>
 where
-    R: Read,
+    R: Seek,
 {
-    decompressed_buffer: BytesMut,
-    compressed_buffer: BytesMut,
-    header_buffer: Vec<u8>,
-    decompressor: Decompressor,
-    reader: R,
+    let previous = reader.stream_position()?;
+    reader.seek(SeekFrom::Start(position))?;
+    Ok(previous)
 }
 
 impl<R> Reader<R>
@@ -51,55 +78,697 @@ where
     R: Read,
 {
     pub fn new(reader: R) -> Self {
-        let decompressor = Decompressor::new();
+        Self::with_source(reader)
+    }
 
-        Self {
-            decompressed_buffer: BytesMut::with_capacity(BUFSIZE),
-            compressed_buffer: BytesMut::with_capacity(BGZF_BLOCK_SIZE),
-            header_buffer: vec![0; BGZF_HEADER_SIZE],
-            decompressor,
-            reader,
+    /// Skip forward `amount` uncompressed bytes by decompressing and discarding them.
+    ///
+    /// Returns the number of bytes skipped, which is less than `amount` only if the end of the
+    /// data was reached.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::{Reader, Writer};
+    /// use std::io::{Read, Write};
+    ///
+    /// let mut compressed = vec![];
+    /// let mut writer = Writer::new(&mut compressed, 2.try_into().unwrap());
+    /// writer.write_all(b"hello world").unwrap();
+    /// drop(writer);
+    ///
+    /// let mut reader = Reader::new(compressed.as_slice());
+    /// assert_eq!(reader.skip(6).unwrap(), 6);
+    /// let mut rest = String::new();
+    /// reader.read_to_string(&mut rest).unwrap();
+    /// assert_eq!(rest, "world");
+    /// ```
+    pub fn skip(&mut self, amount: u64) -> io::Result<u64> {
+        let mut skipped = 0;
+        while skipped < amount {
+            let buffer = &mut self.decoder.decompressed_buffer;
+            let available = std::cmp::min(buffer.remaining() as u64, amount - skipped);
+            buffer.advance(available as usize);
+            skipped += available;
+            if skipped < amount && !self.read_block()? {
+                break;
+            }
+        }
+        Ok(skipped)
+    }
+
+    /// Scan forward to the next plausible block and resume decoding from there, after an error.
+    ///
+    /// A block is plausible if its header has the gzip magic bytes, the `BC` extra subfield and a
+    /// sane block size, and its data then decompresses with a matching CRC. Any decompressed data
+    /// not yet read is discarded.
+    ///
+    /// Returns the number of compressed bytes skipped, including any of the block that failed. If
+    /// no block is found, the rest of the input is skipped and further reads return nothing.
+    pub fn resync(&mut self) -> io::Result<u64> {
+        self.anchored(Self::resync_anchored)
+    }
+
+    /// [`Reader::resync`], with the inner reader at this reader's own position.
+    fn resync_anchored(&mut self) -> io::Result<u64> {
+        // Scanning holds up to a block's worth of candidates on top of the block being checked.
+        self.decoder.check_memory(BGZF_BLOCK_SIZE + MAX_BGZF_BLOCK_SIZE)?;
+        self.decoder.decompressed_buffer.clear();
+        // The failed block, or the part of one read before the error, was consumed but not counted.
+        let mut skipped = (self.framer.partial() + self.peeked.unwrap_or(0)) as u64;
+        skipped += std::mem::take(&mut self.decoder.failed_block) as u64;
+        self.framer.clear();
+        self.peeked = None;
+        let mut scanned = Vec::with_capacity(BGZF_BLOCK_SIZE);
+        let mut start = 0;
+        while fill_to(&mut self.reader, &mut scanned, start + BGZF_HEADER_SIZE)? {
+            let candidate = &scanned[start..];
+            if is_plausible_header(candidate) {
+                let size = block_size(candidate).expect("checked by is_plausible_header");
+                if fill_to(&mut self.reader, &mut scanned, start + size)? {
+                    let block = &scanned[start + BGZF_HEADER_SIZE..start + size];
+                    if let Ok((decompressed, footer)) =
+                        decompress_into_new(&mut self.decoder.decompressor, block)
+                    {
+                        skipped += start as u64;
+                        self.decoder.compressed_offset += skipped;
+                        self.decoder.empty_block = None;
+                        self.decoder.accept(size, decompressed, footer)?;
+                        return Ok(skipped);
+                    }
+                }
+            }
+            start += 1;
+            if start >= BGZF_BLOCK_SIZE {
+                scanned.drain(..start);
+                skipped += start as u64;
+                start = 0;
+            }
+        }
+        skipped += scanned.len() as u64;
+        self.decoder.compressed_offset += skipped;
+        Ok(skipped)
+    }
+
+    /// Read the next block without decompressing it and describe it, or return `None` at the end.
+    ///
+    /// The sizes come from the block's header and footer, so this is much cheaper than decoding.
+    /// The block stays pending, and its data is returned by reads once the decompressed bytes
+    /// already buffered have been read. Peeking again returns the same block.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::{Reader, Writer};
+    /// use std::io::{Read, Write};
+    ///
+    /// let mut compressed = vec![];
+    /// let mut writer = Writer::new(&mut compressed, 2.try_into().unwrap());
+    /// writer.write_all(b"hello world").unwrap();
+    /// drop(writer);
+    ///
+    /// let mut reader = Reader::new(compressed.as_slice());
+    /// let block = reader.peek_block().unwrap().unwrap();
+    /// assert_eq!(block.compressed_offset, 0);
+    /// assert_eq!(block.uncompressed_size, 11);
+    ///
+    /// let mut data = String::new();
+    /// reader.read_to_string(&mut data).unwrap();
+    /// assert_eq!(data, "hello world");
+    /// assert!(reader.peek_block().unwrap().is_none());
+    /// ```
+    pub fn peek_block(&mut self) -> io::Result<Option<BlockInfo>> {
+        if self.peeked.is_none() {
+            self.peeked = self.next_raw_block()?;
+        }
+        match self.peeked {
+            Some(size) => {
+                let footer = BlockFooter::from_block(self.framer.body())
+                    .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+                Ok(Some(BlockInfo {
+                    compressed_offset: self.decoder.compressed_offset,
+                    compressed_size: size,
+                    uncompressed_offset: self.decoder.uncompressed_offset,
+                    uncompressed_size: footer.uncompressed_size as usize,
+                }))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Iterate over the remaining data a block at a time, with the virtual offset each starts at.
+    ///
+    /// Each item holds the whole of one block's data, which suits handing blocks to other threads
+    /// better than reading with exactly sized buffers. Empty blocks are skipped. If part of a block
+    /// has already been read, the rest of it comes first. The iteration ends after an error.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::{Reader, VirtualOffset, Writer};
+    /// use std::io::Write;
+    ///
+    /// let mut writer = Writer::new(vec![], 2.try_into().unwrap());
+    /// writer.write_all(b"hello").unwrap();
+    /// writer.flush().unwrap();
+    /// let world = writer.virtual_position();
+    /// writer.write_all(b" world").unwrap();
+    /// let (compressed, _) = writer.finish().unwrap();
+    ///
+    /// let mut reader = Reader::new(compressed.as_slice());
+    /// let blocks: Vec<_> = reader.blocks_decompressed().collect::<Result<_, _>>().unwrap();
+    /// assert_eq!(
+    ///     blocks,
+    ///     vec![(VirtualOffset::new(0, 0), b"hello".to_vec()), (world, b" world".to_vec())]
+    /// );
+    /// ```
+    pub fn blocks_decompressed(&mut self) -> DecompressedBlocks<'_, R> {
+        DecompressedBlocks { reader: self, failed: false }
+    }
+
+    /// Decompress the rest of the current block, or the next non-empty one, into a new vector.
+    fn next_decompressed_block(&mut self) -> io::Result<Option<(VirtualOffset, Vec<u8>)>> {
+        if !self.decoder.decompressed_buffer.is_empty() {
+            let offset = self.virtual_position();
+            let data = self.decoder.decompressed_buffer.to_vec();
+            self.decoder.decompressed_buffer.clear();
+            return Ok(Some((offset, data)));
+        }
+        while let Some(size) = self.next_raw_block()? {
+            let offset = VirtualOffset::new(self.decoder.compressed_offset, 0);
+            let mut data = vec![];
+            self.decoder.decode_onto(size, self.framer.body(), &mut data)?;
+            if !data.is_empty() {
+                return Ok(Some((offset, data)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Read the next block without decompressing it, returning its size, or `None` at the end.
+    ///
+    /// Follow up with [`Reader::decode_raw_block`] or [`Reader::pass_raw_block`].
+    pub(crate) fn next_raw_block(&mut self) -> io::Result<Option<usize>> {
+        if let Some(size) = self.peeked.take() {
+            return Ok(Some(size));
+        }
+        self.anchored(|this| this.framer.fill_with(|buf| this.reader.read(buf)))
+    }
+
+    /// Read the next block from the inner reader and decompress it into the decompressed buffer.
+    ///
+    /// Returns `false` if no further block is available.
+    fn read_block(&mut self) -> io::Result<bool> {
+        match self.next_raw_block()? {
+            Some(size) => {
+                self.decoder.decode(size, self.framer.body())?;
+                Ok(true)
+            }
+            None => Ok(false),
         }
     }
 }
 
-impl Reader<File> {
-    /// Create a BGZF reader from a [`Path`].
-    pub fn from_path<P>(path: P) -> io::Result<Self>
-    where
-        P: AsRef<Path>,
-    {
-        // TODO: benchmark whether there is any benefit to using a BufReader
-        File::open(path).map(Self::new)
+/// An iterator over the decompressed blocks of a [`Reader`], from
+/// [`Reader::blocks_decompressed`].
+///
+/// The iterator ends after the first error.
+pub struct DecompressedBlocks<'a, R> {
+    reader: &'a mut Reader<R>,
+    failed: bool,
+}
+
+impl<R> Iterator for DecompressedBlocks<'_, R>
+where
+    R: Read,
+{
+    type Item = io::Result<(VirtualOffset, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let block = self.reader.next_decompressed_block().transpose();
+        self.failed = matches!(block, Some(Err(_)));
+        block
     }
 }
 
-impl<R> Read for Reader<R>
+/// Read from `reader` until `buf` holds at least `len` bytes, returning `false` if it ran out.
+pub(crate) fn fill_to<R>(reader: &mut R, buf: &mut Vec<u8>, len: usize) -> io::Result<bool>
 where
     R: Read,
 {
-    /// Attempt to read `buf.len()` bytes from source into `buf`.
+    if buf.len() < len {
+        reader.by_ref().take((len - buf.len()) as u64).read_to_end(buf)?;
+    }
+    Ok(buf.len() >= len)
+}
+
+/// Whether `header` looks like the start of a BGZF block.
+pub(crate) fn is_plausible_header(header: &[u8]) -> bool {
+    BlockHeader::parse(header).is_ok()
+}
+
+impl<R> Clone for Reader<R>
+where
+    R: Read + Seek + Clone,
+{
+    /// Clone this reader into an independent cursor at the same position, by cloning the inner
+    /// reader along with the buffered data.
+    ///
+    /// The clone remembers its own position in the inner reader, and seeks there before each read
+    /// from it and back afterwards. So the inner reader's clone may share its position with the
+    /// original, as a [`File::try_clone`] does, as long as the two readers aren't read at the same
+    /// time from different threads. Block callbacks and progress sinks are not carried over to the
+    /// clone.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the position of the inner reader can't be found.
+    fn clone(&self) -> Self {
+        let mut reader = self.reader.clone();
+        let position = match self.anchor {
+            Some((position, _)) => position,
+            None => reader.stream_position().expect("Failed to find the inner reader's position"),
+        };
+        Self {
+            framer: self.framer.clone(),
+            peeked: self.peeked,
+            uncompressed_len: self.uncompressed_len,
+            decoder: self.decoder.fork(),
+            prefetch: None,
+            anchor: Some((position, swap_position::<R>)),
+            reader,
+        }
+    }
+}
+
+impl<R> Reader<R> {
+    /// Create a reader around `source`, which is not necessarily a [`Read`].
+    fn with_source(source: R) -> Self {
+        Self {
+            framer: Framer::new(),
+            peeked: None,
+            uncompressed_len: None,
+            decoder: BlockDecoder::new(),
+            prefetch: None,
+            anchor: None,
+            reader: source,
+        }
+    }
+
+    /// Call `read` with the inner reader at this reader's own position, if it is a clone, and
+    /// afterwards put the inner reader back where it was.
+    fn anchored<T, F>(&mut self, read: F) -> io::Result<T>
+    where
+        F: FnOnce(&mut Self) -> io::Result<T>,
+    {
+        let Some((position, swap)) = self.anchor else {
+            return read(self);
+        };
+        let resume = swap(&mut self.reader, position)?;
+        let result = read(self);
+        let position = swap(&mut self.reader, resume)?;
+        self.anchor = Some((position, swap));
+        result
+    }
+
+    /// Register a callback to be invoked after each block is decompressed.
+    ///
+    /// The callback receives the [`BlockInfo`] of every block read from the inner reader,
+    /// including empty blocks such as the EOF marker.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::{Reader, Writer};
+    /// use std::io::{Read, Write};
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let mut compressed = vec![];
+    /// let mut writer = Writer::new(&mut compressed, 2.try_into().unwrap());
+    /// writer.write_all(b"hello world").unwrap();
+    /// drop(writer);
+    ///
+    /// let blocks = Arc::new(Mutex::new(vec![]));
+    /// let mut reader = Reader::new(compressed.as_slice());
+    /// let sink = Arc::clone(&blocks);
+    /// reader.set_block_callback(move |block| sink.lock().unwrap().push(block));
+    /// reader.read_to_end(&mut vec![]).unwrap();
+    ///
+    /// let blocks = blocks.lock().unwrap();
+    /// assert_eq!(blocks[0].compressed_offset, 0);
+    /// assert_eq!(blocks[0].uncompressed_size, 11);
+    /// ```
+    pub fn set_block_callback<F>(&mut self, callback: F)
+    where
+        F: FnMut(BlockInfo) + Send + 'static,
+    {
+        self.decoder.block_callback = Some(Box::new(callback));
+    }
+
+    /// Register a [`Progress`] to be updated after each block is decompressed.
+    ///
+    /// Progress is measured in compressed bytes consumed from the inner reader, so `total` should
+    /// be the size of the compressed input, if known.
+    pub fn set_progress<P>(&mut self, progress: P, total: Option<u64>)
+    where
+        P: Progress + 'static,
+    {
+        self.decoder.progress = Some(ProgressTracker::new(progress, total));
+    }
+
+    /// Feed all of the data decompressed from here on to `digest`, for
+    /// [`Reader::finish_digest`] to return once the data is read.
+    ///
+    /// Blocks are digested as they are decompressed, so the digest covers the data in the order
+    /// it was decompressed: seeking or resyncing leaves it digesting something other than the
+    /// stream, and a block decompressed ahead of the data read counts as read. Clones of this
+    /// reader don't digest. See [`StreamDigest`] for the digests available.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::{Reader, StreamDigest, Writer};
+    /// use std::io::{Read, Write};
+    ///
+    /// /// The number of bytes read, a stand-in for a real hash.
+    /// struct Count(u64);
+    ///
+    /// impl StreamDigest for Count {
+    ///     fn update(&mut self, data: &[u8]) {
+    ///         self.0 += data.len() as u64;
+    ///     }
+    ///
+    ///     fn finish(&mut self) -> Vec<u8> {
+    ///         std::mem::take(&mut self.0).to_le_bytes().to_vec()
+    ///     }
+    /// }
+    ///
+    /// let mut writer = Writer::new(vec![], 2.try_into().unwrap());
+    /// writer.write_all(b"hello world").unwrap();
+    /// let (compressed, _) = writer.finish().unwrap();
+    ///
+    /// let mut reader = Reader::new(compressed.as_slice());
+    /// reader.set_digest(Count(0));
+    /// reader.read_to_end(&mut vec![]).unwrap();
+    /// assert_eq!(reader.finish_digest().unwrap(), 11_u64.to_le_bytes());
+    /// ```
+    pub fn set_digest<D>(&mut self, digest: D)
+    where
+        D: StreamDigest + 'static,
+    {
+        self.decoder.digest = Some(Box::new(digest));
+    }
+
+    /// The digest of all of the data decompressed since [`Reader::set_digest`], or since the last
+    /// call to this, if a digest is set.
+    ///
+    /// Call this once the reader reaches the end of the data for the digest of the whole stream.
+    pub fn finish_digest(&mut self) -> Option<Vec<u8>> {
+        self.decoder.digest.as_mut().map(|digest| digest.finish())
+    }
+
+    /// Combine the CRC32 in each block's footer into the CRC32 of the whole uncompressed stream,
+    /// returned by [`Reader::stream_crc32`].
+    ///
+    /// The footers are checked against the data as each block is decompressed, so this verifies a
+    /// whole file against a checksum recorded elsewhere, such as the one a [`Writer`](crate::Writer) reports,
+    /// without a second pass over the data. Seeking back turns this off, since it would count
+    /// blocks twice.
+    ///
+    /// # Panics
+    ///
+    /// Panics if anything has already been read.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::{Reader, Writer};
+    /// use std::io::{Read, Write};
+    ///
+    /// let mut writer = Writer::new(vec![], 2.try_into().unwrap());
+    /// writer.set_stream_checksum(true);
+    /// writer.write_all(&b"ACGT".repeat(100_000)).unwrap();
+    /// let (compressed, summary) = writer.finish().unwrap();
+    ///
+    /// let mut reader = Reader::new(compressed.as_slice());
+    /// reader.set_stream_checksum(true);
+    /// std::io::copy(&mut reader, &mut std::io::sink()).unwrap();
+    /// assert_eq!(reader.stream_crc32(), summary.crc32);
+    /// ```
+    pub fn set_stream_checksum(&mut self, enabled: bool) {
+        assert!(
+            self.decoder.compressed_offset == 0,
+            "The stream checksum must be set before reading"
+        );
+        self.decoder.stream_crc = enabled.then_some(0);
+    }
+
+    /// The CRC32 of the data in every block decoded so far, or `None` if it isn't enabled with
+    /// [`Reader::set_stream_checksum`].
+    ///
+    /// Once the reader reaches the end this is the CRC32 of the whole stream. Blocks decoded but
+    /// not yet read in full are included.
+    pub fn stream_crc32(&self) -> Option<u32> {
+        self.decoder.stream_crc
+    }
+
+    /// Whether a digest needs every block decompressed.
+    pub(crate) fn has_digest(&self) -> bool {
+        self.decoder.digest.is_some()
+    }
+
+    /// The header and the rest of the block of `size` bytes read by [`Reader::next_raw_block`].
+    pub(crate) fn raw_block(&self, size: usize) -> (&[u8], &[u8]) {
+        self.framer.block()[..size].split_at(BGZF_HEADER_SIZE)
+    }
+
+    /// Decompress the block read by [`Reader::next_raw_block`], as if it had been read normally.
+    pub(crate) fn decode_raw_block(&mut self, size: usize) -> io::Result<()> {
+        self.decoder.decode(size, self.framer.body())
+    }
+
+    /// Account for the block read by [`Reader::next_raw_block`] without decompressing it.
+    pub(crate) fn pass_raw_block(&mut self, size: usize) -> io::Result<()> {
+        self.decoder.check_order()?;
+        let footer = BlockFooter::from_block(self.framer.body())
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        self.decoder.decompressed_buffer.clear();
+        self.decoder.record(size, footer);
+        Ok(())
+    }
+
+    /// Write the decompressed bytes not yet read to `writer`, returning how many there were.
+    pub(crate) fn drain_into<W>(&mut self, writer: &mut W) -> io::Result<u64>
+    where
+        W: Write,
+    {
+        let buffer = &mut self.decoder.decompressed_buffer;
+        writer.write_all(buffer)?;
+        let drained = buffer.len() as u64;
+        buffer.clear();
+        Ok(drained)
+    }
+
+    /// Fail with [`BgzfError::InteriorEof`] on an empty block, such as an EOF marker, that is
+    /// followed by more blocks.
+    ///
+    /// By default empty blocks anywhere in the stream are skipped, which is what concatenating
+    /// BGZF files without removing their EOF markers needs. Strict mode flags such files instead.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::{Reader, Writer};
+    /// use std::io::{Read, Write};
+    ///
+    /// let mut concatenated = vec![];
+    /// for text in [&b"hello "[..], &b"world"[..]] {
+    ///     let mut writer = Writer::new(vec![], 2.try_into().unwrap());
+    ///     writer.write_all(text).unwrap();
+    ///     concatenated.extend(writer.finish().unwrap().0);
+    /// }
+    ///
+    /// let mut reader = Reader::new(concatenated.as_slice());
+    /// reader.set_strict_eof(true);
+    /// assert!(reader.read_to_end(&mut vec![]).is_err());
+    /// ```
+    pub fn set_strict_eof(&mut self, strict: bool) {
+        self.decoder.strict_eof = strict;
+    }
+
+    /// Cap the memory this reader may allocate for its buffers at `limit` bytes.
+    ///
+    /// Blocks are at most 64 KiB both compressed and uncompressed, so a reader's buffers are
+    /// bounded up front; for a prefetching reader that bound includes every block it may queue,
+    /// and the number of blocks decompressed ahead is lowered to fit the limit, down to one, or
+    /// raised back towards the number given to [`Reader::with_prefetch`]. Blocks already queued
+    /// are still read, so a lower depth takes effect as they are.
+    ///
+    /// Fails with [`io::ErrorKind::OutOfMemory`] if the bound is still over the limit.
+    /// Afterwards [`Reader::resync`] fails the same way if its scan would go over the limit, and
+    /// the blocks remembered for seeking backward stop being recorded once they would, so seeks
+    /// back restart from further away.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::Reader;
+    /// use std::io::ErrorKind;
+    ///
+    /// let mut reader = Reader::with_prefetch(std::io::empty(), 64);
+    /// reader.set_memory_limit(1024 * 1024).unwrap();
+    /// assert!(reader.prefetch_depth() < 64);
+    /// assert!(reader.max_memory() <= 1024 * 1024);
+    ///
+    /// let err = reader.set_memory_limit(128 * 1024).unwrap_err();
+    /// assert_eq!(err.kind(), ErrorKind::OutOfMemory);
+    /// ```
+    pub fn set_memory_limit(&mut self, limit: usize) -> io::Result<()> {
+        self.decoder.memory_limit = Some(limit);
+        if let Some(prefetch) = self.prefetch.as_mut() {
+            self.decoder.reserved_memory -= prefetch.memory();
+            let room = limit.saturating_sub(self.decoder.max_memory()) / MAX_BGZF_BLOCK_SIZE;
+            prefetch.set_depth(room.saturating_sub(2).clamp(1, prefetch.max_depth));
+            self.decoder.reserved_memory += prefetch.memory();
+        }
+        self.decoder.check_memory(0)
+    }
+
+    /// The most memory this reader's buffers may take up, in bytes.
+    pub fn max_memory(&self) -> usize {
+        self.decoder.max_memory()
+    }
+
+    /// The number of compressed bytes of the blocks decompressed so far, which is the offset of
+    /// the next block in the compressed stream.
+    ///
+    /// This is what to compare with the size of the file for progress, or to record block starts
+    /// for an index. A block read by [`Reader::peek_block`] but not yet decompressed isn't
+    /// counted, nor are blocks decompressed ahead by [`Reader::with_prefetch`] until they are
+    /// read.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::{Reader, Writer};
+    /// use std::io::{Read, Write};
+    ///
+    /// let mut writer = Writer::new(vec![], 2.try_into().unwrap());
+    /// writer.write_all(b"hello").unwrap();
+    /// writer.flush().unwrap();
+    /// writer.write_all(b" world").unwrap();
+    /// let (compressed, _) = writer.finish().unwrap();
+    ///
+    /// let mut reader = Reader::new(compressed.as_slice());
+    /// assert_eq!(reader.compressed_position(), 0);
+    /// let mut hello = [0; 5];
+    /// reader.read_exact(&mut hello).unwrap();
+    /// let first_block = reader.compressed_position();
+    /// assert!(first_block > 0);
+    ///
+    /// reader.read_to_end(&mut vec![]).unwrap();
+    /// assert_eq!(reader.compressed_position(), compressed.len() as u64);
+    /// ```
+    pub fn compressed_position(&self) -> u64 {
+        self.decoder.compressed_offset
+    }
+
+    /// The [`VirtualOffset`] of the next byte to be read, as used by BAI, CSI, and tabix indexes.
+    ///
+    /// Once a block has been read to its end this is the start of the next block. Compressed
+    /// offsets count from where this reader started, as for [`Reader::compressed_position`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::{Reader, VirtualOffset, Writer};
+    /// use std::io::{Read, Write};
+    ///
+    /// let mut writer = Writer::new(vec![], 2.try_into().unwrap());
+    /// writer.write_all(b"hello").unwrap();
+    /// writer.flush_block().unwrap();
+    /// let second_block = writer.virtual_position();
+    /// writer.write_all(b" world").unwrap();
+    /// let (compressed, _) = writer.finish().unwrap();
+    ///
+    /// let mut reader = Reader::new(compressed.as_slice());
+    /// let mut hello = [0; 5];
+    /// reader.read_exact(&mut hello).unwrap();
+    /// assert_eq!(reader.virtual_position(), second_block);
+    /// reader.read_exact(&mut [0; 3]).unwrap();
+    /// assert_eq!(reader.virtual_position(), second_block.checked_add(3).unwrap());
+    /// ```
+    pub fn virtual_position(&self) -> VirtualOffset {
+        if self.decoder.decompressed_buffer.is_empty() {
+            VirtualOffset::new(self.decoder.compressed_offset, 0)
+        } else {
+            let block = self.decoder.last_block;
+            let within = self.position() - block.uncompressed_offset;
+            VirtualOffset::new(block.compressed_offset, within as u16)
+        }
+    }
+
+    /// A reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// A mutable reference to the inner reader.
+    ///
+    /// Reading from or seeking it directly, rather than through this reader, loses track of
+    /// where the next block starts.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Unwrap the inner reader, discarding any data decompressed but not yet read.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Start over as a new reader at the inner reader's current position, after it was moved.
+    pub(crate) fn restart(&mut self) {
+        self.decoder.restart_at(IndexEntry { compressed_offset: 0, uncompressed_offset: 0 });
+        self.decoder.checkpoints.truncate(1);
+        self.framer.clear();
+        self.peeked = None;
+        self.uncompressed_len = None;
+    }
+
+    /// The uncompressed offset of the next byte to be read.
+    fn position(&self) -> u64 {
+        self.decoder.uncompressed_offset - self.decoder.decompressed_buffer.remaining() as u64
+    }
+
+    /// Copy decompressed bytes into `buf`, calling `read_block` whenever more are needed.
     ///
     /// - `Ok(0)` means that EOF has been reached or `buf.len() == 0`.
     /// - `Ok(n < buf.len()` means that EOF has been reached.
     /// - `Err(..)` means that an error has ocurred
     #[inline]
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    fn read_with<F>(&mut self, buf: &mut [u8], mut read_block: F) -> io::Result<usize>
+    where
+        F: FnMut(&mut Self) -> io::Result<bool>,
+    {
         let mut total_bytes_copied = 0;
         loop {
-            let available_bytes = self.decompressed_buffer.remaining();
+            let decompressed_buffer = &mut self.decoder.decompressed_buffer;
+            let available_bytes = decompressed_buffer.remaining();
             let remaining_bytes_needed = buf.len() - total_bytes_copied;
             // There are bytes we've already decompressed but haven't copied to the output buffer yet
             if available_bytes > remaining_bytes_needed {
                 // The total decompressed is greater than the output buffer
-                self.decompressed_buffer.copy_to_slice(&mut buf[total_bytes_copied..]);
-            } else if !self.decompressed_buffer.is_empty() {
+                decompressed_buffer.copy_to_slice(&mut buf[total_bytes_copied..]);
+            } else if !decompressed_buffer.is_empty() {
                 // The total decompressed is less than the output buffer
-                self.decompressed_buffer.copy_to_slice(
+                decompressed_buffer.copy_to_slice(
                     &mut buf[total_bytes_copied..total_bytes_copied + available_bytes],
                 );
             }
-            total_bytes_copied += available_bytes - self.decompressed_buffer.remaining();
+            total_bytes_copied += available_bytes - decompressed_buffer.remaining();
 
             // Check if we've filled the output buffer. If it hasn't been filled then decompress another block.
             if total_bytes_copied == buf.len() {
@@ -113,33 +782,1577 @@ where
             );
 
             // The output buffer hasn't been filled, try to decompress another block. If another
-            // block is not available then we are done.
-            self.header_buffer.fill(0);
-            if let Ok(()) = self.reader.read_exact(&mut self.header_buffer) {
-                check_header(&self.header_buffer)
-                    .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-                let size = get_block_size(&self.header_buffer);
-
-                self.compressed_buffer.clear();
-                self.compressed_buffer.resize(size - BGZF_HEADER_SIZE, 0);
-                self.reader.read_exact(&mut self.compressed_buffer)?;
-
-                let check = get_footer_values(&self.compressed_buffer);
-                self.decompressed_buffer.clear();
-                self.decompressed_buffer.resize(check.amount as usize, 0);
-
-                self.decompressor
-                    .decompress(
-                        strip_footer(&self.compressed_buffer),
-                        &mut self.decompressed_buffer,
-                        check,
-                    )
-                    .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-            } else {
-                break;
+            // block is not available then we are done. If the inner reader would block, hand back
+            // what we have and let the next call pick up the block where it left off.
+            match read_block(self) {
+                Ok(true) => (),
+                Ok(false) => break,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock && total_bytes_copied > 0 => break,
+                Err(e) => return Err(e),
             }
         }
 
         Ok(total_bytes_copied)
     }
 }
+
+impl Reader<File> {
+    /// Create a BGZF reader from a [`Path`].
+    pub fn from_path<P>(path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        // TODO: benchmark whether there is any benefit to using a BufReader
+        File::open(path).map(Self::new)
+    }
+
+    /// Create a BGZF reader from a [`Path`], first checking that the file starts with a BGZF block
+    /// header and ends with an EOF marker block.
+    ///
+    /// This fails fast on files that aren't BGZF or were truncated, rather than partway through
+    /// reading them. A missing EOF marker is an [`io::ErrorKind::UnexpectedEof`] error and a bad
+    /// first header is an [`io::ErrorKind::InvalidData`] error. The file must be seekable.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::{Reader, Writer, BGZF_EOF};
+    /// use std::io::{ErrorKind, Write};
+    ///
+    /// let dir = tempfile::tempdir().unwrap();
+    /// let path = dir.path().join("test.gz");
+    /// let mut writer = Writer::new(std::fs::File::create(&path).unwrap(), 2.try_into().unwrap());
+    /// writer.write_all(b"hello world").unwrap();
+    /// writer.finish().unwrap();
+    /// assert!(Reader::from_path_checked(&path).is_ok());
+    ///
+    /// let len = std::fs::metadata(&path).unwrap().len();
+    /// std::fs::File::options().write(true).open(&path).unwrap().set_len(len - 1).unwrap();
+    /// let err = Reader::from_path_checked(&path).err().unwrap();
+    /// assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+    /// ```
+    pub fn from_path_checked<P>(path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let mut file = File::open(path)?;
+        BlockHeader::read_from(&mut file)?;
+        let missing_eof =
+            || io::Error::new(io::ErrorKind::UnexpectedEof, "Missing BGZF EOF marker");
+        let eof_size = BGZF_EOF.len();
+        if file.metadata()?.len() < eof_size as u64 {
+            return Err(missing_eof());
+        }
+        let mut tail = vec![0; eof_size];
+        file.seek(SeekFrom::End(-(eof_size as i64)))?;
+        file.read_exact(&mut tail)?;
+        let is_eof_block = BlockHeader::parse(&tail).is_ok_and(|h| h.block_size() == eof_size)
+            && BlockFooter::from_block(&tail).is_ok_and(|f| f.uncompressed_size == 0);
+        if !is_eof_block {
+            return Err(missing_eof());
+        }
+        file.seek(SeekFrom::Start(0))?;
+        Ok(Self::new(file))
+    }
+
+    /// Create a BGZF reader from a [`Path`] that reports its progress through the file.
+    ///
+    /// The total passed to `progress` is the size of the file.
+    pub fn from_path_with_progress<P, G>(path: P, progress: G) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+        G: Progress + 'static,
+    {
+        let file = File::open(path)?;
+        let total = file.metadata()?.len();
+        let mut reader = Self::new(file);
+        reader.set_progress(progress, Some(total));
+        Ok(reader)
+    }
+}
+
+impl<R> Read for Reader<R>
+where
+    R: Read,
+{
+    /// Attempt to read `buf.len()` bytes from source into `buf`.
+    ///
+    /// - `Ok(0)` means that EOF has been reached or `buf.len() == 0`.
+    /// - `Ok(n < buf.len()` means that EOF has been reached.
+    /// - `Err(..)` means that an error has ocurred
+    ///
+    /// If nothing is buffered and `buf` has room for whole blocks, they are decompressed straight
+    /// into it rather than through the internal buffer. A block that fails after others have been
+    /// decompressed is left for the next call to report, so that their data is returned first.
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.decoder.decompressed_buffer.is_empty() || buf.len() < MAX_BGZF_BLOCK_SIZE {
+            return self.read_with(buf, Self::read_block);
+        }
+        let mut total = 0;
+        while buf.len() - total >= MAX_BGZF_BLOCK_SIZE {
+            match self.next_raw_block() {
+                Ok(Some(size)) => {
+                    match self.decoder.decode_into(size, self.framer.body(), &mut buf[total..]) {
+                        Ok(n) => total += n,
+                        Err(_) if total > 0 => {
+                            // Decoding it again on the next call fails the same way.
+                            self.decoder.failed_block = 0;
+                            self.peeked = Some(size);
+                            break;
+                        }
+                        Err(e) => return Err(e),
+                    }
+                }
+                Ok(None) => break,
+                // Reading the block again on the next call picks up where this left off.
+                Err(_) if total > 0 => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(total)
+    }
+
+    /// Read into a possibly uninitialized buffer.
+    ///
+    /// Buffered data is copied into `cursor` without initializing the rest of it. If nothing is
+    /// buffered and `cursor` has room for whole blocks, its unfilled part is zeroed once, which
+    /// [`io::BorrowedBuf`] remembers across reads, and blocks are decompressed straight into it.
+    #[cfg(feature = "read-buf")]
+    fn read_buf(&mut self, mut cursor: io::BorrowedCursor<'_>) -> io::Result<()> {
+        if self.decoder.decompressed_buffer.is_empty() && cursor.capacity() >= MAX_BGZF_BLOCK_SIZE {
+            let n = self.read(cursor.ensure_init())?;
+            cursor.advance_checked(n);
+            return Ok(());
+        }
+        let start = cursor.written();
+        while cursor.capacity() > 0 {
+            if self.decoder.decompressed_buffer.is_empty() {
+                match self.read_block() {
+                    Ok(true) => continue,
+                    Ok(false) => break,
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock && cursor.written() > start => {
+                        break
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            let buffer = &mut self.decoder.decompressed_buffer;
+            let n = buffer.remaining().min(cursor.capacity());
+            cursor.append(&buffer.chunk()[..n]);
+            buffer.advance(n);
+        }
+        Ok(())
+    }
+
+    /// Read all remaining bytes into `buf`, decompressing each block straight onto its end.
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        let start = buf.len();
+        buf.extend_from_slice(&self.decoder.decompressed_buffer);
+        self.decoder.decompressed_buffer.clear();
+        while let Some(size) = self.next_raw_block()? {
+            self.decoder.decode_onto(size, self.framer.body(), buf)?;
+        }
+        Ok(buf.len() - start)
+    }
+}
+
+impl<R> Seek for Reader<R>
+where
+    R: Read + Seek,
+{
+    /// Seek to an uncompressed offset without an index.
+    ///
+    /// Seeking restarts from the nearest block remembered while reading, at most about a megabyte
+    /// of uncompressed data before the target, if that is closer than the current position, and
+    /// decompresses and discards the data in between. A seek past the end stops at the end, and
+    /// returns that position.
+    ///
+    /// The first [`SeekFrom::End`] finds the total uncompressed size by reading the size from the
+    /// footer of every block, skipping over the compressed data. The blocks seen are remembered
+    /// along the way, so seeking near the end then only decompresses the last few blocks.
+    ///
+    /// The inner reader must not be read or moved other than through this reader.
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let current = self.position();
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(delta) => current.checked_add_signed(delta),
+            SeekFrom::End(delta) => self.uncompressed_len()?.checked_add_signed(delta),
+        }
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+
+        let checkpoints = &self.decoder.checkpoints;
+        // The first checkpoint is always zero, so at least one is at or before `target`.
+        let checkpoint =
+            checkpoints[checkpoints.partition_point(|c| c.uncompressed_offset <= target) - 1];
+        if target < current || checkpoint.uncompressed_offset > current {
+            self.anchored(|this| {
+                let base = this.data_start()?;
+                this.reader.seek(SeekFrom::Start(base + checkpoint.compressed_offset))
+            })?;
+            self.decoder.restart_at(checkpoint);
+            self.framer.clear();
+            self.peeked = None;
+        }
+        let position = self.position();
+        self.skip(target - position)?;
+        Ok(self.position())
+    }
+}
+
+impl<R> Reader<R>
+where
+    R: Read + Seek,
+{
+    /// Where the BGZF data starts in the inner reader.
+    fn data_start(&mut self) -> io::Result<u64> {
+        let pending = self.framer.partial() + self.peeked.unwrap_or(0);
+        let consumed = self.decoder.compressed_offset + pending as u64;
+        Ok(self.reader.stream_position()? - consumed)
+    }
+
+    /// The total number of uncompressed bytes, found from the block footers the first time.
+    fn uncompressed_len(&mut self) -> io::Result<u64> {
+        match self.uncompressed_len {
+            Some(len) => Ok(len),
+            None => self.anchored(Self::scan_uncompressed_len),
+        }
+    }
+
+    /// Find the total number of uncompressed bytes from the block footers.
+    fn scan_uncompressed_len(&mut self) -> io::Result<u64> {
+        let resume = self.reader.stream_position()?;
+        let base = self.data_start()?;
+        self.reader.seek(SeekFrom::Start(base))?;
+
+        let mut header = Vec::with_capacity(BGZF_HEADER_SIZE);
+        let mut footer = [0; BGZF_FOOTER_SIZE];
+        let mut start = IndexEntry { compressed_offset: 0, uncompressed_offset: 0 };
+        loop {
+            header.clear();
+            if !fill_to(&mut self.reader, &mut header, BGZF_HEADER_SIZE)? {
+                if header.is_empty() {
+                    break;
+                }
+                return Err(into_io_error(BgzfError::Truncated));
+            }
+            let size = block_size(&header).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+            let skip = size - BGZF_HEADER_SIZE - BGZF_FOOTER_SIZE;
+            self.reader.seek(SeekFrom::Current(skip as i64))?;
+            self.reader.read_exact(&mut footer)?;
+            let uncompressed_size = BlockFooter::parse(&footer)
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?
+                .uncompressed_size;
+
+            self.decoder.add_checkpoint(start);
+            start.compressed_offset += size as u64;
+            start.uncompressed_offset += u64::from(uncompressed_size);
+        }
+        self.reader.seek(SeekFrom::Start(resume))?;
+        self.uncompressed_len = Some(start.uncompressed_offset);
+        Ok(start.uncompressed_offset)
+    }
+}
+
+/// A memory mapped BGZF file that a [`Reader`] decodes blocks from in place.
+///
+/// See [`Reader::from_mmap`].
+#[cfg(feature = "mmap")]
+pub struct MmapSource {
+    map: memmap2::Mmap,
+    /// The offset of the next block in the mapping
+    pos: usize,
+}
+
+#[cfg(feature = "mmap")]
+impl Reader<MmapSource> {
+    /// Create a BGZF reader that decodes blocks directly from a memory mapping of the file at `path`.
+    ///
+    /// This avoids a read syscall and a copy into an intermediate buffer for each block.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated, by this or any other process, while the
+    /// returned reader is alive. See [`memmap2::Mmap::map`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::{Reader, Writer};
+    /// use std::io::{Read, Write};
+    ///
+    /// let dir = tempfile::tempdir().unwrap();
+    /// let path = dir.path().join("example.txt.gz");
+    /// let mut writer = Writer::from_path(&path, 2.try_into().unwrap()).unwrap();
+    /// writer.write_all(b"hello world").unwrap();
+    /// drop(writer);
+    ///
+    /// let mut reader = unsafe { Reader::from_mmap(&path) }.unwrap();
+    /// let mut decompressed = vec![];
+    /// reader.read_to_end(&mut decompressed).unwrap();
+    /// assert_eq!(decompressed, b"hello world");
+    /// ```
+    #[allow(unsafe_code)]
+    pub unsafe fn from_mmap<P>(path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let file = File::open(path)?;
+        let map = memmap2::Mmap::map(&file)?;
+        Ok(Self::with_source(MmapSource { map, pos: 0 }))
+    }
+
+    /// Decompress the next block of the mapping into the decompressed buffer.
+    ///
+    /// Returns `false` if no further block is available.
+    fn read_mapped_block(&mut self) -> io::Result<bool> {
+        let decoded = decode_in_place(&mut self.decoder, &self.reader.map[self.reader.pos..])?;
+        Ok(decoded.map(|size| self.reader.pos += size).is_some())
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl Read for Reader<MmapSource> {
+    /// Attempt to read `buf.len()` bytes from the mapping into `buf`.
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.read_with(buf, Self::read_mapped_block)
+    }
+}
+
+/// BGZF data in memory, such as a slice or [`bytes::Bytes`], that a [`Reader`] decodes blocks
+/// from in place.
+///
+/// See [`Reader::from_slice`].
+#[derive(Clone)]
+pub struct SliceSource<B> {
+    data: B,
+    /// The offset of the next block in `data`
+    pos: usize,
+}
+
+/// A [`Reader`] that decodes blocks in place from a borrowed slice.
+pub type SliceReader<'a> = Reader<SliceSource<&'a [u8]>>;
+
+impl<B> Reader<SliceSource<B>>
+where
+    B: AsRef<[u8]>,
+{
+    /// Create a BGZF reader that parses headers and decompresses blocks directly from `data`,
+    /// without copying each block into an intermediate buffer first.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::{Reader, SliceReader, Writer};
+    /// use std::io::{Read, Write};
+    ///
+    /// let mut writer = Writer::new(vec![], 2.try_into().unwrap());
+    /// writer.write_all(b"hello world").unwrap();
+    /// let (compressed, _) = writer.finish().unwrap();
+    ///
+    /// let mut reader: SliceReader = Reader::from_slice(compressed.as_slice());
+    /// let mut decompressed = vec![];
+    /// reader.read_to_end(&mut decompressed).unwrap();
+    /// assert_eq!(decompressed, b"hello world");
+    /// ```
+    pub fn from_slice(data: B) -> Self {
+        Self::with_source(SliceSource { data, pos: 0 })
+    }
+
+    /// Decompress the next block of the data into the decompressed buffer.
+    ///
+    /// Returns `false` if no further block is available.
+    fn read_slice_block(&mut self) -> io::Result<bool> {
+        let remaining = &self.reader.data.as_ref()[self.reader.pos..];
+        let decoded = decode_in_place(&mut self.decoder, remaining)?;
+        Ok(decoded.map(|size| self.reader.pos += size).is_some())
+    }
+}
+
+impl<B> Read for Reader<SliceSource<B>>
+where
+    B: AsRef<[u8]>,
+{
+    /// Attempt to read `buf.len()` bytes from the data into `buf`.
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.read_with(buf, Self::read_slice_block)
+    }
+}
+
+/// A [`BufRead`] that a [`Reader`] decodes blocks from in place within its buffer.
+///
+/// See [`Reader::from_buf_read`].
+pub struct BufReadSource<R> {
+    inner: R,
+}
+
+impl<R> Reader<BufReadSource<R>>
+where
+    R: BufRead,
+{
+    /// Create a BGZF reader that decompresses blocks directly out of the buffer of `reader`,
+    /// such as a [`io::BufReader`], rather than copying them into a buffer of its own first.
+    ///
+    /// Only blocks that straddle the end of the buffer are copied.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::{Reader, Writer};
+    /// use std::io::{BufReader, Read, Write};
+    ///
+    /// let mut writer = Writer::new(vec![], 2.try_into().unwrap());
+    /// writer.write_all(b"hello world").unwrap();
+    /// let (compressed, _) = writer.finish().unwrap();
+    ///
+    /// let mut reader = Reader::from_buf_read(BufReader::new(compressed.as_slice()));
+    /// let mut decompressed = vec![];
+    /// reader.read_to_end(&mut decompressed).unwrap();
+    /// assert_eq!(decompressed, b"hello world");
+    /// ```
+    pub fn from_buf_read(reader: R) -> Self {
+        Self::with_source(BufReadSource { inner: reader })
+    }
+
+    /// Decompress the next block into the decompressed buffer, from within the inner reader's
+    /// buffer if it holds the whole block.
+    ///
+    /// Returns `false` if no further block is available.
+    fn read_buffered_block(&mut self) -> io::Result<bool> {
+        if self.framer.partial() == 0 {
+            let available = self.reader.inner.fill_buf()?;
+            if available.len() >= BGZF_HEADER_SIZE {
+                let size =
+                    block_size(available).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+                if available.len() >= size {
+                    self.decoder.decode(size, &available[BGZF_HEADER_SIZE..size])?;
+                    self.reader.inner.consume(size);
+                    return Ok(true);
+                }
+            }
+        }
+        let inner = &mut self.reader.inner;
+        match self.framer.fill_with(|buf| inner.read(buf))? {
+            Some(size) => {
+                self.decoder.decode(size, self.framer.body())?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+impl<R> Read for Reader<BufReadSource<R>>
+where
+    R: BufRead,
+{
+    /// Attempt to read `buf.len()` bytes from the inner reader into `buf`.
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.read_with(buf, Self::read_buffered_block)
+    }
+}
+
+/// Decompress the block at the start of `remaining` into the decompressed buffer of `decoder`,
+/// returning its size, or `None` if no further block is available.
+fn decode_in_place(decoder: &mut BlockDecoder, remaining: &[u8]) -> io::Result<Option<usize>> {
+    if remaining.len() < BGZF_HEADER_SIZE {
+        return Ok(None);
+    }
+    let size = block_size(remaining).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    if remaining.len() < size {
+        return Err(into_io_error(BgzfError::Truncated));
+    }
+    decoder.decode(size, &remaining[BGZF_HEADER_SIZE..size])?;
+    Ok(Some(size))
+}
+
+/// A background thread that reads and decompresses blocks ahead of a [`Reader`].
+///
+/// See [`Reader::with_prefetch`].
+pub struct PrefetchSource {
+    /// Each decompressed block along with its compressed size and footer
+    blocks: Receiver<io::Result<(usize, BytesMut, BlockFooter)>>,
+}
+
+/// The blocks a [`PrefetchSource`]'s thread may decompress ahead, handed to it as credits that
+/// come back as the blocks are read.
+struct PrefetchCredits {
+    credits: Sender<()>,
+    /// The most blocks to decompress ahead, as given to [`Reader::with_prefetch`]
+    max_depth: usize,
+    /// The blocks to decompress ahead now
+    depth: usize,
+    /// The credits held by the thread, waiting for it, or spent on blocks not yet read
+    outstanding: usize,
+}
+
+impl PrefetchCredits {
+    /// Take back the credit of a block that has been read, passing it on to the thread unless the
+    /// depth has been lowered since.
+    fn release(&mut self) {
+        if self.outstanding > self.depth {
+            self.outstanding -= 1;
+        } else {
+            // The thread has exited if this fails, and needs no more credits.
+            let _ = self.credits.send(());
+        }
+    }
+
+    /// Decompress up to `depth` blocks ahead, handing out more credits at once if that is more
+    /// than before.
+    fn set_depth(&mut self, depth: usize) {
+        self.depth = depth;
+        while self.outstanding < depth {
+            let _ = self.credits.send(());
+            self.outstanding += 1;
+        }
+    }
+
+    /// The most memory the thread's blocks may take up at the current depth: each queued block,
+    /// the one being decompressed, and the compressed buffer.
+    fn memory(&self) -> usize {
+        (self.depth + 2) * MAX_BGZF_BLOCK_SIZE
+    }
+}
+
+impl Reader<PrefetchSource> {
+    /// Create a BGZF reader that reads and decompresses up to `blocks` blocks ahead of the caller
+    /// on a background thread.
+    ///
+    /// This overlaps I/O and decompression with whatever the caller does with the data, which
+    /// helps sequential scans. The thread exits once the input is exhausted, an error occurs, or
+    /// the reader is dropped. [`Reader::set_memory_limit`] lowers the number of blocks read ahead
+    /// to fit a byte budget.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::{Reader, Writer};
+    /// use std::io::{Read, Write};
+    ///
+    /// let mut compressed = vec![];
+    /// let mut writer = Writer::new(&mut compressed, 2.try_into().unwrap());
+    /// writer.write_all(b"hello world").unwrap();
+    /// drop(writer);
+    ///
+    /// let mut reader = Reader::with_prefetch(std::io::Cursor::new(compressed), 4);
+    /// let mut decompressed = vec![];
+    /// reader.read_to_end(&mut decompressed).unwrap();
+    /// assert_eq!(decompressed, b"hello world");
+    /// ```
+    pub fn with_prefetch<R>(reader: R, blocks: usize) -> Self
+    where
+        R: Read + Send + 'static,
+    {
+        let max_depth = blocks.max(1);
+        let (sender, receiver) = bounded(max_depth);
+        let (credit_sender, credits) = unbounded();
+        thread::Builder::new()
+            .name("bgzf-prefetch".to_string())
+            .spawn(move || {
+                let mut reader = reader;
+                let mut framer = Framer::new();
+                let mut decompressor = Decompressor::new();
+                // Each block takes a credit, so stop once the reader has been dropped.
+                while credits.recv().is_ok() {
+                    let block = match framer.fill_with(|buf| reader.read(buf)) {
+                        Ok(Some(size)) => decompress_into_new(&mut decompressor, framer.body())
+                            .map(|(decompressed, footer)| (size, decompressed, footer)),
+                        Ok(None) => break,
+                        Err(e) => Err(e),
+                    };
+                    let failed = block.is_err();
+                    // Stop on errors, or once the reader has been dropped.
+                    if sender.send(block).is_err() || failed {
+                        break;
+                    }
+                }
+            })
+            .expect("Failed to spawn bgzf prefetch thread");
+        let mut prefetch =
+            PrefetchCredits { credits: credit_sender, max_depth, depth: 0, outstanding: 0 };
+        prefetch.set_depth(max_depth);
+        let mut reader = Self::with_source(PrefetchSource { blocks: receiver });
+        reader.decoder.reserved_memory += prefetch.memory();
+        reader.prefetch = Some(prefetch);
+        reader
+    }
+
+    /// The number of blocks decompressed ahead of the caller, see [`Reader::set_memory_limit`].
+    pub fn prefetch_depth(&self) -> usize {
+        self.prefetch.as_ref().map_or(0, |prefetch| prefetch.depth)
+    }
+
+    /// Take the next block decompressed by the background thread.
+    ///
+    /// Returns `false` if no further block is available.
+    fn read_prefetched_block(&mut self) -> io::Result<bool> {
+        match self.reader.blocks.recv() {
+            Ok(block) => {
+                if let Some(prefetch) = self.prefetch.as_mut() {
+                    prefetch.release();
+                }
+                let (size, decompressed, footer) = block?;
+                self.decoder.accept(size, decompressed, footer)?;
+                Ok(true)
+            }
+            Err(_) => Ok(false),
+        }
+    }
+}
+
+impl Read for Reader<PrefetchSource> {
+    /// Attempt to read `buf.len()` bytes of prefetched data into `buf`.
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.read_with(buf, Self::read_prefetched_block)
+    }
+}
+
+/// Decompress a block, given without its header, into a newly allocated buffer.
+fn decompress_into_new(
+    decompressor: &mut Decompressor,
+    compressed: &[u8],
+) -> io::Result<(BytesMut, BlockFooter)> {
+    let mut decompressed = BytesMut::new();
+    let footer = decompress_footed(decompressor, compressed, &mut decompressed)?;
+    Ok((decompressed, footer))
+}
+
+/// Decompress a block, given without its header, replacing the contents of `output`.
+fn decompress_footed(
+    decompressor: &mut Decompressor,
+    compressed: &[u8],
+    output: &mut BytesMut,
+) -> io::Result<BlockFooter> {
+    let result = BlockFooter::from_block(compressed).and_then(|check| {
+        output.clear();
+        output.resize(check.uncompressed_size as usize, 0);
+        decompressor.decompress(strip_footer(compressed)?, output, check).map(|()| check)
+    });
+    if result.is_err() {
+        // Never hand out the partially decompressed data.
+        output.clear();
+    }
+    result.map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+}
+
+/// The state needed to decompress blocks, independent of where the compressed bytes come from.
+struct BlockDecoder {
+    decompressed_buffer: BytesMut,
+    decompressor: Decompressor,
+    /// The number of compressed bytes consumed from the inner reader so far
+    compressed_offset: u64,
+    /// The number of uncompressed bytes decompressed so far
+    uncompressed_offset: u64,
+    /// The callback to invoke after each block is decompressed
+    block_callback: Option<BlockCallback>,
+    /// The progress sink to update after each block is decompressed
+    progress: Option<ProgressTracker>,
+    /// The digest of all data decompressed so far, if set
+    digest: Option<Box<dyn StreamDigest>>,
+    /// The CRC32 of all blocks decoded so far, combined from their footers, if enabled
+    stream_crc: Option<u32>,
+    /// The starts of blocks about [`CHECKPOINT_INTERVAL`] bytes apart, for seeking backward
+    checkpoints: Vec<IndexEntry>,
+    /// Whether to fail on an empty block that isn't the last block
+    strict_eof: bool,
+    /// The cap on the memory used for buffers, if any
+    memory_limit: Option<usize>,
+    /// The most memory the buffers may use, other than the checkpoints
+    reserved_memory: usize,
+    /// The compressed offset of the last block, if it was empty
+    empty_block: Option<u64>,
+    /// The start of the last block decoded, whose data is in `decompressed_buffer`
+    last_block: IndexEntry,
+    /// The size of a block read from the inner reader since the last one decoded, that failed to
+    /// decode and so isn't counted in `compressed_offset`
+    failed_block: usize,
+}
+
+impl BlockDecoder {
+    fn new() -> Self {
+        Self {
+            decompressed_buffer: BytesMut::with_capacity(BUFSIZE),
+            decompressor: Decompressor::new(),
+            compressed_offset: 0,
+            uncompressed_offset: 0,
+            block_callback: None,
+            progress: None,
+            digest: None,
+            stream_crc: None,
+            checkpoints: vec![IndexEntry { compressed_offset: 0, uncompressed_offset: 0 }],
+            strict_eof: false,
+            memory_limit: None,
+            // The compressed buffer holds up to one block, the decompressed buffer starts larger.
+            reserved_memory: BGZF_HEADER_SIZE + MAX_BGZF_BLOCK_SIZE + BUFSIZE,
+            empty_block: None,
+            last_block: IndexEntry { compressed_offset: 0, uncompressed_offset: 0 },
+            failed_block: 0,
+        }
+    }
+
+    /// Copy the state of this decoder, leaving out the block callback, progress sink, and digest,
+    /// which can't be cloned.
+    fn fork(&self) -> Self {
+        Self {
+            decompressed_buffer: self.decompressed_buffer.clone(),
+            decompressor: Decompressor::new(),
+            compressed_offset: self.compressed_offset,
+            uncompressed_offset: self.uncompressed_offset,
+            block_callback: None,
+            progress: None,
+            digest: None,
+            stream_crc: self.stream_crc,
+            checkpoints: self.checkpoints.clone(),
+            strict_eof: self.strict_eof,
+            memory_limit: self.memory_limit,
+            reserved_memory: self.reserved_memory,
+            empty_block: self.empty_block,
+            last_block: self.last_block,
+            failed_block: self.failed_block,
+        }
+    }
+
+    /// Continue decoding from the block at `start`, discarding the decompressed buffer.
+    fn restart_at(&mut self, start: IndexEntry) {
+        self.decompressed_buffer.clear();
+        self.compressed_offset = start.compressed_offset;
+        self.uncompressed_offset = start.uncompressed_offset;
+        self.empty_block = None;
+        self.failed_block = 0;
+        // The blocks before `start` aren't all accounted for any more.
+        self.stream_crc = None;
+    }
+
+    /// The most memory the buffers may take up, in bytes.
+    fn max_memory(&self) -> usize {
+        self.reserved_memory + self.checkpoints.capacity() * std::mem::size_of::<IndexEntry>()
+    }
+
+    /// Fail if `extra` more bytes on top of the buffers would go over the memory limit.
+    fn check_memory(&self, extra: usize) -> io::Result<()> {
+        match self.memory_limit {
+            Some(limit) if self.max_memory() + extra > limit => Err(io::Error::new(
+                io::ErrorKind::OutOfMemory,
+                format!(
+                    "BGZF reader needs up to {} bytes, over its memory limit of {} bytes",
+                    self.max_memory() + extra,
+                    limit
+                ),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// In strict mode, fail if another block follows an empty one.
+    fn check_order(&self) -> io::Result<()> {
+        match self.empty_block {
+            Some(offset) if self.strict_eof => {
+                Err(io::Error::new(io::ErrorKind::InvalidData, BgzfError::InteriorEof(offset)))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Decompress a block of `size` bytes into the decompressed buffer.
+    ///
+    /// `compressed` holds the block with its header removed but its footer intact.
+    fn decode(&mut self, size: usize, compressed: &[u8]) -> io::Result<()> {
+        let footer = self
+            .check_order()
+            .and_then(|()| {
+                decompress_footed(&mut self.decompressor, compressed, &mut self.decompressed_buffer)
+            })
+            .map_err(|e| self.fail(size, e))?;
+        if let Some(digest) = self.digest.as_mut() {
+            digest.update(&self.decompressed_buffer);
+        }
+        self.record(size, footer);
+        Ok(())
+    }
+
+    /// Decompress a block of `size` bytes onto the end of `output`, bypassing the buffer.
+    ///
+    /// `compressed` holds the block with its header removed but its footer intact.
+    fn decode_onto(
+        &mut self,
+        size: usize,
+        compressed: &[u8],
+        output: &mut Vec<u8>,
+    ) -> io::Result<()> {
+        let check = BlockFooter::from_block(compressed)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        let start = output.len();
+        output.resize(start + check.uncompressed_size as usize, 0);
+        if let Err(e) = self.decode_into(size, compressed, &mut output[start..]) {
+            output.truncate(start);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Decompress a block of `size` bytes into the start of `output`, bypassing the buffer, and
+    /// return how many bytes it held.
+    ///
+    /// `compressed` holds the block with its header removed but its footer intact, and `output`
+    /// must have room for the whole block.
+    fn decode_into(
+        &mut self,
+        size: usize,
+        compressed: &[u8],
+        output: &mut [u8],
+    ) -> io::Result<usize> {
+        self.check_order().map_err(|e| self.fail(size, e))?;
+        let check = BlockFooter::from_block(compressed)
+            .map_err(|e| self.fail(size, io::Error::new(io::ErrorKind::Other, e)))?;
+        let amount = check.uncompressed_size as usize;
+        strip_footer(compressed)
+            .and_then(|deflated| {
+                self.decompressor.decompress(deflated, &mut output[..amount], check)
+            })
+            .map_err(|e| self.fail(size, io::Error::new(io::ErrorKind::Other, e)))?;
+        if let Some(digest) = self.digest.as_mut() {
+            digest.update(&output[..amount]);
+        }
+        self.record(size, check);
+        Ok(amount)
+    }
+
+    /// Make `decompressed`, the contents of a block of `size` bytes with `footer`, the
+    /// decompressed buffer.
+    fn accept(
+        &mut self,
+        size: usize,
+        decompressed: BytesMut,
+        footer: BlockFooter,
+    ) -> io::Result<()> {
+        self.check_order().map_err(|e| self.fail(size, e))?;
+        if let Some(digest) = self.digest.as_mut() {
+            digest.update(&decompressed);
+        }
+        self.decompressed_buffer = decompressed;
+        self.record(size, footer);
+        Ok(())
+    }
+
+    /// Note that the block of `size` bytes just read failed to decode with `error`, so that
+    /// [`Reader::resync`] can count it as skipped.
+    fn fail(&mut self, size: usize, error: io::Error) -> io::Error {
+        self.failed_block = size;
+        error
+    }
+
+    /// Remember the block starting at `start` for seeking, if it is far enough past the last one
+    /// remembered and within the memory limit.
+    fn add_checkpoint(&mut self, start: IndexEntry) {
+        let last_checkpoint = self.checkpoints.last().map_or(0, |c| c.uncompressed_offset);
+        let checkpoint_size = std::mem::size_of::<IndexEntry>();
+        if start.uncompressed_offset >= last_checkpoint + CHECKPOINT_INTERVAL
+            && (self.checkpoints.len() < self.checkpoints.capacity()
+                || self.check_memory(checkpoint_size).is_ok())
+        {
+            if self.memory_limit.is_some() {
+                self.checkpoints.reserve_exact(1);
+            }
+            self.checkpoints.push(start);
+        }
+    }
+
+    /// Account for a block of `size` bytes, with `footer` giving its CRC32 and uncompressed size.
+    fn record(&mut self, size: usize, footer: BlockFooter) {
+        let uncompressed_size = footer.uncompressed_size as usize;
+        self.failed_block = 0;
+        if let Some(crc) = self.stream_crc.as_mut() {
+            *crc = crc32_combine(*crc, footer.crc32, uncompressed_size as u64);
+        }
+        let info = BlockInfo {
+            compressed_offset: self.compressed_offset,
+            compressed_size: size,
+            uncompressed_offset: self.uncompressed_offset,
+            uncompressed_size,
+        };
+        self.last_block = IndexEntry {
+            compressed_offset: info.compressed_offset,
+            uncompressed_offset: info.uncompressed_offset,
+        };
+        self.compressed_offset += info.compressed_size as u64;
+        self.uncompressed_offset += info.uncompressed_size as u64;
+        self.empty_block = (uncompressed_size == 0).then_some(info.compressed_offset);
+
+        self.add_checkpoint(IndexEntry {
+            compressed_offset: info.compressed_offset,
+            uncompressed_offset: info.uncompressed_offset,
+        });
+        if let Some(callback) = self.block_callback.as_mut() {
+            callback(info);
+        }
+        if let Some(progress) = self.progress.as_mut() {
+            progress.update(self.compressed_offset);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::{
+        compress_parallel, decompress_all, test::test_input, CompressionLevel, Compressor, Index,
+        IndexedReader, TeeWriter, Writer,
+    };
+
+    #[test]
+    fn test_reader_block_callback() {
+        let input = test_input(200_000);
+        let written = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+        let read = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+
+        let mut output = vec![];
+        let mut writer =
+            Writer::with_capacity(&mut output, CompressionLevel::new(3).unwrap(), 1000);
+        let sink = std::sync::Arc::clone(&written);
+        writer.set_block_callback(move |block| sink.lock().unwrap().push(block));
+        writer.write_all(&input).unwrap();
+        drop(writer);
+
+        let mut reader = Reader::new(&output[..]);
+        let sink = std::sync::Arc::clone(&read);
+        reader.set_block_callback(move |block| sink.lock().unwrap().push(block));
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes).unwrap();
+        assert_eq!(bytes, input);
+
+        let read = read.lock().unwrap();
+        let non_empty: Vec<BlockInfo> =
+            read.iter().copied().filter(|block| block.uncompressed_size > 0).collect();
+        assert_eq!(non_empty, *written.lock().unwrap());
+        let last = read.last().unwrap();
+        assert_eq!(last.compressed_offset + last.compressed_size as u64, output.len() as u64);
+    }
+
+    #[test]
+    #[cfg(feature = "mmap")]
+    #[allow(unsafe_code)]
+    fn test_mmap_reader() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("output.txt.gz");
+        let input = test_input(200_000);
+
+        let mut writer = Writer::from_path(&path, CompressionLevel::new(3).unwrap()).unwrap();
+        writer.write_all(&input).unwrap();
+        drop(writer);
+
+        let mut reader = unsafe { Reader::from_mmap(&path) }.unwrap();
+        let mut bytes = vec![];
+        for chunk in input.chunks(7919) {
+            let mut buf = vec![0; chunk.len()];
+            reader.read_exact(&mut buf).unwrap();
+            bytes.extend(buf);
+        }
+        assert_eq!(reader.read(&mut [0; 10]).unwrap(), 0);
+        assert_eq!(bytes, input);
+    }
+
+    #[test]
+    fn test_slice_reader() {
+        let input = test_input(200_000);
+        let compressed = compress_parallel(&input, CompressionLevel::new(3).unwrap()).unwrap();
+
+        let mut reader = Reader::from_slice(bytes::Bytes::from(compressed.clone()));
+        let mut bytes = vec![];
+        for chunk in input.chunks(7919) {
+            let mut buf = vec![0; chunk.len()];
+            reader.read_exact(&mut buf).unwrap();
+            bytes.extend(buf);
+        }
+        assert_eq!(reader.read(&mut [0; 10]).unwrap(), 0);
+        assert_eq!(bytes, input);
+
+        let truncated = &compressed[..compressed.len() - BGZF_EOF.len() - 1];
+        let error = Reader::from_slice(truncated).read_to_end(&mut vec![]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_cloned_readers_have_independent_cursors() {
+        let input = test_input(500_000);
+        let (mut data, mut gzi) = (vec![], vec![]);
+        let mut writer = TeeWriter::new(&mut data, &mut gzi, CompressionLevel::new(3).unwrap());
+        writer.write_all(&input).unwrap();
+        writer.finish().unwrap();
+        let data: std::sync::Arc<[u8]> = data.into();
+
+        let mut reader = Reader::new(io::Cursor::new(std::sync::Arc::clone(&data)));
+        reader.read_exact(&mut vec![0; 100_000]).unwrap();
+        let mut clone = reader.clone();
+        let mut rest = vec![];
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, &input[100_000..]);
+        rest.clear();
+        clone.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, &input[100_000..]);
+
+        let index = Index::read_gzi(gzi.as_slice()).unwrap();
+        let reader = IndexedReader::new(io::Cursor::new(data), index);
+        std::thread::scope(|scope| {
+            for thread in 0..4_usize {
+                let (mut reader, input) = (reader.clone(), &input);
+                scope.spawn(move || {
+                    let start = thread * 110_000;
+                    let mut buf = vec![0; 60_000];
+                    reader.seek(io::SeekFrom::Start(start as u64)).unwrap();
+                    reader.read_exact(&mut buf).unwrap();
+                    assert_eq!(buf, &input[start..start + 60_000]);
+                });
+            }
+        });
+    }
+
+    /// A file whose clones share its position, as [`File::try_clone`] handles do.
+    struct SharedFile(File);
+
+    impl Clone for SharedFile {
+        fn clone(&self) -> Self {
+            Self(self.0.try_clone().unwrap())
+        }
+    }
+
+    impl Read for SharedFile {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    impl Seek for SharedFile {
+        fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+            self.0.seek(pos)
+        }
+    }
+
+    #[test]
+    fn test_cloned_readers_of_a_shared_file_have_independent_cursors() {
+        let input = test_input(500_000);
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("shared.gz");
+        let mut writer = Writer::from_path(&path, CompressionLevel::new(3).unwrap()).unwrap();
+        writer.write_all(&input).unwrap();
+        writer.finish().unwrap();
+
+        let mut reader = Reader::new(SharedFile(File::open(&path).unwrap()));
+        let mut buf = vec![0; 100_000];
+        reader.read_exact(&mut buf).unwrap();
+        let mut clone = reader.clone();
+        let mut clone_of_clone = clone.clone();
+
+        // Take turns, so that each reads from where the others left the shared position.
+        let mut outputs = vec![input[..100_000].to_vec(); 3];
+        for _ in 0..4 {
+            for (reader, output) in
+                [&mut reader, &mut clone, &mut clone_of_clone].into_iter().zip(outputs.iter_mut())
+            {
+                let n = reader.read(&mut buf[..70_000]).unwrap();
+                output.extend_from_slice(&buf[..n]);
+            }
+        }
+        let read = outputs[1].len();
+        clone.seek(io::SeekFrom::Start(10)).unwrap();
+        for (reader, output) in
+            [&mut reader, &mut clone, &mut clone_of_clone].into_iter().zip(outputs.iter_mut())
+        {
+            reader.read_to_end(output).unwrap();
+        }
+        assert_eq!(outputs[0], input);
+        assert_eq!(outputs[1][..read], input[..read]);
+        assert_eq!(outputs[1][read..], input[10..]);
+        assert_eq!(outputs[2], input);
+    }
+
+    #[test]
+    fn test_prefetch_reader() {
+        let input = test_input(1_000_000);
+        let compressed = compress_parallel(&input, CompressionLevel::new(3).unwrap()).unwrap();
+
+        let blocks = std::sync::Arc::new(std::sync::Mutex::new(0));
+        let mut reader = Reader::with_prefetch(io::Cursor::new(compressed.clone()), 3);
+        let sink = std::sync::Arc::clone(&blocks);
+        reader.set_block_callback(move |_| *sink.lock().unwrap() += 1);
+        let mut decompressed = vec![];
+        reader.read_to_end(&mut decompressed).unwrap();
+        assert_eq!(decompressed, input);
+        // Every data block plus the EOF block
+        assert_eq!(
+            *blocks.lock().unwrap(),
+            (input.len() + BGZF_BLOCK_SIZE - 1) / BGZF_BLOCK_SIZE + 1
+        );
+
+        // Errors from the background thread reach the caller.
+        let mut corrupt = compressed;
+        corrupt[100] ^= 0xff;
+        let mut reader = Reader::with_prefetch(io::Cursor::new(corrupt), 3);
+        assert!(reader.read_to_end(&mut vec![]).is_err());
+    }
+
+    #[test]
+    fn test_reader_seek_without_index() {
+        let input = test_input(3_000_000);
+        // Leave some unrelated bytes in front of the BGZF data.
+        let mut data = b"prefix".to_vec();
+        data.extend(compress_parallel(&input, CompressionLevel::new(3).unwrap()).unwrap());
+        let mut cursor = io::Cursor::new(data);
+        cursor.set_position(6);
+
+        let mut reader = Reader::new(cursor);
+        let mut buf = vec![0; 1000];
+        for &target in &[2_500_000_u64, 100, 1_500_000, 1_499_000, 2_999_000, 0] {
+            assert_eq!(reader.seek(io::SeekFrom::Start(target)).unwrap(), target);
+            reader.read_exact(&mut buf).unwrap();
+            assert_eq!(buf, &input[target as usize..target as usize + 1000]);
+        }
+        assert_eq!(reader.seek(io::SeekFrom::Current(-500)).unwrap(), 500);
+        assert_eq!(reader.seek(io::SeekFrom::Current(70_000)).unwrap(), 70_500);
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, &input[70_500..71_500]);
+        assert_eq!(reader.seek(io::SeekFrom::Start(5_000_000)).unwrap(), input.len() as u64);
+        assert!(reader.seek(io::SeekFrom::Current(-5_000_000)).is_err());
+        assert_eq!(reader.seek(io::SeekFrom::End(0)).unwrap(), input.len() as u64);
+    }
+
+    #[test]
+    fn test_reader_seek_from_end_without_index() {
+        let input = test_input(3_000_000);
+        let compressed = compress_parallel(&input, CompressionLevel::new(3).unwrap()).unwrap();
+        let mut reader = Reader::new(io::Cursor::new(compressed));
+        let decoded = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = std::sync::Arc::clone(&decoded);
+        reader.set_block_callback(move |_| {
+            counter.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        });
+
+        let mut buf = vec![0; 100];
+        reader.read_exact(&mut buf).unwrap();
+        let end = input.len() as u64;
+        assert_eq!(reader.seek(io::SeekFrom::End(-1000)).unwrap(), end - 1000);
+        let mut tail = vec![];
+        reader.read_to_end(&mut tail).unwrap();
+        assert_eq!(tail, &input[input.len() - 1000..]);
+        // Only the blocks from the last checkpoint on are decompressed, plus the first one.
+        assert!(decoded.load(std::sync::atomic::Ordering::Relaxed) < 20);
+
+        assert_eq!(reader.seek(io::SeekFrom::End(-3_000_000)).unwrap(), 0);
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, &input[..100]);
+        assert!(reader.seek(io::SeekFrom::End(-3_000_001)).is_err());
+    }
+
+    #[test]
+    fn test_reader_resync_after_corrupt_header() {
+        let input = test_input(300_000);
+        let mut data = compress_parallel(&input, CompressionLevel::new(3).unwrap()).unwrap();
+        let index = Index::from_reader(io::Cursor::new(&data)).unwrap();
+        let (second, third, fourth) = (index.entries()[0], index.entries()[1], index.entries()[2]);
+        data[second.compressed_offset as usize + 12] = b'X';
+
+        let mut reader = Reader::new(data.as_slice());
+        let mut first = vec![0; second.uncompressed_offset as usize];
+        reader.read_exact(&mut first).unwrap();
+        assert_eq!(first, &input[..first.len()]);
+        assert!(reader.read(&mut [0; 10]).is_err());
+
+        // The corrupt header was already read, and counts as skipped along with the rest of its
+        // block.
+        let skipped = reader.resync().unwrap();
+        assert_eq!(skipped, third.compressed_offset - second.compressed_offset);
+        // The block found is the one just decoded.
+        assert_eq!(reader.compressed_position(), fourth.compressed_offset);
+        let mut rest = vec![];
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, &input[third.uncompressed_offset as usize..]);
+        assert_eq!(reader.resync().unwrap(), 0);
+
+        // A block whose data is corrupt is read whole before it fails to decode.
+        data[second.compressed_offset as usize + 12] = b'B';
+        let middle = (second.compressed_offset + third.compressed_offset) as usize / 2;
+        data[middle] ^= 0xff;
+        let mut reader = Reader::new(data.as_slice());
+        reader.read_exact(&mut first).unwrap();
+        assert!(reader.read(&mut [0; 10]).is_err());
+        assert_eq!(reader.resync().unwrap(), third.compressed_offset - second.compressed_offset);
+        assert_eq!(reader.compressed_position(), fourth.compressed_offset);
+        assert_eq!(reader.virtual_position(), VirtualOffset::new(third.compressed_offset, 0));
+    }
+
+    /// A reader that hands out a few bytes at a time, failing every other call.
+    struct FlakyReader {
+        data: Vec<u8>,
+        pos: usize,
+        calls: usize,
+    }
+
+    impl Read for FlakyReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.calls += 1;
+            match self.calls % 4 {
+                1 => Err(io::Error::from(io::ErrorKind::WouldBlock)),
+                3 => Err(io::Error::from(io::ErrorKind::Interrupted)),
+                _ => {
+                    let amount = std::cmp::min(buf.len(), 7).min(self.data.len() - self.pos);
+                    buf[..amount].copy_from_slice(&self.data[self.pos..self.pos + amount]);
+                    self.pos += amount;
+                    Ok(amount)
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_reader_resumes_after_would_block() {
+        let input = test_input(100_000);
+        let data = compress_parallel(&input, CompressionLevel::new(3).unwrap()).unwrap();
+
+        let mut reader = Reader::new(FlakyReader { data, pos: 0, calls: 0 });
+        let (mut output, mut buf, mut would_block) = (vec![], vec![0; 5000], 0);
+        loop {
+            match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => output.extend_from_slice(&buf[..n]),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => would_block += 1,
+                Err(e) => panic!("{}", e),
+            }
+        }
+        assert!(would_block > 0);
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn test_reader_errors_on_truncated_header() {
+        let data = compress_parallel(b"hello world", CompressionLevel::new(3).unwrap()).unwrap();
+        let mut reader = Reader::new(&data[..data.len() - BGZF_EOF.len() + 10]);
+        let err = reader.read_to_end(&mut vec![]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_empty_blocks_mid_stream() {
+        // An empty block as stored by htslib at level 0, next to the usual EOF marker that htslib
+        // also writes as a flush marker.
+        let stored_empty_block: &[u8] = &[
+            0x1f, 0x8b, 0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x06, 0x00, 0x42, 0x43,
+            0x02, 0x00, 0x1e, 0x00, 0x01, 0x00, 0x00, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00,
+        ];
+        let mut compressor = Compressor::new(CompressionLevel::new(3).unwrap());
+        let mut data = vec![];
+        let mut block = vec![];
+        for (i, text) in [&b"hello "[..], b"bgzf ", b"world"].iter().enumerate() {
+            data.extend(if i == 1 { stored_empty_block } else { BGZF_EOF });
+            compressor.compress(text, &mut block).unwrap();
+            data.extend(&block);
+        }
+        data.extend(BGZF_EOF);
+        data.extend(stored_empty_block);
+
+        let mut output = vec![];
+        Reader::new(data.as_slice()).read_to_end(&mut output).unwrap();
+        assert_eq!(output, b"hello bgzf world");
+        let mut output = vec![];
+        Reader::with_prefetch(io::Cursor::new(data.clone()), 2).read_to_end(&mut output).unwrap();
+        assert_eq!(output, b"hello bgzf world");
+        assert_eq!(decompress_all(&data).unwrap(), b"hello bgzf world");
+
+        let index = Index::from_reader(io::Cursor::new(&data)).unwrap();
+        let mut reader = IndexedReader::new(io::Cursor::new(&data), index);
+        let mut word = String::new();
+        reader.range(6..10).unwrap().read_to_string(&mut word).unwrap();
+        assert_eq!(word, "bgzf");
+    }
+
+    #[test]
+    fn test_strict_eof() {
+        let single = compress_parallel(b"hello", CompressionLevel::new(3).unwrap()).unwrap();
+        let mut duplicated = single.clone();
+        duplicated.extend(BGZF_EOF);
+        let interior = [single.as_slice(), single.as_slice()].concat();
+
+        for (data, expected) in
+            [(&single, "hello"), (&duplicated, "hello"), (&interior, "hellohello")]
+        {
+            let mut output = String::new();
+            Reader::new(data.as_slice()).read_to_string(&mut output).unwrap();
+            assert_eq!(output, expected);
+        }
+
+        let mut reader = Reader::new(single.as_slice());
+        reader.set_strict_eof(true);
+        reader.read_to_end(&mut vec![]).unwrap();
+        for data in [&duplicated, &interior] {
+            let mut reader = Reader::new(data.as_slice());
+            reader.set_strict_eof(true);
+            let err = reader.read_to_end(&mut vec![]).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn test_reader_memory_limit() {
+        let input = test_input(3_000_000);
+        let data = compress_parallel(&input, CompressionLevel::new(1).unwrap()).unwrap();
+
+        let mut reader = Reader::new(io::Cursor::new(&data));
+        assert!(reader.set_memory_limit(100_000).is_err());
+        let limit = reader.max_memory();
+        reader.set_memory_limit(limit).unwrap();
+        let mut output = vec![];
+        reader.read_to_end(&mut output).unwrap();
+        assert_eq!(output, input);
+        // No room was left for seek checkpoints, so seeking back restarts from the beginning.
+        assert_eq!(reader.max_memory(), limit);
+        reader.seek(io::SeekFrom::Start(2_000_000)).unwrap();
+        reader.read_exact(&mut output[..10]).unwrap();
+        assert_eq!(output[..10], input[2_000_000..2_000_010]);
+        assert_eq!(reader.resync().unwrap_err().kind(), io::ErrorKind::OutOfMemory);
+    }
+
+    #[test]
+    fn test_prefetch_reader_memory_limit() {
+        let input = test_input(3_000_000);
+        let data = compress_parallel(&input, CompressionLevel::new(1).unwrap()).unwrap();
+
+        let mut reader = Reader::with_prefetch(io::Cursor::new(data), 16);
+        let unlimited = reader.max_memory();
+        let mut output = vec![0; 500_000];
+        reader.read_exact(&mut output).unwrap();
+
+        // Halfway through, keep the thread to a couple of blocks ahead.
+        let limit = unlimited - 14 * MAX_BGZF_BLOCK_SIZE;
+        reader.set_memory_limit(limit).unwrap();
+        assert_eq!(reader.prefetch_depth(), 2);
+        assert_eq!(reader.max_memory(), limit);
+        reader.read_exact(&mut output[..]).unwrap();
+        assert_eq!(output, input[500_000..1_000_000]);
+
+        // A limit with no room for even one block ahead fails, while a larger one restores the
+        // depth given at the start.
+        assert!(reader.set_memory_limit(limit - 2 * MAX_BGZF_BLOCK_SIZE).is_err());
+        assert_eq!(reader.prefetch_depth(), 1);
+        reader.set_memory_limit(usize::MAX).unwrap();
+        assert_eq!((reader.prefetch_depth(), reader.max_memory()), (16, unlimited));
+        output.clear();
+        reader.read_to_end(&mut output).unwrap();
+        assert_eq!(output, input[1_000_000..]);
+    }
+
+    #[test]
+    fn test_reader_read_to_end_after_partial_read() {
+        let input = test_input(300_000);
+        let data = compress_parallel(&input, CompressionLevel::new(3).unwrap()).unwrap();
+
+        let mut reader = Reader::new(data.as_slice());
+        let mut output = vec![0; 1000];
+        reader.read_exact(&mut output).unwrap();
+        assert_eq!(reader.read_to_end(&mut output).unwrap(), input.len() - 1000);
+        assert_eq!(output, input);
+        assert_eq!(reader.read_to_end(&mut output).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_reader_large_reads() {
+        let input = test_input(300_000);
+        let data = compress_parallel(&input, CompressionLevel::new(3).unwrap()).unwrap();
+
+        let mut reader = Reader::new(data.as_slice());
+        let mut output = vec![];
+        let mut buf = vec![0; 200_000];
+        for len in [10, 200_000, 70_000, 200_000, 200_000, 200_000] {
+            let n = reader.read(&mut buf[..len]).unwrap();
+            output.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn test_reader_large_read_returns_blocks_before_a_corrupt_one() {
+        let input = test_input(200_000);
+        let mut compressed = compress_parallel(&input, CompressionLevel::new(3).unwrap()).unwrap();
+        // Break the CRC32 of the second block.
+        let first_size = u16::from_le_bytes([compressed[16], compressed[17]]) as usize + 1;
+        let second_size =
+            u16::from_le_bytes([compressed[first_size + 16], compressed[first_size + 17]]) as usize
+                + 1;
+        compressed[first_size + second_size - 8] ^= 0xff;
+
+        let mut reader = Reader::new(compressed.as_slice());
+        let mut buf = vec![0; 2 * MAX_BGZF_BLOCK_SIZE];
+        assert_eq!(reader.read(&mut buf).unwrap(), BGZF_BLOCK_SIZE);
+        assert_eq!(buf[..BGZF_BLOCK_SIZE], input[..BGZF_BLOCK_SIZE]);
+        assert!(reader.read(&mut buf).is_err());
+    }
+
+    #[test]
+    fn test_reader_peek_block() {
+        let input = test_input(200_000);
+        let compressed = compress_parallel(&input, CompressionLevel::new(3).unwrap()).unwrap();
+
+        let mut reader = Reader::new(compressed.as_slice());
+        let first = reader.peek_block().unwrap().unwrap();
+        assert_eq!(reader.peek_block().unwrap().unwrap(), first);
+        assert_eq!((first.uncompressed_offset, first.uncompressed_size), (0, BGZF_BLOCK_SIZE));
+
+        let mut buf = vec![0; 100];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, input[..100]);
+        let second = reader.peek_block().unwrap().unwrap();
+        assert_eq!(second.compressed_offset, first.compressed_size as u64);
+        assert_eq!(second.uncompressed_offset, BGZF_BLOCK_SIZE as u64);
+
+        let mut rest = vec![];
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, input[100..]);
+        assert!(reader.peek_block().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_buf_read_reader() {
+        let input = test_input(300_000);
+        let compressed = compress_parallel(&input, CompressionLevel::new(3).unwrap()).unwrap();
+
+        // Buffers too small for any block, about a block, and holding everything.
+        for capacity in [1000, 70_000, compressed.len()] {
+            let inner = io::BufReader::with_capacity(capacity, compressed.as_slice());
+            let mut reader = Reader::from_buf_read(inner);
+            let mut decompressed = vec![];
+            reader.read_to_end(&mut decompressed).unwrap();
+            assert_eq!(decompressed, input);
+        }
+
+        let mut reader = Reader::from_buf_read(&compressed[..compressed.len() - 40]);
+        let error = reader.read_to_end(&mut vec![]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[cfg(all(feature = "digest", feature = "sha256"))]
+    #[test]
+    fn test_reader_digest() {
+        use sha2::{Digest, Sha256};
+
+        use crate::copy_raw;
+
+        let input = test_input(300_000);
+        let compressed = compress_parallel(&input, CompressionLevel::new(3).unwrap()).unwrap();
+        let expected = Sha256::digest(&input).to_vec();
+
+        // Reads into small buffers, straight into large ones, and through read_to_end.
+        let mut reader = Reader::new(compressed.as_slice());
+        reader.set_digest(Sha256::new());
+        let mut buf = vec![0; 1000];
+        reader.read_exact(&mut buf).unwrap();
+        let mut buf = vec![0; 3 * MAX_BGZF_BLOCK_SIZE];
+        assert!(reader.read(&mut buf).unwrap() > 0);
+        reader.read_to_end(&mut vec![]).unwrap();
+        assert_eq!(reader.finish_digest().unwrap(), expected);
+
+        // Copying digests every block, even those the writer could take as they are.
+        let mut reader = Reader::new(compressed.as_slice());
+        reader.set_digest(Sha256::new());
+        let mut writer = Writer::new(vec![], CompressionLevel::new(3).unwrap());
+        copy_raw(&mut reader, &mut writer).unwrap();
+        assert_eq!(reader.finish_digest().unwrap(), expected);
+        assert!(Reader::new(compressed.as_slice()).finish_digest().is_none());
+    }
+
+    #[test]
+    fn test_reader_from_path_checked() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.gz");
+        let input = test_input(200_000);
+        let compressed = compress_parallel(&input, CompressionLevel::new(3).unwrap()).unwrap();
+
+        std::fs::write(&path, &compressed).unwrap();
+        let mut decompressed = vec![];
+        Reader::from_path_checked(&path).unwrap().read_to_end(&mut decompressed).unwrap();
+        assert_eq!(decompressed, input);
+
+        let without_eof = &compressed[..compressed.len() - BGZF_EOF.len()];
+        std::fs::write(&path, without_eof).unwrap();
+        let err = Reader::from_path_checked(&path).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let mut not_bgzf = compressed.clone();
+        not_bgzf[12] = b'X';
+        std::fs::write(&path, not_bgzf).unwrap();
+        let err = Reader::from_path_checked(&path).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        std::fs::write(&path, BGZF_EOF).unwrap();
+        assert!(Reader::from_path_checked(&path).is_ok());
+        std::fs::write(&path, b"").unwrap();
+        assert!(Reader::from_path_checked(&path).is_err());
+    }
+
+    #[cfg(feature = "read-buf")]
+    #[test]
+    fn test_reader_read_buf() {
+        let input = test_input(500_000);
+        let compressed = compress_parallel(&input, CompressionLevel::new(3).unwrap()).unwrap();
+
+        for size in [1000, 3 * MAX_BGZF_BLOCK_SIZE] {
+            let mut reader = Reader::new(compressed.as_slice());
+            let mut storage = vec![std::mem::MaybeUninit::uninit(); size];
+            let mut decompressed = vec![];
+            loop {
+                let mut buf = io::BorrowedBuf::from(storage.as_mut_slice());
+                reader.read_buf(buf.unfilled()).unwrap();
+                if buf.len() == 0 {
+                    break;
+                }
+                decompressed.extend_from_slice(buf.filled());
+            }
+            assert_eq!(decompressed, input);
+        }
+    }
+
+    #[test]
+    fn test_reader_blocks_decompressed() {
+        let input = test_input(500_000);
+        let compressed = compress_parallel(&input, CompressionLevel::new(3).unwrap()).unwrap();
+
+        let mut reader = Reader::new(compressed.as_slice());
+        let mut start = [0; 100];
+        reader.read_exact(&mut start).unwrap();
+        let blocks: Vec<_> = reader.blocks_decompressed().collect::<io::Result<_>>().unwrap();
+        assert_eq!(blocks[0].0, VirtualOffset::new(0, 100));
+        assert_eq!(blocks[0].1.len(), BGZF_BLOCK_SIZE - 100);
+        assert_eq!(blocks[1].0.uncompressed_offset(), 0);
+        let data: Vec<u8> = blocks.iter().flat_map(|(_, data)| data.iter().copied()).collect();
+        assert_eq!(data, &input[100..]);
+        assert!(reader.blocks_decompressed().next().is_none());
+
+        // Each offset seeks to the start of its block's data.
+        let index = Index::from_reader(std::io::Cursor::new(&compressed)).unwrap();
+        for (offset, data) in &blocks[1..] {
+            let block_start = index
+                .entries()
+                .iter()
+                .find(|e| e.compressed_offset == offset.compressed_offset())
+                .unwrap()
+                .uncompressed_offset as usize;
+            assert_eq!(data.as_slice(), &input[block_start..block_start + data.len()]);
+        }
+
+        let truncated = &compressed[..compressed.len() / 2];
+        let results: Vec<_> = Reader::new(truncated).blocks_decompressed().collect();
+        assert!(results.last().unwrap().is_err());
+        assert_eq!(results.iter().filter(|r| r.is_err()).count(), 1);
+    }
+}
@@ -0,0 +1,147 @@
+//! In-place rewriting of the gzip header fields of every block.
+use std::{
+    fs::OpenOptions,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use crate::{read_header, BlockHeader, BGZF_HEADER_SIZE};
+
+/// The header fields [`rewrite_headers`] sets in every block, each left as it is when `None`.
+///
+/// The default leaves every field alone; use [`HeaderFields::reproducible`] for the values this
+/// crate's writers use for MTIME and OS.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct HeaderFields {
+    /// The gzip modification time, in seconds since the Unix epoch
+    pub mtime: Option<u32>,
+    /// The gzip extra flags, a hint of the compression level used
+    pub xfl: Option<u8>,
+    /// The gzip operating system
+    pub os: Option<u8>,
+}
+
+impl HeaderFields {
+    /// Zero the modification time and set the operating system to 255 (unknown), as this crate's
+    /// writers do, leaving the compression hint alone.
+    pub fn reproducible() -> Self {
+        Self { mtime: Some(crate::BGZF_DEFAULT_MTIME), xfl: None, os: Some(crate::BGZF_DEFAULT_OS) }
+    }
+
+    /// Set the fields in `header`, returning whether anything changed.
+    fn apply(&self, header: &mut BlockHeader) -> bool {
+        let before = *header;
+        header.mtime = self.mtime.unwrap_or(header.mtime);
+        header.xfl = self.xfl.unwrap_or(header.xfl);
+        header.os = self.os.unwrap_or(header.os);
+        *header != before
+    }
+}
+
+/// Set the MTIME, XFL, and OS fields of every block header in `file` to `fields`, in place.
+///
+/// Only headers that change are written back; the block sizes and compressed data are untouched,
+/// so any index of the file stays valid. Returns the number of blocks rewritten. Setting a
+/// non-zero MTIME on the EOF marker means it no longer matches [`crate::BGZF_EOF`] exactly.
+///
+/// The file is checked one block at a time as it is rewritten, so an error part of the way
+/// through, such as a truncated last block, leaves the blocks before it rewritten.
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{rewrite_headers, HeaderFields, Writer};
+/// use std::io::{Cursor, Write};
+///
+/// let mut writer = Writer::new(vec![], 2.try_into().unwrap());
+/// writer.write_all(b"hello world").unwrap();
+/// let (compressed, _) = writer.finish().unwrap();
+///
+/// let mut file = Cursor::new(compressed.clone());
+/// let fields = HeaderFields { mtime: Some(1_700_000_000), ..HeaderFields::default() };
+/// assert_eq!(rewrite_headers(&mut file, &fields).unwrap(), 2);
+/// assert_eq!(file.get_ref()[4..8], 1_700_000_000_u32.to_le_bytes());
+///
+/// file.set_position(0);
+/// assert_eq!(rewrite_headers(&mut file, &HeaderFields::reproducible()).unwrap(), 2);
+/// assert_eq!(file.into_inner(), compressed);
+/// ```
+pub fn rewrite_headers<F>(mut file: F, fields: &HeaderFields) -> io::Result<u64>
+where
+    F: Read + Write + Seek,
+{
+    let mut header = [0; BGZF_HEADER_SIZE];
+    let mut offset = file.stream_position()?;
+    let len = file.seek(SeekFrom::End(0))?;
+    file.seek(SeekFrom::Start(offset))?;
+    let mut rewritten = 0;
+    loop {
+        match read_header(&mut file, &mut header)? {
+            0 => break,
+            BGZF_HEADER_SIZE => {}
+            _ => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Truncated BGZF block")),
+        }
+        let mut block = BlockHeader::parse(&header)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let size = block.block_size();
+        if offset + size as u64 > len {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Truncated BGZF block"));
+        }
+        if fields.apply(&mut block) {
+            file.seek(SeekFrom::Start(offset))?;
+            block.write_to(&mut file)?;
+            rewritten += 1;
+        }
+        offset += size as u64;
+        file.seek(SeekFrom::Start(offset))?;
+    }
+    file.flush()?;
+    Ok(rewritten)
+}
+
+/// Rewrite the headers of the BGZF file at `path` in place, see [`rewrite_headers`].
+pub fn rewrite_headers_in_path<P>(path: P, fields: &HeaderFields) -> io::Result<u64>
+where
+    P: AsRef<Path>,
+{
+    let file = OpenOptions::new().read(true).write(true).open(path)?;
+    rewrite_headers(file, fields)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{compress_parallel, decompress_all, test::test_input, CompressionLevel, Index};
+
+    #[test]
+    fn test_rewrite_headers_in_place() {
+        let input = test_input(200_000);
+        let compressed = compress_parallel(&input, CompressionLevel::new(3).unwrap()).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.gz");
+        std::fs::write(&path, &compressed).unwrap();
+
+        let fields = HeaderFields { mtime: Some(12345), xfl: Some(0), os: Some(3) };
+        assert_eq!(rewrite_headers_in_path(&path, &fields).unwrap(), 5);
+        let rewritten = std::fs::read(&path).unwrap();
+        assert_eq!(rewritten.len(), compressed.len());
+        assert_eq!(decompress_all(&rewritten).unwrap(), input);
+        let index = Index::from_reader(io::Cursor::new(&compressed)).unwrap();
+        let starts = std::iter::once(0).chain(index.entries().iter().map(|e| e.compressed_offset));
+        for start in starts.map(|start| start as usize) {
+            assert_eq!(rewritten[start + 4..start + 10], [0x39, 0x30, 0, 0, 0, 3]);
+        }
+        // Nothing left to change.
+        assert_eq!(rewrite_headers_in_path(&path, &fields).unwrap(), 0);
+
+        let fields = HeaderFields { xfl: Some(compressed[8]), ..HeaderFields::reproducible() };
+        assert_eq!(rewrite_headers_in_path(&path, &fields).unwrap(), 5);
+        assert_eq!(std::fs::read(&path).unwrap(), compressed);
+
+        let mut truncated = io::Cursor::new(compressed[..compressed.len() - 10].to_vec());
+        assert_eq!(
+            rewrite_headers(&mut truncated, &fields).unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+    }
+}
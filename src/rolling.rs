@@ -0,0 +1,169 @@
+//! A writer that splits its output across a series of BGZF files.
+use std::{
+    fs::File,
+    io::{self, Write},
+    mem,
+    path::PathBuf,
+};
+
+use crate::{CompressionLevel, Writer};
+
+/// When a [`RollingWriter`] moves on to its next file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rollover {
+    /// Start a new file once this many compressed bytes have been written to the current one.
+    CompressedBytes(u64),
+    /// Start a new file once this many blocks have been written to the current one.
+    Blocks(u64),
+}
+
+/// A BGZF writer that starts a new file whenever a [`Rollover`] limit is reached.
+///
+/// Files only change at block boundaries, so each one is a complete BGZF file with its own EOF
+/// marker. With [`Rollover::CompressedBytes`] a file may exceed the limit by up to one block plus
+/// the EOF marker. A new file is only created once there is data to write to it.
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{Rollover, RollingWriter};
+/// use std::io::Write;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let mut writer = RollingWriter::new(
+///     |index| dir.path().join(format!("part-{:04}.bgz", index)),
+///     2.try_into().unwrap(),
+///     Rollover::Blocks(1),
+/// )
+/// .unwrap();
+/// writer.write_all(&vec![b'A'; 100_000]).unwrap();
+/// let paths = writer.finish().unwrap();
+/// assert_eq!(paths.len(), 2);
+/// ```
+pub struct RollingWriter<F>
+where
+    F: FnMut(usize) -> PathBuf,
+{
+    /// Produces the path of the file with the given index
+    paths: F,
+    compression_level: CompressionLevel,
+    rollover: Rollover,
+    /// The writer for the current file
+    writer: Writer<File>,
+    /// The paths of every file created so far, the last being the current one
+    created: Vec<PathBuf>,
+    /// Whether the current file has reached its limit
+    full: bool,
+}
+
+impl<F> RollingWriter<F>
+where
+    F: FnMut(usize) -> PathBuf,
+{
+    /// Create a [`RollingWriter`] whose `n`th file, counting from zero, is at `paths(n)`.
+    ///
+    /// The first file is created immediately.
+    pub fn new(
+        paths: F,
+        compression_level: CompressionLevel,
+        rollover: Rollover,
+    ) -> io::Result<Self> {
+        let mut paths = paths;
+        let path = paths(0);
+        let writer = Writer::from_path(&path, compression_level)?;
+        Ok(Self { paths, compression_level, rollover, writer, created: vec![path], full: false })
+    }
+
+    /// The paths of the files created so far.
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.created
+    }
+
+    /// Write out all buffered data and the final EOF marker, returning the paths of every file.
+    pub fn finish(mut self) -> io::Result<Vec<PathBuf>> {
+        self.writer.finish_output()?;
+        Ok(mem::take(&mut self.created))
+    }
+
+    /// Whether the current file has reached the rollover limit.
+    fn limit_reached(&self) -> bool {
+        match self.rollover {
+            Rollover::CompressedBytes(bytes) => self.writer.compressed_offset() >= bytes,
+            Rollover::Blocks(blocks) => self.writer.block_count() >= blocks,
+        }
+    }
+
+    /// Finish the current file and move on to the next one.
+    fn roll(&mut self) -> io::Result<()> {
+        let path = (self.paths)(self.created.len());
+        let next = Writer::from_path(&path, self.compression_level)?;
+        self.created.push(path);
+        let mut previous = mem::replace(&mut self.writer, next);
+        self.full = false;
+        previous.finish_output()
+    }
+}
+
+impl<F> Write for RollingWriter<F>
+where
+    F: FnMut(usize) -> PathBuf,
+{
+    /// Write up to one block's worth of `buf`, so that files only change between blocks.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.full {
+            self.roll()?;
+        }
+        let amount = std::cmp::min(buf.len(), self.writer.block_room());
+        self.writer.write_all(&buf[..amount])?;
+        self.full = self.limit_reached();
+        Ok(amount)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl<F> Drop for RollingWriter<F>
+where
+    F: FnMut(usize) -> PathBuf,
+{
+    fn drop(&mut self) {
+        // Errors can't be reported from drop, call `finish` to observe them.
+        let _ = self.writer.finish_output();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{decompress_all, test::test_input, BGZF_EOF, MAX_BGZF_BLOCK_SIZE};
+
+    #[test]
+    fn test_rolling_writer() {
+        let input = test_input(1_000_000);
+        let dir = tempfile::tempdir().unwrap();
+        let limit = 10_000;
+        let mut writer = RollingWriter::new(
+            |index| dir.path().join(format!("part{}.bgz", index)),
+            CompressionLevel::new(3).unwrap(),
+            Rollover::CompressedBytes(limit),
+        )
+        .unwrap();
+        writer.write_all(&input).unwrap();
+        let paths = writer.finish().unwrap();
+        assert!(paths.len() > 1);
+
+        let mut decompressed = vec![];
+        for path in &paths {
+            let bytes = std::fs::read(path).unwrap();
+            assert!(bytes.ends_with(BGZF_EOF));
+            assert!(bytes.len() as u64 <= limit + (MAX_BGZF_BLOCK_SIZE + BGZF_EOF.len()) as u64);
+            decompressed.extend(decompress_all(&bytes).unwrap());
+        }
+        assert_eq!(decompressed, input);
+    }
+}
@@ -0,0 +1,104 @@
+//! Recovery of the intact blocks of corrupt BGZF data.
+use std::io::{self, Read, Write};
+
+use crate::{
+    validate::{BlockScanner, Scanned},
+    BlockProblem, BGZF_EOF,
+};
+
+/// What [`salvage`] kept and dropped.
+#[derive(Debug)]
+pub struct SalvageReport {
+    /// The number of non-empty blocks copied to the output
+    pub blocks: u64,
+    /// The number of uncompressed bytes in the copied blocks
+    pub uncompressed_bytes: u64,
+    /// Each stretch of the input that was dropped, in order
+    pub dropped: Vec<BlockProblem>,
+}
+
+/// Copy every block of `src` that parses and passes its CRC check to `dst`, followed by a fresh
+/// EOF marker, so that `dst` is valid BGZF data holding everything that could be recovered.
+///
+/// Blocks are copied without being recompressed. Problems are skipped over as by
+/// [`crate::validate`], and reported as the compressed byte ranges that were dropped. Empty blocks
+/// carry no data and are dropped as well, without being reported.
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{decompress_all, salvage, Compressor, BGZF_EOF};
+///
+/// let mut damaged = vec![];
+/// for part in [&b"hello "[..], &b"cruel "[..], &b"world"[..]] {
+///     let mut block = vec![];
+///     Compressor::new(2.try_into().unwrap()).compress(part, &mut block).unwrap();
+///     if part == b"cruel " {
+///         block[20] ^= 0xff;
+///     }
+///     damaged.extend_from_slice(&block);
+/// }
+///
+/// let mut recovered = vec![];
+/// let report = salvage(damaged.as_slice(), &mut recovered).unwrap();
+/// assert_eq!(report.dropped.len(), 1);
+/// assert_eq!(decompress_all(&recovered).unwrap(), b"hello world");
+/// assert!(recovered.ends_with(BGZF_EOF));
+/// ```
+pub fn salvage<R, W>(src: R, mut dst: W) -> io::Result<SalvageReport>
+where
+    R: Read,
+    W: Write,
+{
+    let mut scanner = BlockScanner::new(src);
+    let mut report = SalvageReport { blocks: 0, uncompressed_bytes: 0, dropped: vec![] };
+    while let Some(scanned) = scanner.next_block()? {
+        match scanned {
+            Scanned::Block { uncompressed_size: 0, .. } => (),
+            Scanned::Block { uncompressed_size, .. } => {
+                dst.write_all(scanner.block())?;
+                report.blocks += 1;
+                report.uncompressed_bytes += uncompressed_size as u64;
+            }
+            Scanned::Problem(problem) => report.dropped.push(problem),
+        }
+    }
+    dst.write_all(BGZF_EOF)?;
+    dst.flush()?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        compress_parallel, core::block_size, decompress_all, test::test_input, validate, BgzfError,
+        CompressionLevel, BGZF_BLOCK_SIZE,
+    };
+
+    #[test]
+    fn test_salvage_keeps_intact_blocks() {
+        let input = test_input(200_000);
+        let compressed = compress_parallel(&input, CompressionLevel::new(3).unwrap()).unwrap();
+        let first = block_size(&compressed).unwrap();
+
+        let mut damaged = compressed[..first].to_vec();
+        damaged.extend_from_slice(&[0xaa; 100]);
+        damaged.extend_from_slice(&compressed[first..]);
+        damaged[first + 100 + 30] ^= 0xff;
+        // A second copy of the data, cut off partway through its first block.
+        damaged.extend_from_slice(&compressed[..first - 1]);
+
+        let mut recovered = vec![];
+        let report = salvage(damaged.as_slice(), &mut recovered).unwrap();
+        assert_eq!(report.blocks, 3);
+        assert_eq!(report.dropped.len(), 3);
+        assert!(matches!(report.dropped[2].error, BgzfError::Truncated));
+
+        let mut expected = input[..BGZF_BLOCK_SIZE].to_vec();
+        expected.extend_from_slice(&input[2 * BGZF_BLOCK_SIZE..]);
+        assert_eq!(decompress_all(&recovered).unwrap(), expected);
+        assert!(validate(recovered.as_slice()).unwrap().is_valid());
+        assert_eq!(report.uncompressed_bytes, expected.len() as u64);
+    }
+}
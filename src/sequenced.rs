@@ -0,0 +1,189 @@
+//! Writing chunks from many producer threads to one output, in an order they number.
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+    thread::{self, JoinHandle},
+};
+
+use bytes::Bytes;
+use crossbeam_channel::{bounded, Sender};
+
+/// A chunk of uncompressed data and its place in the output.
+type Sequenced = (u64, Bytes);
+
+/// Funnels chunks from any number of producer threads into a single writer, such as a
+/// [`crate::Writer`] or [`crate::ParallelWriter`], on a background thread.
+///
+/// Each producer gets a [`SequencedSender`] and numbers the chunks it sends. The chunks are
+/// written in order of their sequence numbers, starting from zero, however they arrive. Up to
+/// `capacity` chunks are queued for the background thread before [`SequencedSender::send`]
+/// blocks; chunks that arrive ahead of their turn wait in memory until the gap before them is
+/// filled.
+///
+/// A repeated sequence number, or a gap left when every sender has been dropped, is an error.
+/// Errors stop the background thread, so later sends fail, and are reported by
+/// [`SequencedWriter::finish`].
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{decompress_all, SequencedWriter, Writer};
+/// use std::thread;
+///
+/// let writer = SequencedWriter::new(Writer::new(vec![], 2.try_into().unwrap()), 16);
+/// let producers: Vec<_> = (0..4_u64)
+///     .map(|i| {
+///         let sender = writer.sender();
+///         thread::spawn(move || sender.send(3 - i, format!("chunk {}\n", 3 - i)).unwrap())
+///     })
+///     .collect();
+/// for producer in producers {
+///     producer.join().unwrap();
+/// }
+///
+/// let (compressed, _) = writer.finish().unwrap().finish().unwrap();
+/// let text = decompress_all(&compressed).unwrap();
+/// assert_eq!(text, b"chunk 0\nchunk 1\nchunk 2\nchunk 3\n");
+/// ```
+pub struct SequencedWriter<W> {
+    /// The queue of chunks to the background thread
+    sender: Option<Sender<Sequenced>>,
+    /// The background thread, which returns the writer once every sender is dropped
+    handle: Option<JoinHandle<io::Result<W>>>,
+}
+
+impl<W> SequencedWriter<W>
+where
+    W: Write + Send + 'static,
+{
+    /// Spawn a thread writing chunks to `writer` in sequence, with up to `capacity` chunks queued
+    /// for it.
+    pub fn new(writer: W, capacity: usize) -> Self {
+        let (sender, receiver) = bounded::<Sequenced>(capacity);
+        let handle = thread::Builder::new()
+            .name("bgzf-sequencer".to_string())
+            .spawn(move || {
+                let mut writer = writer;
+                let mut next = 0;
+                let mut waiting = BTreeMap::new();
+                // Stop at the first error; the closed queue makes later sends fail.
+                for (seq, chunk) in receiver {
+                    if seq < next || waiting.insert(seq, chunk).is_some() {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            format!("Chunk sequence number {} was sent more than once", seq),
+                        ));
+                    }
+                    while let Some(chunk) = waiting.remove(&next) {
+                        writer.write_all(&chunk)?;
+                        next += 1;
+                    }
+                }
+                if let Some(seq) = waiting.keys().next() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        format!("Chunk {} was never sent, but chunk {} was", next, seq),
+                    ));
+                }
+                writer.flush()?;
+                Ok(writer)
+            })
+            .expect("Failed to spawn bgzf sequencer thread");
+        Self { sender: Some(sender), handle: Some(handle) }
+    }
+
+    /// A new handle for a producer to send chunks with.
+    pub fn sender(&self) -> SequencedSender {
+        SequencedSender { sender: self.sender.clone().expect("sender is only taken by finish") }
+    }
+
+    /// Wait for every [`SequencedSender`] to be dropped and every chunk to be written, then flush
+    /// and return the writer.
+    ///
+    /// This blocks until all senders are dropped, so drop or join the producers first.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.sender = None;
+        match self.handle.take() {
+            Some(handle) => handle.join().unwrap_or_else(|_| {
+                Err(io::Error::new(io::ErrorKind::Other, "bgzf sequencer thread panicked"))
+            }),
+            None => unreachable!("handle is only taken by finish"),
+        }
+    }
+}
+
+impl<W> Drop for SequencedWriter<W> {
+    fn drop(&mut self) {
+        // Errors can't be reported from drop, call `finish` to observe them.
+        self.sender = None;
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// A cloneable handle for sending numbered chunks to a [`SequencedWriter`].
+#[derive(Debug, Clone)]
+pub struct SequencedSender {
+    sender: Sender<Sequenced>,
+}
+
+impl SequencedSender {
+    /// Send `chunk` to be written as the `seq`th chunk of the output, counting from zero.
+    ///
+    /// Blocks while the queue is full. Fails if the [`SequencedWriter`]'s background thread has
+    /// stopped on an error, which [`SequencedWriter::finish`] returns.
+    pub fn send<B>(&self, seq: u64, chunk: B) -> io::Result<()>
+    where
+        B: Into<Bytes>,
+    {
+        self.sender.send((seq, chunk.into())).map_err(|_| {
+            io::Error::new(io::ErrorKind::BrokenPipe, "bgzf sequencer thread has exited")
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{compress_parallel, test::test_input, CompressionLevel, ParallelWriter};
+
+    #[test]
+    fn test_sequenced_writer() {
+        let input = test_input(1_000_000);
+        let input = bytes::Bytes::from(input);
+        let level = CompressionLevel::new(3).unwrap();
+        let chunks: Vec<_> = (0..input.len()).step_by(10_000).collect();
+
+        let writer = SequencedWriter::new(ParallelWriter::new(vec![], level), 4);
+        let producers: Vec<_> = (0..4)
+            .map(|producer| {
+                let sender = writer.sender();
+                let input = input.clone();
+                let chunks = chunks.clone();
+                std::thread::spawn(move || {
+                    for (seq, &start) in chunks.iter().enumerate().skip(producer).step_by(4) {
+                        let end = std::cmp::min(start + 10_000, input.len());
+                        sender.send(seq as u64, input.slice(start..end)).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for producer in producers {
+            producer.join().unwrap();
+        }
+        let compressed = writer.finish().unwrap().finish().unwrap();
+        assert_eq!(compressed, compress_parallel(&input, level).unwrap());
+
+        let writer = SequencedWriter::new(vec![], 4);
+        let sender = writer.sender();
+        sender.send(0, &b"a"[..]).unwrap();
+        sender.send(0, &b"b"[..]).unwrap();
+        drop(sender);
+        assert_eq!(writer.finish().unwrap_err().kind(), io::ErrorKind::InvalidInput);
+
+        let writer = SequencedWriter::new(vec![], 4);
+        writer.sender().send(1, &b"b"[..]).unwrap();
+        assert_eq!(writer.finish().unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
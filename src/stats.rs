@@ -0,0 +1,231 @@
+//! Distributions of block sizes and compression ratios across a BGZF file.
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use crate::{
+    read_header, BlockFooter, BlockHeader, BGZF_FOOTER_SIZE, BGZF_HEADER_SIZE, MAX_BGZF_BLOCK_SIZE,
+};
+
+/// The number of buckets in each [`BlockStats`] histogram.
+const BUCKETS: usize = 16;
+
+/// A histogram of values in equal width buckets, the last of which also holds everything larger.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    bucket_width: f64,
+    counts: Vec<u64>,
+    count: u64,
+    sum: f64,
+    min: f64,
+    max: f64,
+}
+
+impl Histogram {
+    /// An empty histogram of `buckets` buckets, each `bucket_width` wide, starting from zero.
+    fn new(bucket_width: f64, buckets: usize) -> Self {
+        Self {
+            bucket_width,
+            counts: vec![0; buckets],
+            count: 0,
+            sum: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+
+    /// Count `value`, which must not be negative.
+    fn add(&mut self, value: f64) {
+        let bucket = ((value / self.bucket_width) as usize).min(self.counts.len() - 1);
+        self.counts[bucket] += 1;
+        self.count += 1;
+        self.sum += value;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    /// The start of each bucket, from zero, and the number of values in it.
+    pub fn buckets(&self) -> impl Iterator<Item = (f64, u64)> + '_ {
+        self.counts.iter().enumerate().map(|(i, &count)| (i as f64 * self.bucket_width, count))
+    }
+
+    /// The width of every bucket but the last.
+    pub fn bucket_width(&self) -> f64 {
+        self.bucket_width
+    }
+
+    /// The number of values counted.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// The smallest value, or `None` if there are none.
+    pub fn min(&self) -> Option<f64> {
+        (self.count > 0).then_some(self.min)
+    }
+
+    /// The largest value, or `None` if there are none.
+    pub fn max(&self) -> Option<f64> {
+        (self.count > 0).then_some(self.max)
+    }
+
+    /// The mean of the values, or `None` if there are none.
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum / self.count as f64)
+    }
+}
+
+/// Totals and distributions of the block sizes and compression ratios of a BGZF file.
+///
+/// This helps choose compression levels and block sizes, and spot producers that write
+/// pathological blocks, such as many tiny ones. The histograms count data blocks only; empty
+/// blocks, such as the EOF marker and flush markers, are only counted in
+/// [`BlockStats::empty_blocks`].
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{BlockStats, Writer};
+/// use std::io::{Cursor, Write};
+///
+/// let mut writer = Writer::new(vec![], 6.try_into().unwrap());
+/// writer.write_all(&b"ACGT".repeat(100_000)).unwrap();
+/// let (compressed, _) = writer.finish().unwrap();
+///
+/// let stats = BlockStats::from_reader(Cursor::new(compressed)).unwrap();
+/// assert_eq!((stats.blocks, stats.empty_blocks), (7, 1));
+/// assert_eq!(stats.uncompressed_bytes, 400_000);
+/// assert!(stats.ratio().unwrap() > 10.0);
+/// assert_eq!(stats.uncompressed_size.max(), Some(65_280.0));
+/// assert_eq!(stats.ratio.count(), 7);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockStats {
+    /// The number of blocks holding data
+    pub blocks: u64,
+    /// The number of empty blocks, including the EOF marker
+    pub empty_blocks: u64,
+    /// The total size of all blocks, including headers and footers
+    pub compressed_bytes: u64,
+    /// The total size of the uncompressed data
+    pub uncompressed_bytes: u64,
+    /// The whole sizes of data blocks, in buckets of 4 KiB
+    pub compressed_size: Histogram,
+    /// The uncompressed sizes of data blocks, in buckets of 4 KiB
+    pub uncompressed_size: Histogram,
+    /// The ratios of uncompressed to whole block size of data blocks, in buckets of one
+    pub ratio: Histogram,
+}
+
+impl Default for BlockStats {
+    fn default() -> Self {
+        let size_bucket = (MAX_BGZF_BLOCK_SIZE / BUCKETS) as f64;
+        Self {
+            blocks: 0,
+            empty_blocks: 0,
+            compressed_bytes: 0,
+            uncompressed_bytes: 0,
+            compressed_size: Histogram::new(size_bucket, BUCKETS),
+            uncompressed_size: Histogram::new(size_bucket, BUCKETS),
+            ratio: Histogram::new(1.0, BUCKETS),
+        }
+    }
+}
+
+impl BlockStats {
+    /// Gather the statistics of the BGZF file at `path` from its block headers and footers.
+    pub fn from_path<P>(path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Self::from_reader(File::open(path)?)
+    }
+
+    /// Gather the statistics of the BGZF data in `reader` from its block headers and footers.
+    ///
+    /// The compressed data is skipped over and nothing is decompressed.
+    pub fn from_reader<R>(mut reader: R) -> io::Result<Self>
+    where
+        R: Read + Seek,
+    {
+        let mut stats = Self::default();
+        let mut header = [0; BGZF_HEADER_SIZE];
+        loop {
+            match read_header(&mut reader, &mut header)? {
+                0 => break,
+                BGZF_HEADER_SIZE => {}
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "Truncated BGZF block",
+                    ))
+                }
+            }
+            let size = BlockHeader::parse(&header)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+                .block_size();
+            reader.seek(SeekFrom::Current((size - BGZF_HEADER_SIZE - BGZF_FOOTER_SIZE) as i64))?;
+            let footer = BlockFooter::read_from(&mut reader)?;
+            stats.add(size, footer.uncompressed_size as usize);
+        }
+        Ok(stats)
+    }
+
+    /// Count a block of `size` bytes holding `uncompressed_size` bytes of data.
+    fn add(&mut self, size: usize, uncompressed_size: usize) {
+        self.compressed_bytes += size as u64;
+        self.uncompressed_bytes += uncompressed_size as u64;
+        if uncompressed_size == 0 {
+            self.empty_blocks += 1;
+            return;
+        }
+        self.blocks += 1;
+        self.compressed_size.add(size as f64);
+        self.uncompressed_size.add(uncompressed_size as f64);
+        self.ratio.add(uncompressed_size as f64 / size as f64);
+    }
+
+    /// The ratio of uncompressed to compressed bytes over the whole file, or `None` if it is
+    /// empty.
+    pub fn ratio(&self) -> Option<f64> {
+        (self.compressed_bytes > 0)
+            .then(|| self.uncompressed_bytes as f64 / self.compressed_bytes as f64)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{compress_parallel, test::test_input, CompressionLevel, Index, BGZF_EOF};
+
+    #[test]
+    fn test_block_stats() {
+        let input = test_input(1_000_000);
+        let compressed = compress_parallel(&input, CompressionLevel::new(3).unwrap()).unwrap();
+        let stats = BlockStats::from_reader(std::io::Cursor::new(&compressed)).unwrap();
+        let index = Index::from_reader(std::io::Cursor::new(&compressed)).unwrap();
+
+        assert_eq!(stats.blocks as usize, index.entries().len());
+        assert_eq!(stats.empty_blocks, 1);
+        assert_eq!(stats.compressed_bytes as usize, compressed.len());
+        assert_eq!(stats.uncompressed_bytes as usize, input.len());
+        for histogram in [&stats.compressed_size, &stats.uncompressed_size, &stats.ratio] {
+            assert_eq!(histogram.count(), stats.blocks);
+            assert_eq!(histogram.buckets().map(|(_, count)| count).sum::<u64>(), stats.blocks);
+        }
+        let ends: Vec<u64> = index.entries().iter().map(|e| e.uncompressed_offset).collect();
+        let last_size = ends[ends.len() - 1] - ends[ends.len() - 2];
+        assert_eq!(stats.uncompressed_size.min(), Some(last_size as f64));
+        assert!(stats.ratio.min().unwrap() <= stats.ratio.mean().unwrap());
+
+        let empty = BlockStats::from_reader(std::io::Cursor::new(BGZF_EOF)).unwrap();
+        assert_eq!((empty.blocks, empty.empty_blocks), (0, 1));
+        assert_eq!(empty.ratio.mean(), None);
+
+        let mut truncated = compressed.clone();
+        truncated.truncate(compressed.len() / 2);
+        assert!(BlockStats::from_reader(std::io::Cursor::new(truncated)).is_err());
+    }
+}
@@ -0,0 +1,122 @@
+//! Pluggable storage for compressed blocks.
+//!
+//! [`IndexedReader`](crate::IndexedReader) fetches blocks through a [`BlockSource`], and
+//! [`Writer`](crate::Writer) emits them through a [`BlockSink`]. Both traits are implemented for
+//! every [`Read`] + [`Seek`] and [`Write`] type, and can be implemented for other backends such as
+//! HTTP range requests, chunk stores, or storage that is encrypted at rest.
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use crate::{core::block_size, BGZF_HEADER_SIZE};
+
+/// Somewhere whole compressed blocks can be fetched from by their compressed offset.
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{BlockSource, Index, IndexedReader, TeeWriter};
+/// use std::collections::HashMap;
+/// use std::io::{self, Read, Write};
+///
+/// /// Blocks kept in a map, as a chunk store might.
+/// struct Chunks(HashMap<u64, Vec<u8>>);
+///
+/// impl BlockSource for Chunks {
+///     fn fetch_block(&mut self, offset: u64, buf: &mut Vec<u8>) -> io::Result<()> {
+///         let block = self.0.get(&offset).ok_or(io::ErrorKind::NotFound)?;
+///         buf.clear();
+///         buf.extend_from_slice(block);
+///         Ok(())
+///     }
+/// }
+///
+/// let (mut data, mut gzi) = (vec![], vec![]);
+/// let mut writer = TeeWriter::new(&mut data, &mut gzi, 2.try_into().unwrap());
+/// writer.write_all(b"hello world").unwrap();
+/// writer.finish().unwrap();
+///
+/// let chunks = Chunks(HashMap::from([(0, data)]));
+/// let mut reader = IndexedReader::new(chunks, Index::read_gzi(gzi.as_slice()).unwrap());
+/// let mut decompressed = String::new();
+/// reader.read_to_string(&mut decompressed).unwrap();
+/// assert_eq!(decompressed, "hello world");
+/// ```
+pub trait BlockSource {
+    /// Replace the contents of `buf` with the whole block, header and footer included, that starts
+    /// at compressed `offset`.
+    ///
+    /// Extra bytes after the block are allowed, as the block's header gives its size.
+    fn fetch_block(&mut self, offset: u64, buf: &mut Vec<u8>) -> io::Result<()>;
+}
+
+impl<R> BlockSource for R
+where
+    R: Read + Seek + ?Sized,
+{
+    fn fetch_block(&mut self, offset: u64, buf: &mut Vec<u8>) -> io::Result<()> {
+        self.seek(SeekFrom::Start(offset))?;
+        buf.resize(BGZF_HEADER_SIZE, 0);
+        self.read_exact(buf)?;
+        let size = block_size(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        buf.resize(size, 0);
+        self.read_exact(&mut buf[BGZF_HEADER_SIZE..])
+    }
+}
+
+/// Somewhere compressed blocks are emitted to, one whole block at a time and in order.
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{BlockSink, Writer, BGZF_EOF};
+/// use std::io::{self, Write};
+///
+/// /// Each block kept separately, as a chunk store might.
+/// #[derive(Default)]
+/// struct Chunks(Vec<Vec<u8>>);
+///
+/// impl BlockSink for Chunks {
+///     fn emit_block(&mut self, block: &[u8]) -> io::Result<()> {
+///         self.0.push(block.to_vec());
+///         Ok(())
+///     }
+///
+///     fn flush_blocks(&mut self) -> io::Result<()> {
+///         Ok(())
+///     }
+/// }
+///
+/// let mut writer = Writer::new(Chunks::default(), 2.try_into().unwrap());
+/// writer.write_all(b"hello world").unwrap();
+/// let (chunks, _) = writer.finish().unwrap();
+/// assert_eq!(chunks.0.len(), 2);
+/// assert_eq!(chunks.0[1], BGZF_EOF);
+/// ```
+pub trait BlockSink {
+    /// Store `block`, which is a whole block with header and footer, possibly the EOF marker.
+    fn emit_block(&mut self, block: &[u8]) -> io::Result<()>;
+
+    /// Make sure the blocks emitted so far have reached the underlying storage.
+    fn flush_blocks(&mut self) -> io::Result<()>;
+
+    /// Complete the output, once [`Writer::finish`](crate::Writer::finish) has emitted the last
+    /// block and flushed it.
+    ///
+    /// Sinks that only make their output visible once it is whole, such as
+    /// [`AtomicFile`](crate::AtomicFile), do so here. Does nothing by default.
+    fn finish_blocks(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<W> BlockSink for W
+where
+    W: Write + ?Sized,
+{
+    fn emit_block(&mut self, block: &[u8]) -> io::Result<()> {
+        self.write_all(block)
+    }
+
+    fn flush_blocks(&mut self) -> io::Result<()> {
+        self.flush()
+    }
+}
@@ -0,0 +1,58 @@
+//! Digests of all of the uncompressed data passing through a reader or writer.
+
+/// A digest, such as MD5 or SHA-256, of a stream of uncompressed data.
+///
+/// Register one with [`Writer::set_digest`](crate::Writer::set_digest) or
+/// [`Reader::set_digest`](crate::Reader::set_digest) to digest the data as it is written or read,
+/// rather than in a second pass over the file. With the `digest` feature, every
+/// RustCrypto hash implementing `digest::Digest`, such as `md5::Md5` or `sha2::Sha256`,
+/// implements this trait.
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{StreamDigest, Writer};
+/// use std::io::Write;
+///
+/// /// The 64-bit FNV-1a hash.
+/// struct Fnv(u64);
+///
+/// impl StreamDigest for Fnv {
+///     fn update(&mut self, data: &[u8]) {
+///         for &byte in data {
+///             self.0 = (self.0 ^ byte as u64).wrapping_mul(0x100000001b3);
+///         }
+///     }
+///
+///     fn finish(&mut self) -> Vec<u8> {
+///         std::mem::replace(&mut self.0, 0xcbf29ce484222325).to_be_bytes().to_vec()
+///     }
+/// }
+///
+/// let mut writer = Writer::new(vec![], 2.try_into().unwrap());
+/// writer.set_digest(Fnv(0xcbf29ce484222325));
+/// writer.write_all(b"hello world").unwrap();
+/// let (_, summary) = writer.finish().unwrap();
+/// assert_eq!(summary.digest.unwrap(), 0x779a65e7023cd2e7_u64.to_be_bytes());
+/// ```
+pub trait StreamDigest: Send {
+    /// Add `data` to the digest.
+    fn update(&mut self, data: &[u8]);
+
+    /// Return the digest of all of the data added, and start over.
+    fn finish(&mut self) -> Vec<u8>;
+}
+
+#[cfg(feature = "digest")]
+impl<D> StreamDigest for D
+where
+    D: digest::Digest + Send,
+{
+    fn update(&mut self, data: &[u8]) {
+        digest::Digest::update(self, data);
+    }
+
+    fn finish(&mut self) -> Vec<u8> {
+        std::mem::replace(self, D::new()).finalize().to_vec()
+    }
+}
@@ -0,0 +1,132 @@
+//! A writer that produces a BGZF stream and its index in a single pass.
+use std::io::{self, Write};
+
+use crate::{BlockSink, CompressionLevel, Writer};
+
+/// A BGZF writer that also writes the `.gzi` [`crate::Index`] of the blocks it produces.
+///
+/// A `.gzi` file starts with its number of entries, so the index is collected as blocks are
+/// written (16 bytes per block) and written to its sink by [`TeeWriter::finish`].
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{Index, TeeWriter};
+/// use std::io::Write;
+///
+/// let (mut data, mut gzi) = (vec![], vec![]);
+/// let mut writer = TeeWriter::new(&mut data, &mut gzi, 2.try_into().unwrap());
+/// writer.write_all(&vec![b'A'; 100_000]).unwrap();
+/// writer.finish().unwrap();
+///
+/// let index = Index::read_gzi(gzi.as_slice()).unwrap();
+/// assert_eq!(index.entries().len(), 2);
+/// ```
+pub struct TeeWriter<W, I>
+where
+    W: BlockSink,
+    I: Write,
+{
+    writer: Writer<W>,
+    /// The sink for the index, taken by `finish`
+    index_sink: Option<I>,
+}
+
+impl<W, I> TeeWriter<W, I>
+where
+    W: BlockSink,
+    I: Write,
+{
+    /// Create a [`TeeWriter`] writing BGZF data to `writer` and its `.gzi` index to `index_sink`.
+    pub fn new(writer: W, index_sink: I, compression_level: CompressionLevel) -> Self {
+        let mut writer = Writer::new(writer, compression_level);
+        writer.build_index();
+        Self { writer, index_sink: Some(index_sink) }
+    }
+
+    /// Write out all buffered data, the EOF marker, and the index.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.finish_inner()
+    }
+
+    fn finish_inner(&mut self) -> io::Result<()> {
+        self.writer.finish_output()?;
+        if let (Some(mut sink), Some(index)) = (self.index_sink.take(), self.writer.take_index()) {
+            index.write_gzi(&mut sink)?;
+            sink.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl<W, I> Write for TeeWriter<W, I>
+where
+    W: BlockSink,
+    I: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl<W, I> Drop for TeeWriter<W, I>
+where
+    W: BlockSink,
+    I: Write,
+{
+    fn drop(&mut self) {
+        // Errors can't be reported from drop, call `finish` to observe them.
+        let _ = self.finish_inner();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::File;
+
+    use super::*;
+    use crate::{decompress_all, test::test_input, Index, BGZF_BLOCK_SIZE, BGZF_EOF};
+
+    #[test]
+    fn test_tee_writer_index() {
+        let input = test_input(1_000_000);
+        let dir = tempfile::tempdir().unwrap();
+        let data_path = dir.path().join("test.bgz");
+        let gzi_path = dir.path().join("test.bgz.gzi");
+
+        let mut writer = TeeWriter::new(
+            File::create(&data_path).unwrap(),
+            File::create(&gzi_path).unwrap(),
+            CompressionLevel::new(3).unwrap(),
+        );
+        writer.write_all(&input).unwrap();
+        writer.finish().unwrap();
+
+        let compressed = std::fs::read(&data_path).unwrap();
+        let index = Index::read_gzi(File::open(&gzi_path).unwrap()).unwrap();
+        assert_eq!(Index::from_path(&data_path).unwrap(), index);
+        let entries = index.entries();
+        assert_eq!(entries.len(), (input.len() + BGZF_BLOCK_SIZE - 1) / BGZF_BLOCK_SIZE);
+        assert_eq!(entries.last().unwrap().uncompressed_offset, input.len() as u64);
+        assert_eq!(
+            entries.last().unwrap().compressed_offset,
+            (compressed.len() - BGZF_EOF.len()) as u64
+        );
+        // Every entry is the start of a block holding the data at that offset.
+        for entry in &entries[..entries.len() - 1] {
+            let block = decompress_all(
+                &compressed[entry.compressed_offset as usize..compressed.len() - BGZF_EOF.len()],
+            )
+            .unwrap();
+            assert_eq!(block, &input[entry.uncompressed_offset as usize..]);
+        }
+
+        let mut gzi = vec![];
+        index.write_gzi(&mut gzi).unwrap();
+        assert_eq!(gzi, std::fs::read(&gzi_path).unwrap());
+    }
+}
@@ -0,0 +1,157 @@
+//! Line-oriented reading of BGZF compressed text such as VCF, BED, and GFF.
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Read},
+    path::Path,
+};
+
+use crate::Reader;
+
+/// A reader that yields the lines of text from a [`BufRead`], such as a buffered [`Reader`].
+///
+/// Lines are split on `\n`, with a trailing `\r` removed as well, and must be valid UTF-8. A line
+/// longer than the maximum line length is an error rather than an unbounded allocation, and is
+/// skipped so that reading can carry on with the next line.
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{text::LineReader, Reader, Writer};
+/// use std::io::{BufReader, Write};
+///
+/// let mut compressed = vec![];
+/// let mut writer = Writer::new(&mut compressed, 2.try_into().unwrap());
+/// writer.write_all(b"#header\nchr1\t100\r\nchr2\t200").unwrap();
+/// drop(writer);
+///
+/// let mut lines = LineReader::new(BufReader::new(Reader::new(compressed.as_slice())));
+/// assert_eq!(lines.next_line().unwrap().unwrap(), "#header");
+/// let rest: Vec<String> = lines.collect::<Result<_, _>>().unwrap();
+/// assert_eq!(rest, ["chr1\t100", "chr2\t200"]);
+/// ```
+pub struct LineReader<R> {
+    reader: R,
+    /// The raw bytes of the current line
+    buffer: Vec<u8>,
+    max_line_length: usize,
+}
+
+impl<R> LineReader<R>
+where
+    R: BufRead,
+{
+    /// Create a [`LineReader`] over `reader` with no limit on the length of a line.
+    pub fn new(reader: R) -> Self {
+        Self { reader, buffer: Vec::new(), max_line_length: usize::MAX }
+    }
+
+    /// Fail on any line longer than `max_line_length` bytes, not counting the line ending.
+    pub fn set_max_line_length(&mut self, max_line_length: usize) {
+        self.max_line_length = max_line_length;
+    }
+
+    /// Read the next line without allocating, or `None` at the end of the input.
+    pub fn next_line(&mut self) -> Option<io::Result<&str>> {
+        self.buffer.clear();
+        // Allow for the line ending on top of the longest line.
+        let limit = (self.max_line_length as u64).saturating_add(2);
+        match self.reader.by_ref().take(limit).read_until(b'\n', &mut self.buffer) {
+            Ok(0) => return None,
+            Ok(_) => (),
+            Err(e) => return Some(Err(e)),
+        }
+
+        let mut len = self.buffer.len();
+        for ending in [b'\n', b'\r'] {
+            if len > 0 && self.buffer[len - 1] == ending {
+                len -= 1;
+            }
+        }
+        if len > self.max_line_length {
+            if !self.buffer.ends_with(b"\n") {
+                if let Err(e) = self.skip_line() {
+                    return Some(Err(e));
+                }
+            }
+            return Some(Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Line is longer than the maximum of {} bytes", self.max_line_length),
+            )));
+        }
+        Some(
+            std::str::from_utf8(&self.buffer[..len])
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        )
+    }
+
+    /// Discard the rest of the current line, including its line ending.
+    fn skip_line(&mut self) -> io::Result<()> {
+        loop {
+            let available = self.reader.fill_buf()?;
+            if available.is_empty() {
+                return Ok(());
+            }
+            match available.iter().position(|&b| b == b'\n') {
+                Some(end) => {
+                    self.reader.consume(end + 1);
+                    return Ok(());
+                }
+                None => {
+                    let len = available.len();
+                    self.reader.consume(len);
+                }
+            }
+        }
+    }
+
+    /// Unwrap this [`LineReader`], returning the inner reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl LineReader<BufReader<Reader<File>>> {
+    /// Open the BGZF compressed text file at `path`.
+    pub fn from_path<P>(path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Ok(Self::new(BufReader::new(Reader::from_path(path)?)))
+    }
+}
+
+impl<R> Iterator for LineReader<R>
+where
+    R: BufRead,
+{
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_line().map(|line| line.map(str::to_owned))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_max_line_length() {
+        let mut lines = LineReader::new(&b"1234\r\n12345\n123456\n1234567890\n1234"[..]);
+        lines.set_max_line_length(5);
+        assert_eq!(lines.next_line().unwrap().unwrap(), "1234");
+        assert_eq!(lines.next_line().unwrap().unwrap(), "12345");
+        assert_eq!(lines.next_line().unwrap().unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(lines.next_line().unwrap().unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(lines.next_line().unwrap().unwrap(), "1234");
+        assert!(lines.next_line().is_none());
+    }
+
+    #[test]
+    fn test_invalid_utf8() {
+        let mut lines = LineReader::new(&b"ok\n\xff\xfe\n"[..]);
+        assert_eq!(lines.next().unwrap().unwrap(), "ok");
+        assert!(lines.next().unwrap().is_err());
+        assert!(lines.next().is_none());
+    }
+}
@@ -0,0 +1,324 @@
+//! Tokio adapters that run the blocking [`Reader`] and [`Writer`] on tokio's blocking thread pool.
+use std::{
+    future::Future,
+    io::{self, Read, Write},
+    mem,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    task::{spawn_blocking, JoinError, JoinHandle},
+};
+
+use crate::{Reader, Summary, Writer, BUFSIZE};
+
+/// The error for a blocking task that panicked.
+fn join_error(error: JoinError) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, error)
+}
+
+/// The error for using a reader or writer whose blocking task panicked.
+fn lost_inner() -> io::Error {
+    io::Error::new(io::ErrorKind::Other, "The inner reader or writer was lost to a panic")
+}
+
+/// The result of a blocking read: the reader, the buffer read into, and how much was read.
+type ReadTask<R> = JoinHandle<(Reader<R>, Vec<u8>, io::Result<usize>)>;
+
+/// A [`tokio::io::AsyncRead`] over a BGZF [`Reader`], whose reads and decompression run on the
+/// blocking thread pool with [`tokio::task::spawn_blocking`].
+///
+/// Decompressed data is read in chunks of up to [`BUFSIZE`] bytes. Reading returns no bytes once
+/// the end of the data is reached, and errors from the [`Reader`] are passed through.
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{AsyncBridgeReader, Reader, Writer};
+/// use std::io::{Cursor, Write};
+/// use tokio::io::AsyncReadExt;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let mut writer = Writer::new(vec![], 2.try_into().unwrap());
+/// writer.write_all(b"hello world").unwrap();
+/// let (compressed, _) = writer.finish().unwrap();
+///
+/// let mut reader = AsyncBridgeReader::new(Reader::new(Cursor::new(compressed)));
+/// let mut decompressed = String::new();
+/// reader.read_to_string(&mut decompressed).await.unwrap();
+/// assert_eq!(decompressed, "hello world");
+/// # });
+/// ```
+pub struct AsyncBridgeReader<R> {
+    /// The reader, while no read is in flight
+    reader: Option<Reader<R>>,
+    /// The read in flight, if any
+    task: Option<ReadTask<R>>,
+    /// Decompressed data not yet returned
+    buffer: Vec<u8>,
+    /// How much of `buffer` has been returned
+    pos: usize,
+}
+
+impl<R> AsyncBridgeReader<R>
+where
+    R: Read + Send + 'static,
+{
+    /// Create an [`AsyncBridgeReader`] that reads from `reader`.
+    pub fn new(reader: Reader<R>) -> Self {
+        Self { reader: Some(reader), task: None, buffer: Vec::new(), pos: 0 }
+    }
+
+    /// Wait for any read in flight and return the [`Reader`].
+    ///
+    /// Decompressed data read ahead but not yet returned is lost.
+    pub async fn into_inner(mut self) -> io::Result<Reader<R>> {
+        if let Some(task) = self.task.take() {
+            let (reader, _, _) = task.await.map_err(join_error)?;
+            return Ok(reader);
+        }
+        self.reader.take().ok_or_else(lost_inner)
+    }
+}
+
+// The reader is never pinned, it only moves in and out of blocking tasks.
+impl<R> Unpin for AsyncBridgeReader<R> {}
+
+impl<R> AsyncRead for AsyncBridgeReader<R>
+where
+    R: Read + Send + 'static,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            if this.pos < this.buffer.len() {
+                let amount = std::cmp::min(buf.remaining(), this.buffer.len() - this.pos);
+                buf.put_slice(&this.buffer[this.pos..this.pos + amount]);
+                this.pos += amount;
+                return Poll::Ready(Ok(()));
+            }
+
+            if let Some(task) = this.task.as_mut() {
+                let result = ready!(Pin::new(task).poll(cx));
+                this.task = None;
+                let (reader, buffer, read) = result.map_err(join_error)?;
+                this.reader = Some(reader);
+                this.buffer = buffer;
+                this.pos = 0;
+                let read = read?;
+                this.buffer.truncate(read);
+                if read == 0 {
+                    return Poll::Ready(Ok(()));
+                }
+                continue;
+            }
+
+            let mut reader = this.reader.take().ok_or_else(lost_inner)?;
+            let mut buffer = mem::take(&mut this.buffer);
+            buffer.resize(BUFSIZE, 0);
+            this.task = Some(spawn_blocking(move || {
+                let read = reader.read(&mut buffer);
+                (reader, buffer, read)
+            }));
+        }
+    }
+}
+
+/// The blocking operations an [`AsyncBridgeWriter`] runs.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum WriteOp {
+    Write,
+    Flush,
+    Shutdown,
+}
+
+/// The result of a blocking operation: the writer, the spare buffer, and the outcome.
+type WriteTask<W> = JoinHandle<(Writer<W>, Vec<u8>, io::Result<()>)>;
+
+/// A [`tokio::io::AsyncWrite`] over a BGZF [`Writer`], whose compression and writes run on the
+/// blocking thread pool with [`tokio::task::spawn_blocking`].
+///
+/// Like [`tokio::fs::File`], a write returns once its data has been handed to a blocking task, so
+/// an error is reported by the next call. Flushing flushes the [`Writer`]. Shutting down writes
+/// the last partial block and the EOF marker with [`Writer::write_eof`], and
+/// [`AsyncBridgeWriter::finish`] then returns the inner writer.
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{decompress_all, AsyncBridgeWriter, Writer};
+/// use tokio::io::AsyncWriteExt;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let mut writer = AsyncBridgeWriter::new(Writer::new(vec![], 2.try_into().unwrap()));
+/// writer.write_all(b"hello world").await.unwrap();
+/// writer.shutdown().await.unwrap();
+/// let (compressed, summary) = writer.finish().await.unwrap();
+/// assert_eq!(decompress_all(&compressed).unwrap(), b"hello world");
+/// assert_eq!(summary.compressed_bytes, compressed.len() as u64);
+/// # });
+/// ```
+pub struct AsyncBridgeWriter<W>
+where
+    W: Write + Send + 'static,
+{
+    /// The writer, while no operation is in flight
+    writer: Option<Writer<W>>,
+    /// The operation in flight, if any
+    task: Option<(WriteOp, WriteTask<W>)>,
+    /// The buffer to copy the next write into
+    spare: Vec<u8>,
+}
+
+impl<W> AsyncBridgeWriter<W>
+where
+    W: Write + Send + 'static,
+{
+    /// Create an [`AsyncBridgeWriter`] that writes to `writer`.
+    pub fn new(writer: Writer<W>) -> Self {
+        Self { writer: Some(writer), task: None, spare: Vec::new() }
+    }
+
+    /// Wait for the operation in flight, if any, returning which it was.
+    fn poll_idle(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<Option<WriteOp>>> {
+        let Some((op, task)) = self.task.as_mut() else {
+            return Poll::Ready(Ok(None));
+        };
+        let op = *op;
+        let result = ready!(Pin::new(task).poll(cx));
+        self.task = None;
+        let (writer, spare, outcome) = result.map_err(join_error)?;
+        self.writer = Some(writer);
+        self.spare = spare;
+        Poll::Ready(outcome.map(|()| Some(op)))
+    }
+
+    /// Start `op` on the blocking thread pool, writing `data` for [`WriteOp::Write`].
+    fn start(&mut self, op: WriteOp, data: &[u8]) -> io::Result<()> {
+        let mut writer = self.writer.take().ok_or_else(lost_inner)?;
+        let mut buffer = mem::take(&mut self.spare);
+        buffer.clear();
+        buffer.extend_from_slice(data);
+        let task = spawn_blocking(move || {
+            let outcome = match op {
+                WriteOp::Write => writer.write_all(&buffer),
+                WriteOp::Flush => writer.flush(),
+                WriteOp::Shutdown => writer.write_eof(),
+            };
+            (writer, buffer, outcome)
+        });
+        self.task = Some((op, task));
+        Ok(())
+    }
+
+    /// Run `op` to completion, unless it was the operation already in flight.
+    fn poll_run(&mut self, cx: &mut Context<'_>, op: WriteOp) -> Poll<io::Result<()>> {
+        loop {
+            if ready!(self.poll_idle(cx))? == Some(op) {
+                return Poll::Ready(Ok(()));
+            }
+            self.start(op, &[])?;
+        }
+    }
+
+    /// Wait for any operation in flight, then finish the [`Writer`] on the blocking thread pool
+    /// and return the inner writer along with the [`Summary`].
+    ///
+    /// This writes the EOF marker if [`tokio::io::AsyncWriteExt::shutdown`] has not already.
+    pub async fn finish(mut self) -> io::Result<(W, Summary)> {
+        std::future::poll_fn(|cx| self.poll_idle(cx)).await?;
+        let writer = self.writer.take().ok_or_else(lost_inner)?;
+        spawn_blocking(move || writer.finish()).await.map_err(join_error)?
+    }
+}
+
+// The writer is never pinned, it only moves in and out of blocking tasks.
+impl<W> Unpin for AsyncBridgeWriter<W> where W: Write + Send + 'static {}
+
+impl<W> AsyncWrite for AsyncBridgeWriter<W>
+where
+    W: Write + Send + 'static,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.poll_idle(cx))?;
+        let amount = std::cmp::min(buf.len(), BUFSIZE);
+        this.start(WriteOp::Write, &buf[..amount])?;
+        Poll::Ready(Ok(amount))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().poll_run(cx, WriteOp::Flush)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().poll_run(cx, WriteOp::Shutdown)
+    }
+}
+
+impl<W> Drop for AsyncBridgeWriter<W>
+where
+    W: Write + Send + 'static,
+{
+    fn drop(&mut self) {
+        // Dropping the `Writer` flushes it, which blocks, so do that on the blocking thread pool
+        // when there is one. An operation in flight carries on and drops its writer when done.
+        if let Some(writer) = self.writer.take() {
+            match tokio::runtime::Handle::try_current() {
+                Ok(handle) => drop(handle.spawn_blocking(move || drop(writer))),
+                Err(_) => drop(writer),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+    use crate::{compress_parallel, test::test_input, CompressionLevel};
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let input = test_input(300_000);
+        let level = CompressionLevel::new(3).unwrap();
+
+        let mut writer = AsyncBridgeWriter::new(Writer::new(vec![], level));
+        for chunk in input.chunks(100_000) {
+            writer.write_all(chunk).await.unwrap();
+        }
+        writer.shutdown().await.unwrap();
+        let (compressed, summary) = writer.finish().await.unwrap();
+        assert_eq!(compressed, compress_parallel(&input, level).unwrap());
+        assert_eq!(summary.uncompressed_bytes, input.len() as u64);
+
+        let mut reader = AsyncBridgeReader::new(Reader::new(Cursor::new(compressed)));
+        let mut decompressed = vec![];
+        reader.read_to_end(&mut decompressed).await.unwrap();
+        assert_eq!(decompressed, input);
+        assert_eq!(reader.read(&mut [0; 10]).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_truncated_input() {
+        let compressed =
+            compress_parallel(b"hello world", CompressionLevel::new(3).unwrap()).unwrap();
+        let mut reader =
+            AsyncBridgeReader::new(Reader::new(Cursor::new(compressed[..20].to_vec())));
+        let error = reader.read_to_end(&mut vec![]).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,98 @@
+//! Recompressing BGZF data with different settings.
+use std::io::{self, Read, Write};
+
+use crate::{CompressionLevel, ParallelWriter, Reader, ThreadPool, BGZF_BLOCK_SIZE};
+
+/// The settings for the output of [`transcode`].
+#[derive(Debug, Copy, Clone)]
+pub struct TranscodeOptions {
+    /// The compression level of the output blocks
+    pub compression_level: CompressionLevel,
+    /// The number of uncompressed bytes per output block, at most [`BGZF_BLOCK_SIZE`]
+    pub block_size: usize,
+}
+
+impl TranscodeOptions {
+    /// Output blocks at `compression_level` with the default block size.
+    pub fn new(compression_level: CompressionLevel) -> Self {
+        Self { compression_level, block_size: BGZF_BLOCK_SIZE }
+    }
+}
+
+/// Decompress the BGZF data in `reader` and recompress it to `writer` according to `options`.
+///
+/// This is a single pass: blocks are decompressed on the calling thread and read straight into
+/// the buffers that are recompressed on the [`ThreadPool::global`] pool. The output ends with the
+/// EOF marker, and the inner writer is returned.
+///
+/// Fails with [`io::ErrorKind::InvalidInput`] if the block size is zero or more than
+/// [`BGZF_BLOCK_SIZE`].
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{transcode, Reader, TranscodeOptions, Writer};
+/// use std::io::{Read, Write};
+///
+/// let mut compressed = vec![];
+/// let mut writer = Writer::new(&mut compressed, 1.try_into().unwrap());
+/// writer.write_all(&b"ACGT".repeat(100_000)).unwrap();
+/// drop(writer);
+///
+/// let mut options = TranscodeOptions::new(9.try_into().unwrap());
+/// options.block_size = 16_384;
+/// let recompressed = transcode(compressed.as_slice(), vec![], options).unwrap();
+///
+/// let mut decompressed = vec![];
+/// Reader::new(recompressed.as_slice()).read_to_end(&mut decompressed).unwrap();
+/// assert_eq!(decompressed, b"ACGT".repeat(100_000));
+/// ```
+pub fn transcode<R, W>(reader: R, writer: W, options: TranscodeOptions) -> io::Result<W>
+where
+    R: Read,
+    W: Write,
+{
+    if options.block_size == 0 || options.block_size > BGZF_BLOCK_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Block size of {} bytes is not between 1 and {}",
+                options.block_size, BGZF_BLOCK_SIZE
+            ),
+        ));
+    }
+    let mut reader = Reader::new(reader);
+    let mut output =
+        ParallelWriter::with_pool(writer, options.compression_level, ThreadPool::global());
+    output.set_blocksize(options.block_size);
+    output.fill_from(&mut reader)?;
+    output.finish()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{compress_parallel, decompress_all, test::test_input, Index, BGZF_EOF};
+
+    #[test]
+    fn test_transcode_changes_block_size() {
+        let input = test_input(300_000);
+        let compressed = compress_parallel(&input, CompressionLevel::new(1).unwrap()).unwrap();
+
+        let mut options = TranscodeOptions::new(CompressionLevel::new(6).unwrap());
+        options.block_size = 10_000;
+        let output = transcode(compressed.as_slice(), vec![], options).unwrap();
+
+        assert!(output.ends_with(BGZF_EOF));
+        let index = Index::from_reader(io::Cursor::new(&output)).unwrap();
+        assert_eq!(index.entries().len(), 30);
+        assert!(index.entries().iter().zip(1..).all(|(e, i)| e.uncompressed_offset == i * 10_000));
+        assert_eq!(decompress_all(&output).unwrap(), input);
+
+        for block_size in [0, BGZF_BLOCK_SIZE + 1] {
+            options.block_size = block_size;
+            let error = transcode(compressed.as_slice(), vec![], options).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+}
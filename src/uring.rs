@@ -0,0 +1,329 @@
+//! Linux io_uring backed file I/O for the [`Reader`] and [`Writer`].
+//!
+//! Both types keep one operation in flight on the ring while the caller works on a second buffer,
+//! so reads and writes of the file overlap with (de)compression.
+#![allow(unsafe_code)]
+use std::{
+    fs::File,
+    io::{self, Read, Write},
+    mem,
+    os::unix::io::AsRawFd,
+    path::Path,
+};
+
+use io_uring::{cqueue, opcode, types, IoUring};
+
+use crate::{CompressionLevel, Reader, Writer, BUFSIZE};
+
+/// The size of each read or write submitted to the ring.
+const CHUNK_SIZE: usize = 4 * BUFSIZE;
+
+/// The number of entries in each ring, only one operation is in flight at a time.
+const RING_ENTRIES: u32 = 2;
+
+/// Block until the single in flight operation on `ring` completes, returning its completion.
+///
+/// An error here means no completion was reaped, so the operation may still be in flight.
+fn wait(ring: &mut IoUring) -> io::Result<cqueue::Entry> {
+    loop {
+        if let Some(entry) = ring.completion().next() {
+            return Ok(entry);
+        }
+        match ring.submit_and_wait(1) {
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// The number of bytes a completed operation transferred, or its error.
+fn result(entry: &cqueue::Entry) -> io::Result<usize> {
+    if entry.result() < 0 {
+        Err(io::Error::from_raw_os_error(-entry.result()))
+    } else {
+        Ok(entry.result() as usize)
+    }
+}
+
+/// A file reader that reads the next chunk of the file on an io_uring while the current one is consumed.
+///
+/// See [`Reader::from_path_uring`].
+pub struct UringReader {
+    ring: IoUring,
+    file: File,
+    /// The buffer being consumed by the caller
+    current: Vec<u8>,
+    /// The position of the next unread byte in `current`
+    pos: usize,
+    /// The buffer a read is in flight for, if any
+    pending: Option<Vec<u8>>,
+    /// The file offset the pending read started at
+    offset: u64,
+}
+
+impl UringReader {
+    /// Open the file at `path` and start reading its first chunk.
+    pub fn open<P>(path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let mut reader = Self {
+            ring: IoUring::new(RING_ENTRIES)?,
+            file: File::open(path)?,
+            current: Vec::with_capacity(CHUNK_SIZE),
+            pos: 0,
+            pending: None,
+            offset: 0,
+        };
+        reader.submit_read(Vec::with_capacity(CHUNK_SIZE))?;
+        Ok(reader)
+    }
+
+    /// Submit a read of the chunk at `self.offset` into `buffer`.
+    fn submit_read(&mut self, mut buffer: Vec<u8>) -> io::Result<()> {
+        buffer.resize(CHUNK_SIZE, 0);
+        let entry = opcode::Read::new(
+            types::Fd(self.file.as_raw_fd()),
+            buffer.as_mut_ptr(),
+            buffer.len() as u32,
+        )
+        .offset(self.offset)
+        .build();
+        // SAFETY: the heap allocation of `buffer` is held in `self.pending`, and is neither
+        // touched nor freed until the read completes in `wait` (see `Drop`).
+        unsafe { self.ring.submission().push(&entry) }
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        self.pending = Some(buffer);
+        self.ring.submit()?;
+        Ok(())
+    }
+
+    /// Swap the completed pending read in as the current buffer and submit the next read.
+    ///
+    /// Returns `false` once the end of the file has been reached.
+    fn fill(&mut self) -> io::Result<bool> {
+        if self.pending.is_none() {
+            return Ok(false);
+        }
+        // Until its completion is reaped the kernel may still be writing into the pending buffer,
+        // so it must stay in `self.pending`, where `Drop` keeps it alive, if the wait fails.
+        let entry = wait(&mut self.ring)?;
+        let mut buffer = self.pending.take().expect("checked above");
+        let bytes_read = result(&entry)?;
+        buffer.truncate(bytes_read);
+        if bytes_read == 0 {
+            return Ok(false);
+        }
+
+        self.offset += bytes_read as u64;
+        mem::swap(&mut self.current, &mut buffer);
+        self.pos = 0;
+        self.submit_read(buffer)?;
+        Ok(true)
+    }
+}
+
+impl Read for UringReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.current.len() && !self.fill()? {
+            return Ok(0);
+        }
+        let amount = std::cmp::min(buf.len(), self.current.len() - self.pos);
+        buf[..amount].copy_from_slice(&self.current[self.pos..self.pos + amount]);
+        self.pos += amount;
+        Ok(amount)
+    }
+}
+
+impl Drop for UringReader {
+    fn drop(&mut self) {
+        // The kernel may still be writing into the pending buffer. If its completion can't be
+        // reaped, leak the buffer rather than free memory the kernel may write to.
+        if self.pending.is_some() && wait(&mut self.ring).is_err() {
+            mem::forget(self.pending.take());
+        }
+    }
+}
+
+/// A file writer that writes the previous chunk on an io_uring while the next one is filled.
+///
+/// See [`Writer::from_path_uring`].
+pub struct UringWriter {
+    ring: IoUring,
+    file: File,
+    /// The buffer being filled by the caller
+    current: Vec<u8>,
+    /// The buffer a write is in flight for, if any
+    pending: Option<Vec<u8>>,
+    /// A written buffer kept for reuse as the next current buffer
+    spare: Vec<u8>,
+    /// The number of bytes of the pending buffer confirmed written
+    pending_written: usize,
+    /// The file offset the pending buffer starts at
+    offset: u64,
+}
+
+impl UringWriter {
+    /// Create, or truncate, the file at `path`.
+    pub fn create<P>(path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Ok(Self {
+            ring: IoUring::new(RING_ENTRIES)?,
+            file: File::create(path)?,
+            current: Vec::with_capacity(CHUNK_SIZE),
+            pending: None,
+            spare: Vec::new(),
+            pending_written: 0,
+            offset: 0,
+        })
+    }
+
+    /// Submit a write of the unwritten remainder of `buffer`.
+    fn submit_write(&mut self, buffer: Vec<u8>) -> io::Result<()> {
+        let remaining = &buffer[self.pending_written..];
+        let entry = opcode::Write::new(
+            types::Fd(self.file.as_raw_fd()),
+            remaining.as_ptr(),
+            remaining.len() as u32,
+        )
+        .offset(self.offset + self.pending_written as u64)
+        .build();
+        // SAFETY: the heap allocation of `buffer` is held in `self.pending`, and is neither
+        // modified nor freed until the write completes in `wait` (see `Drop`).
+        unsafe { self.ring.submission().push(&entry) }
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        self.pending = Some(buffer);
+        self.ring.submit()?;
+        Ok(())
+    }
+
+    /// Wait for the pending buffer, if any, to be completely written, resubmitting short writes.
+    fn wait_pending(&mut self) -> io::Result<()> {
+        while self.pending.is_some() {
+            // Until its completion is reaped the kernel may still be reading the pending buffer,
+            // so it must stay in `self.pending`, where `Drop` keeps it alive, if the wait fails.
+            let entry = wait(&mut self.ring)?;
+            let buffer = self.pending.take().expect("checked above");
+            let bytes_written = result(&entry)?;
+            if bytes_written == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::WriteZero,
+                    "failed to write whole chunk",
+                ));
+            }
+            self.pending_written += bytes_written;
+            if self.pending_written < buffer.len() {
+                self.submit_write(buffer)?;
+            } else {
+                self.offset += buffer.len() as u64;
+                self.pending_written = 0;
+                self.spare = buffer;
+                self.spare.clear();
+            }
+        }
+        Ok(())
+    }
+
+    /// Hand the current buffer to the ring once the previous write is done.
+    fn submit_current(&mut self) -> io::Result<()> {
+        self.wait_pending()?;
+        let spare = mem::take(&mut self.spare);
+        let buffer = mem::replace(&mut self.current, spare);
+        self.submit_write(buffer)
+    }
+}
+
+impl Write for UringWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.current.capacity() == 0 {
+            self.current.reserve_exact(CHUNK_SIZE);
+        }
+        let amount = std::cmp::min(buf.len(), CHUNK_SIZE - self.current.len());
+        self.current.extend_from_slice(&buf[..amount]);
+        if self.current.len() == CHUNK_SIZE {
+            self.submit_current()?;
+        }
+        Ok(amount)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.current.is_empty() {
+            self.submit_current()?;
+        }
+        self.wait_pending()?;
+        self.file.flush()
+    }
+}
+
+impl Drop for UringWriter {
+    fn drop(&mut self) {
+        // Like `BufWriter`, make a best effort to write out buffered data. Failing that, the
+        // kernel may still be reading from the pending buffer, which is leaked rather than freed
+        // if its completion can't be reaped.
+        if self.flush().is_err() && self.pending.is_some() && wait(&mut self.ring).is_err() {
+            mem::forget(self.pending.take());
+        }
+    }
+}
+
+impl Reader<UringReader> {
+    /// Create a BGZF reader from a [`Path`] that reads the file with io_uring.
+    ///
+    /// The next chunk of the file is read while the current one is being decompressed.
+    pub fn from_path_uring<P>(path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        UringReader::open(path).map(Self::new)
+    }
+}
+
+impl Writer<UringWriter> {
+    /// Create a BGZF writer from a [`Path`] that writes the file with io_uring.
+    ///
+    /// Compressed data is written out in the background while the next chunk is compressed.
+    pub fn from_path_uring<P>(path: P, compression_level: CompressionLevel) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        UringWriter::create(path).map(|f| Self::new(f, compression_level))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn test_uring_round_trip() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("output.txt.gz");
+        // Poorly compressible input so the compressed file spans several io_uring chunks.
+        let mut state = 0x2545_f491_u32;
+        let input: Vec<u8> = (0..3_000_000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+
+        let mut writer = Writer::from_path_uring(&path, CompressionLevel::new(3).unwrap()).unwrap();
+        for chunk in input.chunks(100_003) {
+            writer.write_all(chunk).unwrap();
+        }
+        drop(writer);
+        assert!(std::fs::metadata(&path).unwrap().len() > 2 * BUFSIZE as u64 * 4);
+
+        let mut reader = Reader::from_path_uring(&path).unwrap();
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes).unwrap();
+        assert_eq!(bytes, input);
+    }
+}
@@ -0,0 +1,343 @@
+//! Whole-file validation that reports every problem rather than stopping at the first.
+use std::{
+    fs::File,
+    io::{self, BufReader, Read},
+    path::Path,
+};
+
+use crate::{
+    core::block_size,
+    reader::{fill_to, is_plausible_header},
+    BgzfError, Decompressor, BGZF_BLOCK_SIZE, BGZF_EOF, BGZF_HEADER_SIZE,
+};
+
+/// Totals and problems found by [`validate`] in BGZF data.
+#[derive(Debug)]
+pub struct ValidationReport {
+    /// The number of valid blocks, including empty blocks such as the EOF marker
+    pub blocks: u64,
+    /// The number of compressed bytes scanned, valid or not
+    pub compressed_bytes: u64,
+    /// The number of uncompressed bytes in the valid blocks
+    pub uncompressed_bytes: u64,
+    /// Each stretch of the input that is not a valid block, in order
+    pub problems: Vec<BlockProblem>,
+    /// Whether the data ends with the EOF marker block
+    pub has_eof: bool,
+}
+
+impl ValidationReport {
+    /// Whether every block is valid and the data ends with the EOF marker.
+    pub fn is_valid(&self) -> bool {
+        self.problems.is_empty() && self.has_eof
+    }
+}
+
+/// A stretch of input that is not a valid block.
+#[derive(Debug)]
+pub struct BlockProblem {
+    /// The compressed offset where the problem starts
+    pub offset: u64,
+    /// The number of compressed bytes skipped because of the problem
+    pub length: u64,
+    /// What is wrong
+    pub error: BgzfError,
+}
+
+/// Scan all of the BGZF data in `reader`, decompressing and checking every block, and report what
+/// was found.
+///
+/// Unlike reading with a [`crate::Reader`], which stops at the first error, a block that fails to
+/// decompress is skipped by the size in its header, and a bad header is skipped by scanning ahead
+/// for the next plausible one, so the report covers the whole input. Only I/O errors are returned
+/// as errors.
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{validate, Writer};
+/// use std::io::Write;
+///
+/// let mut writer = Writer::new(vec![], 2.try_into().unwrap());
+/// writer.write_all(b"hello world").unwrap();
+/// let (mut compressed, _) = writer.finish().unwrap();
+///
+/// let report = validate(compressed.as_slice()).unwrap();
+/// assert!(report.is_valid());
+/// assert_eq!((report.blocks, report.uncompressed_bytes), (2, 11));
+///
+/// compressed[20] ^= 0xff;
+/// let report = validate(compressed.as_slice()).unwrap();
+/// assert_eq!(report.problems[0].offset, 0);
+/// assert!(report.has_eof);
+/// ```
+pub fn validate<R>(reader: R) -> io::Result<ValidationReport>
+where
+    R: Read,
+{
+    let mut scanner = BlockScanner::new(reader);
+    let mut report = ValidationReport {
+        blocks: 0,
+        compressed_bytes: 0,
+        uncompressed_bytes: 0,
+        problems: vec![],
+        has_eof: false,
+    };
+    while let Some(scanned) = scanner.next_block()? {
+        match scanned {
+            Scanned::Block { size, uncompressed_size } => {
+                report.blocks += 1;
+                report.compressed_bytes += size as u64;
+                report.uncompressed_bytes += uncompressed_size as u64;
+                report.has_eof = scanner.block() == BGZF_EOF;
+            }
+            Scanned::Problem(problem) => {
+                report.compressed_bytes += problem.length;
+                report.has_eof = false;
+                report.problems.push(problem);
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// Scan the BGZF file at `path` with [`validate`].
+pub fn validate_path<P>(path: P) -> io::Result<ValidationReport>
+where
+    P: AsRef<Path>,
+{
+    validate(File::open(path)?)
+}
+
+/// The outcome of [`verify`].
+#[derive(Debug)]
+pub struct Verification {
+    /// The number of blocks that checked out, including empty blocks such as the EOF marker
+    pub blocks: u64,
+    /// The number of compressed bytes in the blocks that checked out
+    pub compressed_bytes: u64,
+    /// The number of uncompressed bytes in the blocks that checked out
+    pub uncompressed_bytes: u64,
+    /// The first problem found, where verification stopped
+    pub failure: Option<BlockProblem>,
+    /// Whether the data ends with the EOF marker block
+    pub has_eof: bool,
+}
+
+impl Verification {
+    /// Whether every block checked out and the data ends with the EOF marker.
+    pub fn is_ok(&self) -> bool {
+        self.failure.is_none() && self.has_eof
+    }
+}
+
+/// Decompress every block of the BGZF data in `reader`, checking its CRC32 and uncompressed size
+/// against its footer, and check that the data ends with the EOF marker.
+///
+/// Verification stops at the first problem, which is reported along with where it was found.
+/// Use [`validate`] to find every problem instead. Only I/O errors are returned as errors.
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::{verify, Writer};
+/// use std::io::Write;
+///
+/// let mut writer = Writer::new(vec![], 2.try_into().unwrap());
+/// writer.write_all(b"hello world").unwrap();
+/// let (compressed, _) = writer.finish().unwrap();
+///
+/// let verification = verify(compressed.as_slice()).unwrap();
+/// assert!(verification.is_ok());
+/// assert_eq!(verification.blocks, 2);
+///
+/// let without_eof = &compressed[..compressed.len() - bgzf::BGZF_EOF.len()];
+/// let verification = verify(without_eof).unwrap();
+/// assert!(verification.failure.is_none());
+/// assert!(!verification.has_eof);
+/// ```
+pub fn verify<R>(reader: R) -> io::Result<Verification>
+where
+    R: Read,
+{
+    let mut scanner = BlockScanner::new(reader);
+    let mut verification = Verification {
+        blocks: 0,
+        compressed_bytes: 0,
+        uncompressed_bytes: 0,
+        failure: None,
+        has_eof: false,
+    };
+    while let Some(scanned) = scanner.next_block()? {
+        match scanned {
+            Scanned::Block { size, uncompressed_size } => {
+                verification.blocks += 1;
+                verification.compressed_bytes += size as u64;
+                verification.uncompressed_bytes += uncompressed_size as u64;
+                verification.has_eof = scanner.block() == BGZF_EOF;
+            }
+            Scanned::Problem(problem) => {
+                verification.failure = Some(problem);
+                verification.has_eof = false;
+                break;
+            }
+        }
+    }
+    Ok(verification)
+}
+
+/// What [`BlockScanner`] found next in its input.
+pub(crate) enum Scanned {
+    /// A valid block, whose bytes are in [`BlockScanner::block`]
+    Block { size: usize, uncompressed_size: usize },
+    /// A stretch of input that was skipped
+    Problem(BlockProblem),
+}
+
+/// Walks the blocks of possibly corrupt BGZF data, decompressing each and skipping past problems.
+pub(crate) struct BlockScanner<R> {
+    reader: BufReader<R>,
+    /// Input read but not yet consumed, starting at `offset`
+    window: Vec<u8>,
+    /// The number of bytes at the start of `window` to drop before scanning on
+    consumed: usize,
+    /// The compressed offset of the start of `window`
+    offset: u64,
+    decompressor: Decompressor,
+    /// The buffer to decompress blocks into
+    decompressed: Vec<u8>,
+}
+
+impl<R> BlockScanner<R>
+where
+    R: Read,
+{
+    pub(crate) fn new(reader: R) -> Self {
+        Self {
+            reader: BufReader::new(reader),
+            window: Vec::new(),
+            consumed: 0,
+            offset: 0,
+            decompressor: Decompressor::new(),
+            decompressed: Vec::new(),
+        }
+    }
+
+    /// Move on to the next block or problem, or return `None` at the end of the input.
+    pub(crate) fn next_block(&mut self) -> io::Result<Option<Scanned>> {
+        self.window.drain(..self.consumed);
+        self.offset += self.consumed as u64;
+        self.consumed = 0;
+
+        let offset = self.offset;
+        if !fill_to(&mut self.reader, &mut self.window, BGZF_HEADER_SIZE)? {
+            if self.window.is_empty() {
+                return Ok(None);
+            }
+            self.consumed = self.window.len();
+            return Ok(Some(problem(offset, self.consumed as u64, BgzfError::Truncated)));
+        }
+        let size = match block_size(&self.window) {
+            Ok(size) => size,
+            Err(e) => {
+                let skipped = self.skip_to_plausible_header()?;
+                return Ok(Some(problem(offset, skipped, e)));
+            }
+        };
+        if !fill_to(&mut self.reader, &mut self.window, size)? {
+            self.consumed = self.window.len();
+            return Ok(Some(problem(offset, self.consumed as u64, BgzfError::Truncated)));
+        }
+        self.consumed = size;
+        match self.decompressor.decompress_block(&self.window[..size], &mut self.decompressed) {
+            Ok(()) => Ok(Some(Scanned::Block { size, uncompressed_size: self.decompressed.len() })),
+            Err(e) => Ok(Some(problem(offset, size as u64, e))),
+        }
+    }
+
+    /// The bytes of the block last returned by [`BlockScanner::next_block`].
+    pub(crate) fn block(&self) -> &[u8] {
+        &self.window[..self.consumed]
+    }
+
+    /// Skip from the start of the window, which is not a valid header, up to the next plausible
+    /// header or the end of the input, returning the number of bytes skipped.
+    fn skip_to_plausible_header(&mut self) -> io::Result<u64> {
+        let mut skipped = 0;
+        let mut start = 1;
+        while fill_to(&mut self.reader, &mut self.window, start + BGZF_HEADER_SIZE)? {
+            if is_plausible_header(&self.window[start..]) {
+                break;
+            }
+            start += 1;
+            // Don't hold on to a long run of garbage.
+            if start >= BGZF_BLOCK_SIZE {
+                self.window.drain(..start);
+                self.offset += start as u64;
+                skipped += start as u64;
+                start = 0;
+            }
+        }
+        self.consumed = std::cmp::min(start, self.window.len());
+        Ok(skipped + self.consumed as u64)
+    }
+}
+
+/// A problem covering `length` bytes from `offset`.
+fn problem(offset: u64, length: u64, error: BgzfError) -> Scanned {
+    Scanned::Problem(BlockProblem { offset, length, error })
+}
+
+#[cfg(test)]
+mod test {
+    use byteorder::{ByteOrder, LittleEndian};
+
+    use super::*;
+    use crate::{compress_parallel, test::test_input, CompressionLevel};
+
+    #[test]
+    fn test_validate_reports_every_problem() {
+        let input = test_input(200_000);
+        let compressed = compress_parallel(&input, CompressionLevel::new(3).unwrap()).unwrap();
+        let first = block_size(&compressed).unwrap();
+        let second = block_size(&compressed[first..]).unwrap();
+
+        let mut damaged = compressed[..first].to_vec();
+        damaged.extend_from_slice(&[0xaa; 100]);
+        damaged.extend_from_slice(&compressed[first..compressed.len() - 10]);
+        damaged[first + 100 + 30] ^= 0xff;
+
+        let report = validate(damaged.as_slice()).unwrap();
+        let problems: Vec<_> = report.problems.iter().map(|p| (p.offset, p.length)).collect();
+        let end = (damaged.len() - (BGZF_EOF.len() - 10)) as u64;
+        assert_eq!(problems, [(first as u64, 100), (first as u64 + 100, second as u64), (end, 18)]);
+        assert!(matches!(report.problems[2].error, BgzfError::Truncated));
+        assert_eq!(report.blocks, 3);
+        assert_eq!(report.compressed_bytes, damaged.len() as u64);
+        assert_eq!(report.uncompressed_bytes, input.len() as u64 - BGZF_BLOCK_SIZE as u64);
+        assert!(!report.has_eof);
+        assert!(!report.is_valid());
+    }
+
+    #[test]
+    fn test_verify_stops_at_first_failure() {
+        let input = test_input(200_000);
+        let mut compressed = compress_parallel(&input, CompressionLevel::new(3).unwrap()).unwrap();
+        assert!(verify(compressed.as_slice()).unwrap().is_ok());
+
+        // Claim one byte more in the second block's footer than it holds.
+        let first = block_size(&compressed).unwrap();
+        let second = block_size(&compressed[first..]).unwrap();
+        let isize_offset = first + second - 4;
+        let claimed = LittleEndian::read_u32(&compressed[isize_offset..]) + 1;
+        LittleEndian::write_u32(&mut compressed[isize_offset..], claimed);
+
+        let verification = verify(compressed.as_slice()).unwrap();
+        let failure = verification.failure.unwrap();
+        assert_eq!((failure.offset, failure.length), (first as u64, second as u64));
+        assert!(matches!(failure.error, BgzfError::UncompressedSizeMismatch { .. }));
+        assert_eq!(verification.blocks, 1);
+        assert_eq!(verification.uncompressed_bytes, BGZF_BLOCK_SIZE as u64);
+        assert!(!verification.has_eof);
+    }
+}
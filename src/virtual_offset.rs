@@ -0,0 +1,137 @@
+//! BGZF virtual file offsets.
+use std::fmt;
+
+/// A position in BGZF data: the compressed offset of a block combined with an offset into its
+/// uncompressed contents.
+///
+/// This is the virtual file offset used by BAI, CSI, and tabix indexes. It packs into a `u64` as
+/// `compressed_offset << 16 | uncompressed_offset`, so compressed offsets are limited to 48 bits.
+/// Ordering compares the compressed offset first, which matches the order of positions in the file.
+///
+/// # Example
+///
+/// ```rust
+/// use bgzf::VirtualOffset;
+///
+/// let offset = VirtualOffset::from((1_000, 42));
+/// assert_eq!(offset.compressed_offset(), 1_000);
+/// assert_eq!(offset.uncompressed_offset(), 42);
+/// assert_eq!(u64::from(offset), 1_000 << 16 | 42);
+/// assert_eq!(offset.to_string(), "1000:42");
+/// assert!(offset < VirtualOffset::new(1_001, 0));
+/// ```
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VirtualOffset(u64);
+
+impl VirtualOffset {
+    /// The first position in the data.
+    pub const MIN: Self = Self(0);
+
+    /// The largest virtual offset.
+    pub const MAX: Self = Self(u64::MAX);
+
+    /// The largest compressed offset that fits in a virtual offset.
+    pub const MAX_COMPRESSED_OFFSET: u64 = (1 << 48) - 1;
+
+    /// Create a [`VirtualOffset`] for `uncompressed_offset` bytes into the block at `compressed_offset`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `compressed_offset` is greater than [`VirtualOffset::MAX_COMPRESSED_OFFSET`].
+    pub fn new(compressed_offset: u64, uncompressed_offset: u16) -> Self {
+        assert!(
+            compressed_offset <= Self::MAX_COMPRESSED_OFFSET,
+            "Compressed offset {} does not fit in a virtual offset",
+            compressed_offset
+        );
+        Self(compressed_offset << 16 | u64::from(uncompressed_offset))
+    }
+
+    /// The offset of the block in the compressed data.
+    pub fn compressed_offset(self) -> u64 {
+        self.0 >> 16
+    }
+
+    /// The offset within the uncompressed contents of the block.
+    pub fn uncompressed_offset(self) -> u16 {
+        self.0 as u16
+    }
+
+    /// Move `amount` bytes further into the same block, returning `None` if that leaves the range
+    /// a virtual offset can address within a block.
+    ///
+    /// ```rust
+    /// use bgzf::VirtualOffset;
+    ///
+    /// let offset = VirtualOffset::new(100, 65_000);
+    /// assert_eq!(offset.checked_add(35), Some(VirtualOffset::new(100, 65_035)));
+    /// assert_eq!(offset.checked_add(1_000), None);
+    /// ```
+    pub fn checked_add(self, amount: u16) -> Option<Self> {
+        self.uncompressed_offset()
+            .checked_add(amount)
+            .map(|uncompressed_offset| Self::new(self.compressed_offset(), uncompressed_offset))
+    }
+}
+
+impl From<u64> for VirtualOffset {
+    fn from(value: u64) -> Self {
+        Self(value)
+    }
+}
+
+impl From<VirtualOffset> for u64 {
+    fn from(offset: VirtualOffset) -> Self {
+        offset.0
+    }
+}
+
+impl From<(u64, u16)> for VirtualOffset {
+    /// Convert a `(compressed_offset, uncompressed_offset)` pair, see [`VirtualOffset::new`].
+    fn from((compressed_offset, uncompressed_offset): (u64, u16)) -> Self {
+        Self::new(compressed_offset, uncompressed_offset)
+    }
+}
+
+impl From<VirtualOffset> for (u64, u16) {
+    fn from(offset: VirtualOffset) -> Self {
+        (offset.compressed_offset(), offset.uncompressed_offset())
+    }
+}
+
+impl fmt::Display for VirtualOffset {
+    /// Format as `compressed_offset:uncompressed_offset`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.compressed_offset(), self.uncompressed_offset())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_virtual_offset_order_and_round_trip() {
+        let mut offsets = vec![
+            VirtualOffset::new(2, 0),
+            VirtualOffset::new(1, u16::MAX),
+            VirtualOffset::new(VirtualOffset::MAX_COMPRESSED_OFFSET, 7),
+            VirtualOffset::new(1, 3),
+        ];
+        offsets.sort();
+        let pairs: Vec<(u64, u16)> = offsets.iter().map(|&offset| offset.into()).collect();
+        assert_eq!(
+            pairs,
+            vec![(1, 3), (1, u16::MAX), (2, 0), (VirtualOffset::MAX_COMPRESSED_OFFSET, 7)]
+        );
+        for offset in offsets {
+            assert_eq!(VirtualOffset::from(u64::from(offset)), offset);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "does not fit")]
+    fn test_virtual_offset_too_large() {
+        VirtualOffset::new(VirtualOffset::MAX_COMPRESSED_OFFSET + 1, 0);
+    }
+}
@@ -3,16 +3,22 @@ use std::{
     fs::File,
     io::{self, Write},
     path::Path,
+    time::{Duration, Instant},
 };
 
-use bytes::BytesMut;
+use bytes::{Bytes, BytesMut};
 
 use crate::{
-    CompressionLevel, Compressor, BGZF_BLOCK_SIZE, BGZF_EOF, BUFSIZE, MAX_BGZF_BLOCK_SIZE,
+    core::Encoder, crc32_combine, AdaptiveController, AdaptiveLevel, BgzfResult, BlockCallback,
+    BlockFooter, BlockInfo, BlockSink, BoundaryPolicy, CompressionLevel, Compressor, Index,
+    LineBoundary, Progress, ProgressTracker, StreamDigest, VirtualOffset, BGZF_BLOCK_SIZE,
+    BGZF_EOF, BUFSIZE, MAX_BGZF_BLOCK_SIZE,
 };
 
 /// A BGZF writer.
 ///
+/// Blocks are written to any [`Write`], or to any other storage that implements [`BlockSink`].
+///
 /// # Example
 ///
 /// ```rust
@@ -35,23 +41,51 @@ use crate::{
 /// ```
 pub struct Writer<W>
 where
-    W: Write,
+    W: BlockSink,
 {
     /// The internal buffer to use
     uncompressed_buffer: BytesMut,
-    /// The buffer to reuse for compressed bytes
-    compressed_buffer: Vec<u8>,
     /// The size of the blocks to create
     blocksize: usize,
-    /// The compressor to reuse
-    compressor: Compressor,
-    /// The inner writer
-    writer: W,
+    /// Compresses each block, reusing its compressor and output buffer
+    encoder: Encoder,
+    /// The number of compressed bytes written to the inner writer so far
+    compressed_offset: u64,
+    /// The number of uncompressed bytes compressed into blocks so far
+    uncompressed_offset: u64,
+    /// The number of blocks written so far, excluding EOF blocks
+    block_count: u64,
+    /// Whether the last block written was an EOF block
+    ends_with_eof: bool,
+    /// The callback to invoke after each block is written
+    block_callback: Option<BlockCallback>,
+    /// The progress sink to update after each block is written
+    progress: Option<ProgressTracker>,
+    /// Picks the compression level per block, if enabled
+    adaptive: Option<AdaptiveController>,
+    /// The index of the blocks written so far, if enabled
+    index: Option<Index>,
+    /// The checksum of all uncompressed data written so far, if enabled
+    stream_crc: Option<u32>,
+    /// The digest of all uncompressed data written so far, if set
+    digest: Option<Box<dyn StreamDigest>>,
+    /// Flush once this many bytes are buffered, if set
+    flush_after_bytes: Option<usize>,
+    /// Flush once the oldest buffered bytes have waited this long, if set
+    flush_after_delay: Option<Duration>,
+    /// When the buffered bytes started accumulating, if any are buffered and a delay is set
+    pending_since: Option<Instant>,
+    /// When `flush` writes an empty block
+    flush_marker: FlushMarker,
+    /// Picks where full blocks end, if set
+    boundary: Option<Box<dyn BoundaryPolicy>>,
+    /// The inner writer, taken by `finish`
+    writer: Option<W>,
 }
 
 impl<W> Writer<W>
 where
-    W: Write,
+    W: BlockSink,
 {
     /// Create a new [`Writer`]
     pub fn new(writer: W, compression_level: CompressionLevel) -> Self {
@@ -63,15 +97,633 @@ where
     /// By default the capacity is [`bgzf::BUFSIZE`]. The capacity bust be less than [`bgzf::BGZF_BLOCK_SIZE`].
     pub fn with_capacity(writer: W, compression_level: CompressionLevel, blocksize: usize) -> Self {
         assert!(blocksize <= BGZF_BLOCK_SIZE);
-        let compressor = Compressor::new(compression_level);
         Self {
             uncompressed_buffer: BytesMut::with_capacity(BUFSIZE),
-            compressed_buffer: Vec::with_capacity(BUFSIZE),
             blocksize,
-            compressor,
-            writer,
+            encoder: Encoder::from_compressor(Compressor::new(compression_level)),
+            compressed_offset: 0,
+            uncompressed_offset: 0,
+            block_count: 0,
+            ends_with_eof: false,
+            block_callback: None,
+            progress: None,
+            adaptive: None,
+            index: None,
+            stream_crc: None,
+            digest: None,
+            flush_after_bytes: None,
+            flush_after_delay: None,
+            pending_since: None,
+            flush_marker: FlushMarker::default(),
+            boundary: None,
+            writer: Some(writer),
+        }
+    }
+
+    /// Create a writer whose output matches htslib's `bgzip -l bgzip_level` byte for byte.
+    ///
+    /// Blocks hold the same [`BGZF_BLOCK_SIZE`] bytes as `bgzip`'s and are compressed as
+    /// described by [`Compressor::bgzip_compatible`]. Flushing part of the way through, or
+    /// turning on [`Writer::set_adaptive_level`], changes the output from what `bgzip` would
+    /// write.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::Writer;
+    /// use std::io::Write;
+    ///
+    /// let mut writer = Writer::bgzip_compatible(vec![], -1).unwrap();
+    /// writer.write_all(b"hello world").unwrap();
+    /// let (compressed, _) = writer.finish().unwrap();
+    /// assert_eq!(compressed[..16], *b"\x1f\x8b\x08\x04\0\0\0\0\0\xff\x06\0BC\x02\0");
+    /// ```
+    pub fn bgzip_compatible(writer: W, bgzip_level: i32) -> BgzfResult<Self> {
+        let compressor = Compressor::bgzip_compatible(bgzip_level)?;
+        let mut writer = Self::new(writer, compressor.level);
+        writer.encoder.set_compressor(compressor);
+        Ok(writer)
+    }
+
+    /// Register a callback to be invoked after each block is written to the inner writer.
+    ///
+    /// The callback receives the [`BlockInfo`] of the block, which includes the offset of the
+    /// block in the compressed output. The EOF marker block is not reported.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::Writer;
+    /// use std::io::Write;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let blocks = Arc::new(Mutex::new(vec![]));
+    /// let mut writer = Writer::new(vec![], 2.try_into().unwrap());
+    /// let sink = Arc::clone(&blocks);
+    /// writer.set_block_callback(move |block| sink.lock().unwrap().push(block));
+    /// writer.write_all(b"hello world").unwrap();
+    /// writer.flush().unwrap();
+    ///
+    /// let blocks = blocks.lock().unwrap();
+    /// assert_eq!(blocks.len(), 1);
+    /// assert_eq!(blocks[0].compressed_offset, 0);
+    /// assert_eq!(blocks[0].uncompressed_size, 11);
+    /// ```
+    pub fn set_block_callback<F>(&mut self, callback: F)
+    where
+        F: FnMut(BlockInfo) + Send + 'static,
+    {
+        self.block_callback = Some(Box::new(callback));
+    }
+
+    /// Register a [`Progress`] to be updated after each block is written to the inner writer.
+    ///
+    /// Progress is measured in uncompressed bytes, so `total` should be the number of
+    /// uncompressed bytes expected to be written, if known.
+    pub fn set_progress<P>(&mut self, progress: P, total: Option<u64>)
+    where
+        P: Progress + 'static,
+    {
+        self.progress = Some(ProgressTracker::new(progress, total));
+    }
+
+    /// Choose the compression level for each block to meet the target of `adaptive`.
+    ///
+    /// Adapting starts from the current level, see [`AdaptiveLevel`] for how it changes.
+    pub fn set_adaptive_level(&mut self, adaptive: AdaptiveLevel) {
+        let controller = AdaptiveController::new(adaptive, self.compression_level());
+        if controller.level() != self.compression_level() {
+            self.encoder.set_compressor(Compressor::new(controller.level()));
+        }
+        self.adaptive = Some(controller);
+    }
+
+    /// The level the next block will be compressed at.
+    pub fn compression_level(&self) -> CompressionLevel {
+        self.encoder.compressor().level
+    }
+
+    /// Compute the CRC32 of the whole uncompressed stream, to be reported by [`Writer::finish`].
+    ///
+    /// The CRC32 each block's footer holds anyway is combined into it with [`crc32_combine`], so
+    /// this costs next to nothing per block.
+    ///
+    /// # Panics
+    ///
+    /// Panics if anything has already been written.
+    pub fn set_stream_checksum(&mut self, enabled: bool) {
+        assert!(
+            self.uncompressed_offset == 0 && self.uncompressed_buffer.is_empty(),
+            "The stream checksum must be set before writing"
+        );
+        self.stream_crc = enabled.then_some(0);
+    }
+
+    /// The CRC32 of the data in every block written so far, or `None` if it isn't enabled with
+    /// [`Writer::set_stream_checksum`].
+    ///
+    /// Data still buffered is not included until it is written as a block.
+    pub fn stream_crc32(&self) -> Option<u32> {
+        self.stream_crc
+    }
+
+    /// Write the partial block and flush the inner writer during a write once `max_bytes` are
+    /// buffered, or once the oldest buffered bytes have waited `max_delay`.
+    ///
+    /// This keeps a slow live stream from sitting in the buffer waiting for a whole block. The
+    /// thresholds are only checked when data is written, so data written just before the stream
+    /// goes quiet still needs an explicit flush. Pass `None` for both to turn this off.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::Writer;
+    /// use std::io::Write;
+    /// use std::time::Duration;
+    ///
+    /// let mut writer = Writer::new(vec![], 2.try_into().unwrap());
+    /// writer.set_auto_flush(Some(1024), Some(Duration::from_millis(100)));
+    /// writer.write_all(&[b'A'; 1000]).unwrap();
+    /// assert_eq!(writer.block_count(), 0);
+    /// writer.write_all(&[b'A'; 100]).unwrap();
+    /// assert_eq!((writer.block_count(), writer.pending_bytes()), (1, 0));
+    /// ```
+    pub fn set_auto_flush(&mut self, max_bytes: Option<usize>, max_delay: Option<Duration>) {
+        self.flush_after_bytes = max_bytes;
+        self.flush_after_delay = max_delay;
+        self.pending_since = None;
+    }
+
+    /// Choose when [`Write::flush`] writes an empty block after the buffered data.
+    ///
+    /// With [`FlushMarker::Always`] every flush leaves an empty block in the output, like the
+    /// sync points some streaming consumers split on.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::{FlushMarker, Writer, BGZF_EOF};
+    /// use std::io::Write;
+    ///
+    /// let mut writer = Writer::new(vec![], 2.try_into().unwrap());
+    /// writer.set_flush_marker(FlushMarker::Always);
+    /// writer.flush().unwrap();
+    /// writer.flush().unwrap();
+    /// let (compressed, _) = writer.finish().unwrap();
+    /// assert_eq!(compressed, [BGZF_EOF, BGZF_EOF].concat());
+    /// ```
+    pub fn set_flush_marker(&mut self, marker: FlushMarker) {
+        self.flush_marker = marker;
+    }
+
+    /// End each full block after the last newline within it, so that blocks hold whole lines.
+    ///
+    /// This sets the [`LineBoundary`] policy, or clears any policy if `aligned` is false. A block
+    /// with no newline at all, from a line longer than a block, is still cut at the block size.
+    /// Flushes write out everything buffered, partial lines included.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::{ChecksumManifest, Writer, BGZF_BLOCK_SIZE};
+    /// use std::io::{Cursor, Write};
+    ///
+    /// let mut writer = Writer::new(vec![], 2.try_into().unwrap());
+    /// writer.set_line_aligned(true);
+    /// for i in 0..20_000 {
+    ///     writeln!(writer, "chr1\t{}\t.\tA\tC", i).unwrap();
+    /// }
+    /// let (compressed, _) = writer.finish().unwrap();
+    ///
+    /// let blocks = ChecksumManifest::from_reader(Cursor::new(compressed)).unwrap();
+    /// assert!(blocks.blocks()[0].uncompressed_size < BGZF_BLOCK_SIZE as u32);
+    /// ```
+    pub fn set_line_aligned(&mut self, aligned: bool) {
+        if aligned {
+            self.set_boundary_policy(LineBoundary);
+        } else {
+            self.boundary = None;
+        }
+    }
+
+    /// Let `policy` pick where each full block ends, so that blocks hold whole records.
+    ///
+    /// See [`BoundaryPolicy`], and [`LineGroupBoundary`](crate::LineGroupBoundary) for FASTQ.
+    pub fn set_boundary_policy<P>(&mut self, policy: P)
+    where
+        P: BoundaryPolicy + 'static,
+    {
+        self.boundary = Some(Box::new(policy));
+    }
+
+    /// Feed all of the uncompressed data written to `digest`, and return the result in the
+    /// [`Summary::digest`] from [`Writer::finish`].
+    ///
+    /// Set this before writing anything, for the digest to cover all of the data. See
+    /// [`StreamDigest`] for the digests available.
+    pub fn set_digest<D>(&mut self, digest: D)
+    where
+        D: StreamDigest + 'static,
+    {
+        self.digest = Some(Box::new(digest));
+    }
+
+    /// Write out all buffered data and the EOF marker, returning the inner writer and a
+    /// [`Summary`] of everything written.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::Writer;
+    /// use std::io::Write;
+    ///
+    /// let mut writer = Writer::new(vec![], 2.try_into().unwrap());
+    /// writer.set_stream_checksum(true);
+    /// writer.write_all(b"hello world").unwrap();
+    /// let (compressed, summary) = writer.finish().unwrap();
+    ///
+    /// assert_eq!(summary.blocks, 1);
+    /// assert_eq!(summary.uncompressed_bytes, 11);
+    /// assert_eq!(summary.compressed_bytes, compressed.len() as u64);
+    /// assert_eq!(summary.crc32, Some(0x0d4a1185));
+    /// ```
+    pub fn finish(mut self) -> io::Result<(W, Summary)> {
+        let result = self.try_finish();
+        let writer = self.writer.take().expect("writer is only taken by finish");
+        result.map(|summary| (writer, summary))
+    }
+
+    /// Write out all buffered data and the EOF marker, and summarize everything written.
+    fn try_finish(&mut self) -> io::Result<Summary> {
+        self.finish_output()?;
+        self.inner().finish_blocks()?;
+        Ok(Summary {
+            blocks: self.block_count,
+            uncompressed_bytes: self.uncompressed_offset,
+            compressed_bytes: self.compressed_offset,
+            crc32: self.stream_crc,
+            digest: self.digest.as_mut().map(|digest| digest.finish()),
+        })
+    }
+
+    /// Write out any buffered data followed by the EOF marker block, and flush the inner writer.
+    ///
+    /// This leaves the output a complete BGZF file, for example before handing the inner writer to
+    /// other code. The marker is not written again by [`Writer::finish`] unless more data is
+    /// written after it.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::{Writer, BGZF_EOF};
+    /// use std::io::Write;
+    ///
+    /// let mut writer = Writer::new(vec![], 2.try_into().unwrap());
+    /// writer.write_all(b"hello world").unwrap();
+    /// writer.write_eof().unwrap();
+    /// let (compressed, _) = writer.finish().unwrap();
+    ///
+    /// assert!(compressed.ends_with(BGZF_EOF));
+    /// assert!(!compressed[..compressed.len() - BGZF_EOF.len()].ends_with(BGZF_EOF));
+    /// ```
+    pub fn write_eof(&mut self) -> io::Result<()> {
+        self.flush_block()?;
+        if !self.ends_with_eof {
+            self.write_eof_block()?;
+        }
+        self.inner().flush_blocks()
+    }
+
+    /// Give up on the inner writer, returning the uncompressed bytes that have not been written.
+    ///
+    /// After a failed `write` or `flush` this holds everything not yet written to the inner
+    /// writer as a complete block, including the data of the failed call, so it can be written
+    /// again to a new [`Writer`] without loss. Nothing further is written to the inner writer.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::Writer;
+    /// use std::io::Write;
+    ///
+    /// let mut writer = Writer::new(vec![], 2.try_into().unwrap());
+    /// writer.write_all(b"hello world").unwrap();
+    /// assert_eq!(writer.into_pending(), b"hello world");
+    /// ```
+    pub fn into_pending(mut self) -> Vec<u8> {
+        self.writer = None;
+        self.uncompressed_buffer.to_vec()
+    }
+
+    /// A reference to the inner writer.
+    pub fn get_ref(&self) -> &W {
+        self.writer.as_ref().expect("writer is only taken by finish")
+    }
+
+    /// A mutable reference to the inner writer.
+    ///
+    /// Writing to it directly, rather than through this writer, corrupts the output.
+    pub fn get_mut(&mut self) -> &mut W {
+        self.inner()
+    }
+
+    /// The inner writer.
+    #[inline]
+    fn inner(&mut self) -> &mut W {
+        self.writer.as_mut().expect("writer is only taken by finish")
+    }
+
+    /// Flush the partial block if the thresholds set by [`Writer::set_auto_flush`] are reached.
+    fn auto_flush(&mut self) -> io::Result<()> {
+        if self.uncompressed_buffer.is_empty() {
+            self.pending_since = None;
+            return Ok(());
+        }
+        let over_size = self.flush_after_bytes.is_some_and(|max| self.pending_bytes() >= max);
+        let overdue = match self.flush_after_delay {
+            Some(max) => self.pending_since.get_or_insert_with(Instant::now).elapsed() >= max,
+            None => false,
+        };
+        if over_size || overdue {
+            self.flush_block()?;
+            self.pending_since = None;
+            self.inner().flush_blocks()?;
+        }
+        Ok(())
+    }
+
+    /// How many of the `pending` bytes, at least a block's worth, to put in the next block.
+    fn block_len(
+        boundary: &mut Option<Box<dyn BoundaryPolicy>>,
+        pending: &[u8],
+        blocksize: usize,
+    ) -> usize {
+        let window = &pending[..blocksize];
+        match boundary.as_mut().and_then(|policy| policy.split_point(window)) {
+            Some(len) if len > 0 && len <= blocksize => len,
+            _ => blocksize,
+        }
+    }
+
+    /// Write the first `len` buffered bytes as a block, keeping them buffered if that fails.
+    fn write_buffered(&mut self, len: usize) -> io::Result<()> {
+        let bytes = self.uncompressed_buffer.split_to(len);
+        let result = self.write_block(&bytes);
+        if result.is_err() {
+            let rest = std::mem::replace(&mut self.uncompressed_buffer, bytes);
+            self.uncompressed_buffer.unsplit(rest);
+        }
+        result
+    }
+
+    /// Compress `bytes` into a single block and write it to the inner writer.
+    fn write_block(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let start = Instant::now();
+        let block = self
+            .encoder
+            .encode_block(bytes)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        let level = self
+            .adaptive
+            .as_mut()
+            .and_then(|adaptive| adaptive.observe(bytes.len(), block.len(), start.elapsed()));
+        self.writer.as_mut().expect("writer is only taken by finish").emit_block(block)?;
+        combine_block_crc(&mut self.stream_crc, block)?;
+        let compressed_size = block.len();
+        if let Some(level) = level {
+            self.encoder.set_compressor(Compressor::new(level));
+        }
+        if let Some(digest) = self.digest.as_mut() {
+            digest.update(bytes);
+        }
+        self.record_block(compressed_size, bytes.len());
+        Ok(())
+    }
+
+    /// Account for a block of `compressed_size` bytes holding `uncompressed_size` bytes of data.
+    fn record_block(&mut self, compressed_size: usize, uncompressed_size: usize) {
+        let info = BlockInfo {
+            compressed_offset: self.compressed_offset,
+            compressed_size,
+            uncompressed_offset: self.uncompressed_offset,
+            uncompressed_size,
+        };
+        self.compressed_offset += info.compressed_size as u64;
+        self.uncompressed_offset += info.uncompressed_size as u64;
+        self.block_count += 1;
+        self.ends_with_eof = false;
+
+        if let Some(index) = self.index.as_mut() {
+            index.add_block(&info);
+        }
+        if let Some(callback) = self.block_callback.as_mut() {
+            callback(info);
         }
+        if let Some(progress) = self.progress.as_mut() {
+            progress.update(self.uncompressed_offset);
+        }
+    }
+
+    /// Write the EOF marker block to the inner writer.
+    fn write_eof_block(&mut self) -> io::Result<()> {
+        self.inner().emit_block(BGZF_EOF)?;
+        self.compressed_offset += BGZF_EOF.len() as u64;
+        self.ends_with_eof = true;
+        Ok(())
+    }
+
+    /// Flush all buffered data and make sure the output ends with an EOF block.
+    pub(crate) fn finish_output(&mut self) -> io::Result<()> {
+        self.write_eof()
+    }
+
+    /// Whether an already compressed block with `header` and `uncompressed_size` bytes of data can
+    /// be written as is, because it matches the blocks this writer would produce.
+    ///
+    /// The level is judged by the compression hint in the header, which only distinguishes the
+    /// fastest and best levels from the rest.
+    pub(crate) fn accepts_raw_block(&self, header: &[u8], uncompressed_size: usize) -> bool {
+        self.uncompressed_buffer.is_empty()
+            && self.adaptive.is_none()
+            && self.digest.is_none()
+            && uncompressed_size == self.blocksize
+            && header[8] == self.encoder.compressor().hint()
+    }
+
+    /// Write an already compressed block made up of `header` and `rest` as is.
+    ///
+    /// Only valid if [`Writer::accepts_raw_block`] allows it.
+    pub(crate) fn write_raw_block(
+        &mut self,
+        header: &[u8],
+        rest: &[u8],
+        uncompressed_size: usize,
+    ) -> io::Result<()> {
+        combine_block_crc(&mut self.stream_crc, rest)?;
+        let block = self.encoder.copy_block(header, rest);
+        self.writer.as_mut().expect("writer is only taken by finish").emit_block(block)?;
+        self.record_block(header.len() + rest.len(), uncompressed_size);
+        Ok(())
+    }
+
+    /// The number of uncompressed bytes that can be written before the next block is compressed.
+    pub(crate) fn block_room(&self) -> usize {
+        self.blocksize - self.uncompressed_buffer.len()
+    }
+
+    /// The number of compressed bytes written to the inner writer so far.
+    pub(crate) fn compressed_offset(&self) -> u64 {
+        self.compressed_offset
     }
+
+    /// The number of blocks written so far, excluding EOF blocks.
+    pub fn block_count(&self) -> u64 {
+        self.block_count
+    }
+
+    /// The [`VirtualOffset`] the next byte written will have, as used by BAI, CSI, and tabix
+    /// indexes.
+    ///
+    /// Compressed offsets count from where this writer started writing to the inner writer.
+    pub fn virtual_position(&self) -> VirtualOffset {
+        VirtualOffset::new(self.compressed_offset, self.uncompressed_buffer.len() as u16)
+    }
+
+    /// The number of uncompressed bytes buffered for the current block.
+    ///
+    /// Together with [`Writer::flush_block`] this lets a record writer start a new block before a
+    /// record that would otherwise straddle two blocks.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::{Writer, BGZF_BLOCK_SIZE};
+    /// use std::io::Write;
+    ///
+    /// let mut writer = Writer::new(vec![], 2.try_into().unwrap());
+    /// writer.write_all(&[b'A'; 60_000]).unwrap();
+    /// assert_eq!((writer.block_count(), writer.pending_bytes()), (0, 60_000));
+    ///
+    /// let record = [b'B'; 10_000];
+    /// if writer.pending_bytes() + record.len() > BGZF_BLOCK_SIZE {
+    ///     writer.flush_block().unwrap();
+    /// }
+    /// writer.write_all(&record).unwrap();
+    /// assert_eq!((writer.block_count(), writer.pending_bytes()), (1, 10_000));
+    /// ```
+    pub fn pending_bytes(&self) -> usize {
+        self.uncompressed_buffer.len()
+    }
+
+    /// Compress the bytes buffered so far into a block of their own, so that the next write starts
+    /// a new block.
+    ///
+    /// Unlike [`Write::flush`], this neither writes an empty block after it nor flushes the inner
+    /// writer. Does nothing if no bytes are buffered.
+    pub fn flush_block(&mut self) -> io::Result<()> {
+        if !self.uncompressed_buffer.is_empty() {
+            self.write_buffered(self.uncompressed_buffer.len())?;
+        }
+        Ok(())
+    }
+
+    /// Write all of `bytes`, compressing whole blocks straight from it rather than copying them
+    /// into the internal buffer.
+    ///
+    /// If bytes are already buffered, just enough of `bytes` is copied to complete that block.
+    /// Only the tail too short for a block of its own is copied and buffered, so for payloads of a
+    /// block or more this copies far less than [`Write::write_all`] might, which must buffer the
+    /// whole payload whenever anything is already buffered.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::{Reader, Writer, BGZF_BLOCK_SIZE};
+    /// use bytes::Bytes;
+    /// use std::io::{Read, Write};
+    ///
+    /// let mut writer = Writer::new(vec![], 2.try_into().unwrap());
+    /// writer.write_all(b"header\n").unwrap();
+    /// writer.write_bytes(Bytes::from(vec![b'A'; 3 * BGZF_BLOCK_SIZE])).unwrap();
+    /// assert_eq!((writer.block_count(), writer.pending_bytes()), (3, 7));
+    ///
+    /// let (compressed, _) = writer.finish().unwrap();
+    /// let mut decompressed = vec![];
+    /// Reader::new(compressed.as_slice()).read_to_end(&mut decompressed).unwrap();
+    /// assert_eq!(decompressed.len(), 3 * BGZF_BLOCK_SIZE + 7);
+    /// ```
+    pub fn write_bytes(&mut self, mut bytes: Bytes) -> io::Result<()> {
+        if !self.uncompressed_buffer.is_empty() && bytes.len() >= self.blocksize {
+            let fill = self.blocksize.saturating_sub(self.uncompressed_buffer.len());
+            self.uncompressed_buffer.extend_from_slice(&bytes.split_to(fill));
+            while self.uncompressed_buffer.len() >= self.blocksize {
+                let len =
+                    Self::block_len(&mut self.boundary, &self.uncompressed_buffer, self.blocksize);
+                if let Err(e) = self.write_buffered(len) {
+                    self.uncompressed_buffer.extend_from_slice(&bytes);
+                    return Err(e);
+                }
+            }
+        }
+        if self.uncompressed_buffer.is_empty() {
+            while bytes.len() >= self.blocksize {
+                let block =
+                    bytes.split_to(Self::block_len(&mut self.boundary, &bytes, self.blocksize));
+                if let Err(e) = self.write_block(&block) {
+                    // Keep everything not yet written, as `write_buffered` would.
+                    self.uncompressed_buffer.extend_from_slice(&block);
+                    self.uncompressed_buffer.extend_from_slice(&bytes);
+                    return Err(e);
+                }
+            }
+        }
+        self.write_all(&bytes)
+    }
+
+    /// Start building an [`Index`] of the blocks written from here on.
+    pub(crate) fn build_index(&mut self) {
+        self.index = Some(Index::new());
+    }
+
+    /// Take the [`Index`] started by [`Writer::build_index`].
+    pub(crate) fn take_index(&mut self) -> Option<Index> {
+        self.index.take()
+    }
+}
+
+/// Combine the CRC32 in the footer of `block` into `stream_crc`, if enabled.
+fn combine_block_crc(stream_crc: &mut Option<u32>, block: &[u8]) -> io::Result<()> {
+    if let Some(crc) = stream_crc.as_mut() {
+        let footer =
+            BlockFooter::from_block(block).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        *crc = crc32_combine(*crc, footer.crc32, footer.uncompressed_size.into());
+    }
+    Ok(())
+}
+
+/// Totals for everything written by a [`Writer`], returned by [`Writer::finish`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Summary {
+    /// The number of blocks written, excluding EOF blocks.
+    pub blocks: u64,
+    /// The number of uncompressed bytes written.
+    pub uncompressed_bytes: u64,
+    /// The number of compressed bytes written, including EOF blocks.
+    pub compressed_bytes: u64,
+    /// The CRC32 of the uncompressed stream, if enabled with [`Writer::set_stream_checksum`].
+    pub crc32: Option<u32>,
+    /// The digest of the uncompressed stream, if set with [`Writer::set_digest`].
+    pub digest: Option<Vec<u8>>,
+}
+
+/// When [`Writer`]'s [`Write::flush`] writes an empty block, set with [`Writer::set_flush_marker`].
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum FlushMarker {
+    /// After the buffered data, if there is any
+    #[default]
+    AfterData,
+    /// On every flush, even when nothing is buffered
+    Always,
 }
 
 impl Writer<File> {
@@ -83,49 +735,355 @@ impl Writer<File> {
         // TODO: benchmark whether there is any benefit to using a BufWriter
         File::create(path).map(|f| Self::new(f, compression_level))
     }
+
+    /// Flush all buffered data as by [`Write::flush`], then sync the file's data and metadata to
+    /// disk with [`File::sync_all`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bgzf::{decompress_all, Writer};
+    /// use std::io::Write;
+    ///
+    /// let dir = tempfile::tempdir().unwrap();
+    /// let path = dir.path().join("example.gz");
+    /// let mut writer = Writer::from_path(&path, 2.try_into().unwrap()).unwrap();
+    /// writer.write_all(b"hello world").unwrap();
+    /// writer.sync_all().unwrap();
+    /// assert_eq!(decompress_all(&std::fs::read(&path).unwrap()).unwrap(), b"hello world");
+    /// ```
+    pub fn sync_all(&mut self) -> io::Result<()> {
+        self.flush()?;
+        self.inner().sync_all()
+    }
+
+    /// Flush all buffered data as by [`Write::flush`], then sync the file's data to disk with
+    /// [`File::sync_data`], skipping metadata that isn't needed to read it back.
+    pub fn sync_data(&mut self) -> io::Result<()> {
+        self.flush()?;
+        self.inner().sync_data()
+    }
 }
 
 impl<W> Write for Writer<W>
 where
-    W: Write,
+    W: BlockSink,
 {
     /// Write a buffer into this writer, returning how many bytes were written.
+    ///
+    /// While nothing is buffered, whole blocks are compressed straight from `buf` without being
+    /// copied into the buffer first.
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        self.uncompressed_buffer.extend_from_slice(buf);
+        let mut rest = buf;
+        if self.uncompressed_buffer.is_empty() {
+            while rest.len() >= self.blocksize {
+                let (block, remaining) =
+                    rest.split_at(Self::block_len(&mut self.boundary, rest, self.blocksize));
+                if let Err(e) = self.write_block(block) {
+                    // Keep everything not yet written, as `write_buffered` would.
+                    self.uncompressed_buffer.extend_from_slice(rest);
+                    return Err(e);
+                }
+                rest = remaining;
+            }
+        }
+        self.uncompressed_buffer.extend_from_slice(rest);
         while self.uncompressed_buffer.len() >= self.blocksize {
-            let b = self.uncompressed_buffer.split_to(self.blocksize).freeze();
-            self.compressor
-                .compress(&b[..], &mut self.compressed_buffer)
-                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-            self.writer.write_all(&self.compressed_buffer)?;
-            self.compressed_buffer.clear();
+            let len =
+                Self::block_len(&mut self.boundary, &self.uncompressed_buffer, self.blocksize);
+            self.write_buffered(len)?;
         }
+        self.auto_flush()?;
         Ok(buf.len())
     }
 
     /// Flush this output stream, ensuring all intermediately buffered contents are sent.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(pending_len = self.uncompressed_buffer.len())
+        )
+    )]
     fn flush(&mut self) -> std::io::Result<()> {
+        let mut marked = false;
         while !self.uncompressed_buffer.is_empty() {
-            let b = self
-                .uncompressed_buffer
-                .split_to(std::cmp::min(self.uncompressed_buffer.len(), MAX_BGZF_BLOCK_SIZE))
-                .freeze();
-            self.compressor
-                .compress(&b[..], &mut self.compressed_buffer)
-                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-            self.writer.write_all(&self.compressed_buffer)?;
-            self.compressed_buffer.clear();
-            self.writer.write_all(BGZF_EOF)?; // this is an empty block
-        }
-        self.writer.flush()
+            self.write_buffered(std::cmp::min(
+                self.uncompressed_buffer.len(),
+                MAX_BGZF_BLOCK_SIZE,
+            ))?;
+            self.write_eof_block()?; // this is an empty block
+            marked = true;
+        }
+        if !marked && self.flush_marker == FlushMarker::Always {
+            self.write_eof_block()?;
+        }
+        self.inner().flush_blocks()
     }
 }
 
 impl<W> Drop for Writer<W>
 where
-    W: Write,
+    W: BlockSink,
 {
     fn drop(&mut self) {
-        self.flush().unwrap();
+        // Errors can't be reported from drop, call `finish` to observe them.
+        if self.writer.is_some() {
+            let _ = self.flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Read;
+
+    use super::*;
+    use crate::{
+        compress_parallel, core::block_size, decompress_all, test::test_input, ChecksumManifest,
+        Reader, BGZF_MAGIC_BYTE_A, BGZF_MAGIC_BYTE_B,
+    };
+
+    #[test]
+    fn test_writer_block_callback() {
+        let input = test_input(200_000);
+        let blocks = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+
+        let mut output = vec![];
+        let mut writer =
+            Writer::with_capacity(&mut output, CompressionLevel::new(3).unwrap(), 1000);
+        let sink = std::sync::Arc::clone(&blocks);
+        writer.set_block_callback(move |block| sink.lock().unwrap().push(block));
+        writer.write_all(&input).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+
+        let blocks = blocks.lock().unwrap();
+        assert_eq!(blocks.len(), 200);
+        let mut uncompressed_offset = 0;
+        for block in blocks.iter() {
+            let start = block.compressed_offset as usize;
+            assert_eq!(&output[start..start + 2], &[BGZF_MAGIC_BYTE_A, BGZF_MAGIC_BYTE_B]);
+            assert_eq!(block_size(&output[start..]).unwrap(), block.compressed_size);
+            assert_eq!(block.uncompressed_offset, uncompressed_offset);
+            uncompressed_offset += block.uncompressed_size as u64;
+        }
+        assert_eq!(uncompressed_offset, input.len() as u64);
+    }
+
+    #[test]
+    fn test_writer_finish_summary() {
+        let input = test_input(BGZF_BLOCK_SIZE * 3);
+        let mut writer = Writer::new(vec![], CompressionLevel::new(3).unwrap());
+        writer.set_stream_checksum(true);
+        writer.write_all(&input).unwrap();
+        let (compressed, summary) = writer.finish().unwrap();
+
+        let mut crc = libdeflater::Crc::new();
+        crc.update(&input);
+        assert_eq!(summary.blocks, 3);
+        assert_eq!(summary.uncompressed_bytes, input.len() as u64);
+        assert_eq!(summary.compressed_bytes, compressed.len() as u64);
+        assert_eq!(summary.crc32, Some(crc.sum()));
+        // A whole number of blocks still gets its EOF marker.
+        assert!(compressed.ends_with(BGZF_EOF));
+        assert_eq!(decompress_all(&compressed).unwrap(), input);
+    }
+
+    /// A sink that accepts whole writes until `capacity` bytes have been written.
+    struct LimitedSink {
+        written: Vec<u8>,
+        capacity: usize,
+    }
+
+    impl Write for LimitedSink {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.written.len() + buf.len() > self.capacity {
+                return Err(io::Error::new(io::ErrorKind::Other, "sink full"));
+            }
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_writer_finish_reports_sink_errors() {
+        let mut sink = LimitedSink { written: vec![], capacity: 0 };
+        let mut writer = Writer::new(&mut sink, CompressionLevel::new(3).unwrap());
+        writer.write_all(b"hello world").unwrap();
+        assert_eq!(writer.finish().err().unwrap().kind(), io::ErrorKind::Other);
+
+        // Dropping a writer that can't flush doesn't panic either.
+        let mut writer = Writer::new(&mut sink, CompressionLevel::new(3).unwrap());
+        writer.write_all(b"hello world").unwrap();
+        drop(writer);
+        assert!(sink.written.is_empty());
+    }
+
+    #[test]
+    fn test_writer_into_pending() {
+        let input = test_input(1_000_000);
+        let mut sink = LimitedSink { written: vec![], capacity: 8_000 };
+        let mut writer = Writer::new(&mut sink, CompressionLevel::new(3).unwrap());
+        let mut written = 0;
+        for chunk in input.chunks(10_000) {
+            written += chunk.len();
+            if writer.write_all(chunk).is_err() {
+                break;
+            }
+        }
+        assert!(written < input.len());
+        let pending = writer.into_pending();
+
+        assert!(!sink.written.is_empty());
+        let mut recovered = decompress_all(&sink.written).unwrap();
+        recovered.extend_from_slice(&pending);
+        assert_eq!(recovered, &input[..written]);
+    }
+
+    #[test]
+    fn test_writer_large_writes() {
+        let input = test_input(1_000_000);
+        let level = CompressionLevel::new(3).unwrap();
+        let mut writer = Writer::new(vec![], level);
+        writer.write_all(&input[..100]).unwrap();
+        writer.write_all(&input[100..]).unwrap();
+        assert_eq!(writer.finish().unwrap().0, compress_parallel(&input, level).unwrap());
+
+        // A failure partway through a large write keeps everything not yet written.
+        let mut sink = LimitedSink { written: vec![], capacity: 8_000 };
+        let mut writer = Writer::new(&mut sink, level);
+        assert!(writer.write_all(&input).is_err());
+        let pending = writer.into_pending();
+        let mut recovered = decompress_all(&sink.written).unwrap();
+        recovered.extend_from_slice(&pending);
+        assert_eq!(recovered, input);
+    }
+
+    #[test]
+    fn test_writer_auto_flush_after_delay() {
+        let mut writer = Writer::new(vec![], CompressionLevel::new(3).unwrap());
+        writer.set_auto_flush(None, Some(std::time::Duration::from_millis(50)));
+        writer.write_all(b"first record\n").unwrap();
+        assert_eq!(writer.pending_bytes(), 13);
+        std::thread::sleep(std::time::Duration::from_millis(60));
+        writer.write_all(b"second record\n").unwrap();
+        assert_eq!((writer.block_count(), writer.pending_bytes()), (1, 0));
+        writer.write_all(b"third record\n").unwrap();
+        assert_eq!((writer.block_count(), writer.pending_bytes()), (1, 13));
+
+        let (compressed, _) = writer.finish().unwrap();
+        let data = decompress_all(&compressed).unwrap();
+        assert_eq!(data, b"first record\nsecond record\nthird record\n");
+    }
+
+    #[test]
+    fn test_flush_marker() {
+        let mut writer = Writer::new(vec![], CompressionLevel::new(3).unwrap());
+        writer.write_all(b"hello").unwrap();
+        writer.flush().unwrap();
+        writer.flush().unwrap();
+        writer.set_flush_marker(FlushMarker::Always);
+        writer.write_all(b" world").unwrap();
+        writer.flush().unwrap();
+        writer.flush().unwrap();
+        let (compressed, summary) = writer.finish().unwrap();
+        assert_eq!(summary.blocks, 2);
+
+        let checksums = ChecksumManifest::from_reader(io::Cursor::new(&compressed)).unwrap();
+        let sizes: Vec<u32> = checksums.blocks().iter().map(|b| b.uncompressed_size).collect();
+        assert_eq!(sizes, [5, 0, 6, 0, 0]);
+        assert_eq!(decompress_all(&compressed).unwrap(), b"hello world");
+    }
+
+    #[test]
+    fn test_line_aligned_blocks() {
+        let mut input = vec![];
+        for i in 0..100_000_u32 {
+            input.extend_from_slice(
+                format!("read{}\t{}\n", i, "ACGT".repeat((i % 7) as usize)).as_bytes(),
+            );
+        }
+        // A line longer than a block has to be cut.
+        input.extend(std::iter::repeat(b'N').take(BGZF_BLOCK_SIZE + 10));
+        input.push(b'\n');
+        input.extend_from_slice(b"last\n");
+
+        let mut writer = Writer::new(vec![], CompressionLevel::new(3).unwrap());
+        writer.set_line_aligned(true);
+        // Write both straight from large buffers and through the buffer in small pieces.
+        let (head, tail) = input.split_at(input.len() / 2);
+        writer.write_all(head).unwrap();
+        for chunk in tail.chunks(1000) {
+            writer.write_all(chunk).unwrap();
+        }
+        let (compressed, _) = writer.finish().unwrap();
+        assert_eq!(decompress_all(&compressed).unwrap(), input);
+
+        let manifest = ChecksumManifest::from_reader(std::io::Cursor::new(&compressed)).unwrap();
+        let mut rest = input.as_slice();
+        let mut cut = 0;
+        for block in manifest.blocks() {
+            let (data, remaining) = rest.split_at(block.uncompressed_size as usize);
+            rest = remaining;
+            if !data.is_empty() && !data.ends_with(b"\n") {
+                assert_eq!(data, vec![b'N'; BGZF_BLOCK_SIZE]);
+                cut += 1;
+            }
+        }
+        assert_eq!(cut, 1);
+    }
+
+    #[cfg(all(feature = "digest", feature = "sha256"))]
+    #[test]
+    fn test_writer_digest() {
+        use sha2::{Digest, Sha256};
+
+        let input = test_input(300_000);
+        let mut writer = Writer::new(vec![], CompressionLevel::new(3).unwrap());
+        writer.set_digest(Sha256::new());
+        // Flushes and blocks written straight from the input are all digested.
+        writer.write_all(&input[..1000]).unwrap();
+        writer.flush().unwrap();
+        writer.write_all(&input[1000..]).unwrap();
+        let (compressed, summary) = writer.finish().unwrap();
+
+        assert_eq!(decompress_all(&compressed).unwrap(), input);
+        assert_eq!(summary.digest.unwrap(), Sha256::digest(&input).to_vec());
+    }
+
+    #[test]
+    fn test_writer_write_bytes() {
+        let input = test_input(500_000);
+        let level = CompressionLevel::new(3).unwrap();
+        let chunks = [0, 10, 100_000, 5, 65_280, 65_270, 200_000, 69_435];
+        assert_eq!(chunks.iter().sum::<usize>(), input.len());
+
+        let mut expected = Writer::new(vec![], level);
+        let mut writer = Writer::new(vec![], level);
+        let bytes = bytes::Bytes::from(input.clone());
+        let mut start = 0;
+        for len in chunks {
+            expected.write_all(&input[start..start + len]).unwrap();
+            writer.write_bytes(bytes.slice(start..start + len)).unwrap();
+            assert_eq!(writer.pending_bytes(), expected.pending_bytes());
+            start += len;
+        }
+        assert_eq!(writer.finish().unwrap().0, expected.finish().unwrap().0);
+
+        let mut writer = Writer::new(vec![], level);
+        writer.set_line_aligned(true);
+        let lines = bytes::Bytes::from(b"ACGT\n".repeat(40_000));
+        writer.write_bytes(lines.slice(..3)).unwrap();
+        writer.write_bytes(lines.slice(3..)).unwrap();
+        let (compressed, _) = writer.finish().unwrap();
+        let mut decompressed = vec![];
+        Reader::new(compressed.as_slice()).read_to_end(&mut decompressed).unwrap();
+        assert_eq!(decompressed, lines);
     }
 }